@@ -15,22 +15,77 @@
 */
 
 mod py_classes;
+mod py_errors;
 mod py_functions;
+mod py_interrupt;
 
-use py_classes::{IpAddress, ScpiMessenger, ScpiNetworkMode};
+use py_classes::{
+    IpAddress, ScpiCommand, ScpiCommandTree, ScpiCompletionMethod, ScpiCompoundCommand,
+    ScpiConnectionOptions, ScpiDutyCycleHandle, ScpiErrorCheckMode, ScpiEventStatus,
+    ScpiIdentification, ScpiInstrumentResult, ScpiMessenger, ScpiMnemonicForm, ScpiMockInstrument,
+    ScpiNetworkMode, ScpiRunStatistics, ScpiSequence, ScpiSerialPort, ScpiStatusByte,
+    ScpiStatusRegister, ScpiStatusRegisterGroup, ScpiStopHandle, ScpiTcpMulticastGroup,
+    ScpiTimingStrategy,
+};
+use py_errors::{
+    ScpiConnectionError, ScpiException, ScpiGroupError, ScpiInstrumentError,
+    ScpiInvalidArgumentError, ScpiParseError, ScpiTimeoutError, ScpiUnsupportedError,
+};
 use py_functions::{
-    send_dutycycled_message, send_list_of_messages, send_message, send_repeated_message,
+    parse_response, query_message, query_resource_message, send_dutycycled_message,
+    send_list_of_messages, send_list_of_messages_to_group, send_message, send_repeated_message,
+    send_resource_message,
 };
 use pyo3::prelude::*;
 
 #[pymodule]
-fn py_scpi(_py: Python, m: &PyModule) -> PyResult<()> {
+fn py_scpi(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(send_message, m)?)?;
+    m.add_function(wrap_pyfunction!(query_message, m)?)?;
     m.add_function(wrap_pyfunction!(send_list_of_messages, m)?)?;
     m.add_function(wrap_pyfunction!(send_repeated_message, m)?)?;
     m.add_function(wrap_pyfunction!(send_dutycycled_message, m)?)?;
+    m.add_function(wrap_pyfunction!(send_list_of_messages_to_group, m)?)?;
+    m.add_function(wrap_pyfunction!(send_resource_message, m)?)?;
+    m.add_function(wrap_pyfunction!(query_resource_message, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_response, m)?)?;
     m.add_class::<ScpiNetworkMode>()?;
+    m.add_class::<ScpiSerialPort>()?;
+    m.add_class::<ScpiConnectionOptions>()?;
+    m.add_class::<ScpiErrorCheckMode>()?;
+    m.add_class::<ScpiTimingStrategy>()?;
     m.add_class::<ScpiMessenger>()?;
+    m.add_class::<ScpiCommand>()?;
+    m.add_class::<ScpiCompoundCommand>()?;
+    m.add_class::<ScpiCommandTree>()?;
+    m.add_class::<ScpiMnemonicForm>()?;
+    m.add_class::<ScpiIdentification>()?;
+    m.add_class::<ScpiEventStatus>()?;
+    m.add_class::<ScpiStatusByte>()?;
+    m.add_class::<ScpiStatusRegisterGroup>()?;
+    m.add_class::<ScpiStatusRegister>()?;
+    m.add_class::<ScpiCompletionMethod>()?;
+    m.add_class::<ScpiTcpMulticastGroup>()?;
+    m.add_class::<ScpiInstrumentResult>()?;
+    m.add_class::<ScpiStopHandle>()?;
+    m.add_class::<ScpiDutyCycleHandle>()?;
+    m.add_class::<ScpiRunStatistics>()?;
+    m.add_class::<ScpiSequence>()?;
+    m.add_class::<ScpiMockInstrument>()?;
     m.add_class::<IpAddress>()?;
+    m.add("ScpiException", py.get_type::<ScpiException>())?;
+    m.add("ScpiConnectionError", py.get_type::<ScpiConnectionError>())?;
+    m.add("ScpiTimeoutError", py.get_type::<ScpiTimeoutError>())?;
+    m.add(
+        "ScpiUnsupportedError",
+        py.get_type::<ScpiUnsupportedError>(),
+    )?;
+    m.add("ScpiInstrumentError", py.get_type::<ScpiInstrumentError>())?;
+    m.add("ScpiParseError", py.get_type::<ScpiParseError>())?;
+    m.add(
+        "ScpiInvalidArgumentError",
+        py.get_type::<ScpiInvalidArgumentError>(),
+    )?;
+    m.add("ScpiGroupError", py.get_type::<ScpiGroupError>())?;
     Ok(())
 }
@@ -14,13 +14,36 @@
    limitations under the License.
 */
 
-use pyo3::{pyclass, pymethods};
+use pyo3::types::PyBytes;
+use pyo3::{pyclass, pymethods, FromPyObject, IntoPy, PyObject, PyRef, PyResult, Python};
 use std::net::AddrParseError;
-use std::{io::Error, net::IpAddr, str::FromStr};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
 
-use scpi::duty_cycle::DutyCycleMessage;
+use scpi::block::ByteOrder;
+use scpi::command::{ChannelList, Command, CompoundCommand, Parameter};
+use scpi::command_tree::{CommandTree, MnemonicForm};
+use scpi::common::{CompletionMethod, CompletionOptions, Identification, StandardEventStatus};
+use scpi::connection_options::{ConnectionOptions, MulticastOptions};
+use scpi::duty_cycle::{DutyCycleHandle, DutyCycleMessage};
+use scpi::error::ScpiError;
+use scpi::error_queue::{ErrorCheckMode, InstrumentError};
 use scpi::messenger::Messenger;
-use scpi::networking::NetworkMode;
+use scpi::mock::{MockInstrument, MockScript};
+use scpi::networking::{DataBits, FlowControl, NetworkMode, Parity, SerialConfig, StopBits};
+use scpi::response::{parse_channel_list, parse_value, ResponseValue};
+use scpi::run_control::{RunLimits, RunStatistics, StopHandle, StopReason};
+use scpi::sequence::Sequence;
+use scpi::status::{StatusByte, StatusRegister, StatusRegisterGroup};
+use scpi::tcp_multicast::{InstrumentResult, TcpMulticastGroup};
+use scpi::timing::{TimingReport, TimingStrategy};
+
+use crate::py_errors::PyScpiError;
+use crate::py_interrupt::run_interruptible;
 
 #[derive(Clone)]
 #[pyclass]
@@ -29,26 +52,220 @@ pub enum ScpiNetworkMode {
     Tcp,
     UdpMulticast,
     TcpMulticast,
+    Hislip,
+    Serial,
 }
 
 #[pymethods]
 impl ScpiNetworkMode {
     #[new]
-    fn new(mode: u8) -> Result<ScpiNetworkMode, Error> {
+    fn new(mode: u8) -> Result<ScpiNetworkMode, PyScpiError> {
         match mode {
             0 => Ok(Self::Udp),
             1 => Ok(Self::Tcp),
             2 => Ok(Self::UdpMulticast),
             3 => Ok(Self::TcpMulticast),
-            _ => {
-                const MSG: &str = "Not a valid enum in range [0, 4]";
-                eprintln!("{}", MSG);
-                Err(Error::new(std::io::ErrorKind::NotFound, MSG))
-            }
+            4 => Ok(Self::Hislip),
+            5 => Ok(Self::Serial),
+            _ => Err(
+                ScpiError::InvalidArgument("Not a valid enum in range [0, 6)".to_string()).into(),
+            ),
+        }
+    }
+}
+
+impl ScpiNetworkMode {
+    /// Python enums cannot hold data, so `TcpMulticast` takes its instruments from `group` and
+    /// `Serial` its port from `serial`.
+    pub fn to_network_mode(
+        &self,
+        group: Option<&[PyRef<IpAddress>]>,
+        serial: Option<&ScpiSerialPort>,
+    ) -> Result<NetworkMode, PyScpiError> {
+        match self {
+            Self::Udp => Ok(NetworkMode::Udp),
+            Self::Tcp => Ok(NetworkMode::Tcp),
+            Self::UdpMulticast => Ok(NetworkMode::UdpMulticast),
+            Self::TcpMulticast => match group {
+                Some(x) => Ok(NetworkMode::TcpMulticast {
+                    members: x.iter().map(|x| x.address).collect(),
+                    parallel: true,
+                }),
+                None => Err(ScpiError::InvalidArgument(
+                    "TcpMulticast needs a group of instruments to send to".to_string(),
+                )
+                .into()),
+            },
+            Self::Hislip => Ok(NetworkMode::Hislip),
+            Self::Serial => match serial {
+                Some(x) => Ok(NetworkMode::Serial {
+                    path: x.path.clone(),
+                    config: x.config,
+                }),
+                None => Err(ScpiError::InvalidArgument(
+                    "Serial needs a serial port to talk to".to_string(),
+                )
+                .into()),
+            },
+        }
+    }
+}
+
+/// The port used with `ScpiNetworkMode.Serial`.
+#[derive(Clone)]
+#[pyclass]
+pub struct ScpiSerialPort {
+    path: String,
+    config: SerialConfig,
+}
+
+#[pymethods]
+impl ScpiSerialPort {
+    /// `parity` is one of "N", "E" or "O"; `flow_control` is "none", "software" or "hardware".
+    #[new]
+    #[pyo3(signature = (
+        path,
+        baud_rate=9600,
+        data_bits=8,
+        parity="N",
+        stop_bits=1,
+        flow_control="none"
+    ))]
+    fn new(
+        path: String,
+        baud_rate: u32,
+        data_bits: u8,
+        parity: &str,
+        stop_bits: u8,
+        flow_control: &str,
+    ) -> Result<Self, PyScpiError> {
+        let config: SerialConfig =
+            serial_config(baud_rate, data_bits, parity, stop_bits, flow_control)?;
+
+        Ok(Self { path, config })
+    }
+}
+
+fn serial_config(
+    baud_rate: u32,
+    data_bits: u8,
+    parity: &str,
+    stop_bits: u8,
+    flow_control: &str,
+) -> Result<SerialConfig, PyScpiError> {
+    let data_bits: DataBits = match data_bits {
+        5 => DataBits::Five,
+        6 => DataBits::Six,
+        7 => DataBits::Seven,
+        8 => DataBits::Eight,
+        _ => {
+            return Err(
+                ScpiError::InvalidArgument(format!("Unsupported data bits {}", data_bits)).into(),
+            )
+        }
+    };
+
+    let parity: Parity = match parity {
+        "N" => Parity::None,
+        "E" => Parity::Even,
+        "O" => Parity::Odd,
+        _ => {
+            return Err(
+                ScpiError::InvalidArgument(format!("Unsupported parity {:?}", parity)).into(),
+            )
+        }
+    };
+
+    let stop_bits: StopBits = match stop_bits {
+        1 => StopBits::One,
+        2 => StopBits::Two,
+        _ => {
+            return Err(
+                ScpiError::InvalidArgument(format!("Unsupported stop bits {}", stop_bits)).into(),
+            )
+        }
+    };
+
+    let flow_control: FlowControl = match flow_control {
+        "none" => FlowControl::None,
+        "software" => FlowControl::Software,
+        "hardware" => FlowControl::Hardware,
+        _ => {
+            return Err(ScpiError::InvalidArgument(format!(
+                "Unsupported flow control {:?}",
+                flow_control
+            ))
+            .into())
+        }
+    };
+
+    Ok(SerialConfig::new(
+        baud_rate,
+        data_bits,
+        parity,
+        stop_bits,
+        flow_control,
+    ))
+}
+
+#[derive(Clone)]
+#[pyclass]
+pub enum ScpiErrorCheckMode {
+    Disabled,
+    AfterEachCommand,
+    AfterList,
+}
+
+#[derive(Clone)]
+#[pyclass]
+pub enum ScpiMnemonicForm {
+    AsWritten,
+    Short,
+    Long,
+}
+
+impl From<&ScpiMnemonicForm> for MnemonicForm {
+    fn from(form: &ScpiMnemonicForm) -> Self {
+        match form {
+            ScpiMnemonicForm::AsWritten => MnemonicForm::AsWritten,
+            ScpiMnemonicForm::Short => MnemonicForm::Short,
+            ScpiMnemonicForm::Long => MnemonicForm::Long,
         }
     }
 }
 
+#[derive(Clone)]
+#[pyclass]
+pub enum ScpiCompletionMethod {
+    OperationCompleteQuery,
+    EventStatusPolling,
+    StatusBytePolling,
+}
+
+#[derive(Clone)]
+#[pyclass]
+pub enum ScpiStatusRegisterGroup {
+    Operation,
+    Questionable,
+}
+
+impl From<&ScpiStatusRegisterGroup> for StatusRegisterGroup {
+    fn from(group: &ScpiStatusRegisterGroup) -> Self {
+        match group {
+            ScpiStatusRegisterGroup::Operation => Self::Operation,
+            ScpiStatusRegisterGroup::Questionable => Self::Questionable,
+        }
+    }
+}
+
+#[derive(Clone)]
+#[pyclass]
+pub enum ScpiTimingStrategy {
+    Spin,
+    Hybrid,
+    AbsoluteDeadline,
+}
+
 #[pyclass]
 pub struct IpAddress {
     pub address: IpAddr,
@@ -65,6 +282,53 @@ impl IpAddress {
     }
 }
 
+#[derive(Clone)]
+#[pyclass]
+pub struct ScpiConnectionOptions {
+    pub options: ConnectionOptions,
+}
+
+#[pymethods]
+impl ScpiConnectionOptions {
+    #[new]
+    #[pyo3(signature = (
+        write_terminator="\r\n",
+        read_terminator="\n",
+        read_timeout_ms=None,
+        write_timeout_ms=None,
+        connect_timeout_ms=None,
+        trim=true
+    ))]
+    pub fn new(
+        write_terminator: &str,
+        read_terminator: &str,
+        read_timeout_ms: Option<u64>,
+        write_timeout_ms: Option<u64>,
+        connect_timeout_ms: Option<u64>,
+        trim: bool,
+    ) -> Self {
+        let options: ConnectionOptions = ConnectionOptions::new()
+            .with_write_terminator(write_terminator)
+            .with_read_terminator(read_terminator)
+            .with_read_timeout(read_timeout_ms.map(Duration::from_millis))
+            .with_write_timeout(write_timeout_ms.map(Duration::from_millis))
+            .with_connect_timeout(connect_timeout_ms.map(Duration::from_millis))
+            .with_trim(trim);
+
+        Self { options }
+    }
+
+    /// `hops` is the IPv4 TTL or IPv6 hop limit; an `interface_index` of 0 lets the OS choose.
+    #[pyo3(signature = (interface_index=0, hops=None, loopback=true))]
+    fn set_multicast(&mut self, interface_index: u32, hops: Option<u32>, loopback: bool) {
+        let multicast: MulticastOptions = MulticastOptions::new()
+            .with_interface_index(interface_index)
+            .with_hops(hops)
+            .with_loopback(loopback);
+        self.options = self.options.clone().with_multicast(multicast);
+    }
+}
+
 #[pyclass]
 pub struct ScpiMessenger {
     inner: Messenger,
@@ -73,45 +337,1254 @@ pub struct ScpiMessenger {
 #[pymethods]
 impl ScpiMessenger {
     #[new]
+    /// With `ScpiNetworkMode.TcpMulticast`, messages go to each address in `group` instead, and
+    /// with `ScpiNetworkMode.Serial` to the `serial` port.
+    #[pyo3(signature = (
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        options=None,
+        group=None,
+        serial=None
+    ))]
     fn new(
         local_port: u16,
         remote_port: u16,
         remote_client: &IpAddress,
         mode: ScpiNetworkMode,
-    ) -> Result<Self, Error> {
-        let scpi_mode: NetworkMode = match mode {
-            ScpiNetworkMode::Udp => NetworkMode::Udp,
-            ScpiNetworkMode::Tcp => NetworkMode::Tcp,
-            ScpiNetworkMode::UdpMulticast => NetworkMode::UdpMulticast,
-            ScpiNetworkMode::TcpMulticast => NetworkMode::TcpMulticast,
+        options: Option<&ScpiConnectionOptions>,
+        group: Option<Vec<PyRef<IpAddress>>>,
+        serial: Option<&ScpiSerialPort>,
+    ) -> Result<Self, PyScpiError> {
+        let scpi_mode: NetworkMode = mode.to_network_mode(group.as_deref(), serial)?;
+
+        let connection_options: ConnectionOptions = match options {
+            Some(x) => x.options.clone(),
+            None => ConnectionOptions::default(),
+        };
+
+        let inner: Messenger = Messenger::new(
+            local_port,
+            remote_port,
+            &remote_client.address,
+            &scpi_mode,
+            &connection_options,
+        )?;
+
+        Ok(Self { inner })
+    }
+
+    /// `parity` is one of "N", "E" or "O"; `flow_control` is "none", "software" or "hardware".
+    #[staticmethod]
+    #[pyo3(signature = (
+        path,
+        baud_rate=9600,
+        data_bits=8,
+        parity="N",
+        stop_bits=1,
+        flow_control="none",
+        options=None
+    ))]
+    fn open_serial(
+        path: &str,
+        baud_rate: u32,
+        data_bits: u8,
+        parity: &str,
+        stop_bits: u8,
+        flow_control: &str,
+        options: Option<&ScpiConnectionOptions>,
+    ) -> Result<Self, PyScpiError> {
+        let config: SerialConfig =
+            serial_config(baud_rate, data_bits, parity, stop_bits, flow_control)?;
+
+        let connection_options: ConnectionOptions = match options {
+            Some(x) => x.options.clone(),
+            None => ConnectionOptions::default(),
+        };
+
+        let inner: Messenger = Messenger::new_serial(path, &config, &connection_options)?;
+
+        Ok(Self { inner })
+    }
+
+    /// e.g. "TCPIP0::192.168.1.70::5025::SOCKET" or "TCPIP::host::hislip0::INSTR".
+    #[staticmethod]
+    #[pyo3(signature = (resource, options=None))]
+    fn open(resource: &str, options: Option<&ScpiConnectionOptions>) -> Result<Self, PyScpiError> {
+        let connection_options: ConnectionOptions = match options {
+            Some(x) => x.options.clone(),
+            None => ConnectionOptions::default(),
+        };
+
+        let inner: Messenger = Messenger::open(resource, &connection_options)?;
+
+        Ok(Self { inner })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (remote_client, device_name="inst0", options=None))]
+    fn open_vxi11(
+        remote_client: &IpAddress,
+        device_name: &str,
+        options: Option<&ScpiConnectionOptions>,
+    ) -> Result<Self, PyScpiError> {
+        let connection_options: ConnectionOptions = match options {
+            Some(x) => x.options.clone(),
+            None => ConnectionOptions::default(),
         };
 
         let inner: Messenger =
-            Messenger::new(local_port, remote_port, &remote_client.address, &scpi_mode)?;
+            Messenger::new_vxi11(&remote_client.address, device_name, &connection_options)?;
 
         Ok(Self { inner })
     }
 
-    fn send_message(&mut self, message: &str) -> Result<usize, Error> {
-        self.inner.send_message(message)
+    fn send_message(&mut self, message: &str) -> Result<usize, PyScpiError> {
+        Ok(self.inner.send_message(message)?)
+    }
+
+    fn query(&mut self, message: &str) -> Result<String, PyScpiError> {
+        Ok(self.inner.query(message)?)
+    }
+
+    fn send_command(&mut self, command: &ScpiCommand) -> Result<usize, PyScpiError> {
+        Ok(self.inner.send_command(&command.inner)?)
+    }
+
+    fn query_command(&mut self, command: &ScpiCommand) -> Result<String, PyScpiError> {
+        Ok(self.inner.query_command(&command.inner)?)
+    }
+
+    fn query_int(&mut self, message: &str) -> Result<i64, PyScpiError> {
+        Ok(self.inner.query_as(message)?)
+    }
+
+    fn query_float(&mut self, message: &str) -> Result<f64, PyScpiError> {
+        Ok(self.inner.query_as(message)?)
+    }
+
+    fn query_bool(&mut self, message: &str) -> Result<bool, PyScpiError> {
+        Ok(self.inner.query_as(message)?)
+    }
+
+    /// Returns the contents of a quoted string response.
+    fn query_str(&mut self, message: &str) -> Result<String, PyScpiError> {
+        Ok(self.inner.query_as(message)?)
+    }
+
+    /// Returns every channel in a channel list response, with ranges expanded.
+    fn query_channel_list(&mut self, message: &str) -> Result<Vec<u32>, PyScpiError> {
+        let response: String = self.inner.query(message)?;
+        Ok(parse_channel_list(&response)?.channels())
+    }
+
+    /// Returns an int, float, str or list, depending on what the response looks like.
+    fn query_value(&mut self, py: Python, message: &str) -> Result<PyObject, PyScpiError> {
+        let response: String = self.inner.query(message)?;
+        Ok(response_value_to_py(py, &parse_value(&response)?))
+    }
+
+    /// Like `query_value`, but always returns a list, even for a single element.
+    fn query_list(&mut self, py: Python, message: &str) -> Result<PyObject, PyScpiError> {
+        let values: Vec<ResponseValue> = self.inner.query_as(message)?;
+        Ok(values
+            .iter()
+            .map(|x| response_value_to_py(py, x))
+            .collect::<Vec<PyObject>>()
+            .into_py(py))
+    }
+
+    fn query_with_timeout(
+        &mut self,
+        message: &str,
+        timeout_ms: u64,
+    ) -> Result<String, PyScpiError> {
+        Ok(self
+            .inner
+            .query_with_timeout(message, Duration::from_millis(timeout_ms))?)
+    }
+
+    fn clear(&mut self) -> Result<(), PyScpiError> {
+        Ok(self.inner.clear()?)
+    }
+
+    fn reset(&mut self) -> Result<(), PyScpiError> {
+        Ok(self.inner.reset()?)
+    }
+
+    fn clear_status(&mut self) -> Result<(), PyScpiError> {
+        Ok(self.inner.clear_status()?)
+    }
+
+    fn wait_to_continue(&mut self) -> Result<(), PyScpiError> {
+        Ok(self.inner.wait_to_continue()?)
+    }
+
+    fn query_operation_complete(&mut self) -> Result<bool, PyScpiError> {
+        Ok(self.inner.query_operation_complete()?)
+    }
+
+    fn self_test(&mut self) -> Result<i64, PyScpiError> {
+        Ok(self.inner.self_test()?)
+    }
+
+    fn query_event_status(&mut self) -> Result<ScpiEventStatus, PyScpiError> {
+        Ok(self.inner.query_event_status()?.into())
+    }
+
+    fn query_status_byte(&mut self) -> Result<ScpiStatusByte, PyScpiError> {
+        Ok(self.inner.query_status_byte()?.into())
+    }
+
+    fn identify(&mut self) -> Result<ScpiIdentification, PyScpiError> {
+        Ok(self.inner.identify()?.into())
+    }
+
+    /// Sends `message` and waits, with the GIL released, until the instrument has completed it.
+    #[pyo3(signature = (
+        message,
+        method=ScpiCompletionMethod::OperationCompleteQuery,
+        timeout_ms=10_000,
+        poll_interval_ms=50
+    ))]
+    fn send_and_wait(
+        &mut self,
+        py: Python,
+        message: &str,
+        method: ScpiCompletionMethod,
+        timeout_ms: u64,
+        poll_interval_ms: u64,
+    ) -> Result<(), PyScpiError> {
+        let options: CompletionOptions = completion_options(method, timeout_ms, poll_interval_ms);
+        let inner: &mut Messenger = &mut self.inner;
+        Ok(py.allow_threads(|| inner.send_and_wait(message, &options))?)
+    }
+
+    #[pyo3(signature = (
+        messages,
+        method=ScpiCompletionMethod::OperationCompleteQuery,
+        timeout_ms=10_000,
+        poll_interval_ms=50
+    ))]
+    fn send_list_of_messages_and_wait(
+        &mut self,
+        py: Python,
+        messages: Vec<&str>,
+        method: ScpiCompletionMethod,
+        timeout_ms: u64,
+        poll_interval_ms: u64,
+    ) -> Result<(), PyScpiError> {
+        let options: CompletionOptions = completion_options(method, timeout_ms, poll_interval_ms);
+        let inner: &mut Messenger = &mut self.inner;
+        Ok(py.allow_threads(|| inner.send_list_of_messages_and_wait(&messages, &options))?)
+    }
+
+    fn set_event_status_enable(&mut self, mask: u8) -> Result<(), PyScpiError> {
+        Ok(self.inner.set_event_status_enable(mask)?)
+    }
+
+    fn query_event_status_enable(&mut self) -> Result<u8, PyScpiError> {
+        Ok(self.inner.query_event_status_enable()?)
+    }
+
+    fn set_service_request_enable(&mut self, mask: u8) -> Result<(), PyScpiError> {
+        Ok(self.inner.set_service_request_enable(mask)?)
+    }
+
+    fn query_service_request_enable(&mut self) -> Result<u8, PyScpiError> {
+        Ok(self.inner.query_service_request_enable()?)
+    }
+
+    fn preset_status(&mut self) -> Result<(), PyScpiError> {
+        Ok(self.inner.preset_status()?)
+    }
+
+    fn query_status_condition(
+        &mut self,
+        group: ScpiStatusRegisterGroup,
+    ) -> Result<u16, PyScpiError> {
+        Ok(self
+            .inner
+            .query_status_condition(StatusRegisterGroup::from(&group))?)
+    }
+
+    fn query_status_event(&mut self, group: ScpiStatusRegisterGroup) -> Result<u16, PyScpiError> {
+        Ok(self
+            .inner
+            .query_status_event(StatusRegisterGroup::from(&group))?)
+    }
+
+    fn set_status_enable(
+        &mut self,
+        group: ScpiStatusRegisterGroup,
+        mask: u16,
+    ) -> Result<(), PyScpiError> {
+        Ok(self
+            .inner
+            .set_status_enable(StatusRegisterGroup::from(&group), mask)?)
+    }
+
+    fn query_status_enable(&mut self, group: ScpiStatusRegisterGroup) -> Result<u16, PyScpiError> {
+        Ok(self
+            .inner
+            .query_status_enable(StatusRegisterGroup::from(&group))?)
+    }
+
+    fn set_status_transitions(
+        &mut self,
+        group: ScpiStatusRegisterGroup,
+        positive: u16,
+        negative: u16,
+    ) -> Result<(), PyScpiError> {
+        Ok(self.inner.set_status_transitions(
+            StatusRegisterGroup::from(&group),
+            positive,
+            negative,
+        )?)
+    }
+
+    fn query_status_register(
+        &mut self,
+        group: ScpiStatusRegisterGroup,
+    ) -> Result<ScpiStatusRegister, PyScpiError> {
+        Ok(self
+            .inner
+            .query_status_register(StatusRegisterGroup::from(&group))?
+            .into())
+    }
+
+    /// Waits, with the GIL released, until the instrument requests service.
+    #[pyo3(signature = (timeout_ms=10_000, poll_interval_ms=50))]
+    fn wait_for_service_request(
+        &mut self,
+        py: Python,
+        timeout_ms: u64,
+        poll_interval_ms: u64,
+    ) -> Result<ScpiStatusByte, PyScpiError> {
+        let timeout: Duration = Duration::from_millis(timeout_ms);
+        let poll_interval: Duration = Duration::from_millis(poll_interval_ms);
+        let inner: &mut Messenger = &mut self.inner;
+        Ok(py
+            .allow_threads(|| inner.wait_for_service_request(timeout, poll_interval))?
+            .into())
+    }
+
+    fn set_error_check_mode(&mut self, mode: ScpiErrorCheckMode) {
+        let error_check_mode: ErrorCheckMode = match mode {
+            ScpiErrorCheckMode::Disabled => ErrorCheckMode::Disabled,
+            ScpiErrorCheckMode::AfterEachCommand => ErrorCheckMode::AfterEachCommand,
+            ScpiErrorCheckMode::AfterList => ErrorCheckMode::AfterList,
+        };
+
+        self.inner.set_error_check_mode(error_check_mode);
+    }
+
+    /// `spin_window_us` is how long before each deadline the sleeping strategies start spinning.
+    #[pyo3(signature = (strategy, spin_window_us=200))]
+    fn set_timing_strategy(&mut self, strategy: ScpiTimingStrategy, spin_window_us: u64) {
+        let spin_window: Duration = Duration::from_micros(spin_window_us);
+        let timing_strategy: TimingStrategy = match strategy {
+            ScpiTimingStrategy::Spin => TimingStrategy::Spin,
+            ScpiTimingStrategy::Hybrid => TimingStrategy::Hybrid { spin_window },
+            ScpiTimingStrategy::AbsoluteDeadline => {
+                TimingStrategy::AbsoluteDeadline { spin_window }
+            }
+        };
+
+        self.inner.set_timing_strategy(timing_strategy);
+    }
+
+    /// Validates outgoing commands against `tree` and rewrites them in `form`. None removes the tree.
+    #[pyo3(signature = (tree=None, form=ScpiMnemonicForm::AsWritten))]
+    fn set_command_tree(&mut self, tree: Option<&ScpiCommandTree>, form: ScpiMnemonicForm) {
+        self.inner.set_command_tree(tree.map(|x| x.inner.clone()));
+        self.inner.set_mnemonic_form(MnemonicForm::from(&form));
+    }
+
+    fn read_error_queue(&mut self) -> Result<Vec<(i32, String)>, PyScpiError> {
+        let errors: Vec<InstrumentError> = self.inner.read_error_queue()?;
+        Ok(errors
+            .iter()
+            .map(|x| (x.get_code(), x.get_message().to_string()))
+            .collect())
+    }
+
+    fn send_block(&mut self, header: &str, data: &[u8]) -> Result<usize, PyScpiError> {
+        Ok(self.inner.send_block(header, data)?)
+    }
+
+    fn send_indefinite_block(&mut self, header: &str, data: &[u8]) -> Result<usize, PyScpiError> {
+        Ok(self.inner.send_indefinite_block(header, data)?)
+    }
+
+    fn query_block(&mut self, py: Python, message: &str) -> Result<PyObject, PyScpiError> {
+        let data: Vec<u8> = self.inner.query_block(message)?;
+        Ok(PyBytes::new(py, &data).into())
+    }
+
+    /// `datatype` follows the `struct` module format characters: b, B, h, H, i, I, f or d.
+    #[pyo3(signature = (message, datatype, big_endian=false))]
+    fn query_binary_values(
+        &mut self,
+        py: Python,
+        message: &str,
+        datatype: char,
+        big_endian: bool,
+    ) -> Result<PyObject, PyScpiError> {
+        let order: ByteOrder = match big_endian {
+            true => ByteOrder::BigEndian,
+            false => ByteOrder::LittleEndian,
+        };
+
+        let values: PyObject = match datatype {
+            'b' => self
+                .inner
+                .query_binary_values::<i8>(message, order)?
+                .into_py(py),
+            'B' => self
+                .inner
+                .query_binary_values::<u8>(message, order)?
+                .into_py(py),
+            'h' => self
+                .inner
+                .query_binary_values::<i16>(message, order)?
+                .into_py(py),
+            'H' => self
+                .inner
+                .query_binary_values::<u16>(message, order)?
+                .into_py(py),
+            'i' => self
+                .inner
+                .query_binary_values::<i32>(message, order)?
+                .into_py(py),
+            'I' => self
+                .inner
+                .query_binary_values::<u32>(message, order)?
+                .into_py(py),
+            'f' => self
+                .inner
+                .query_binary_values::<f32>(message, order)?
+                .into_py(py),
+            'd' => self
+                .inner
+                .query_binary_values::<f64>(message, order)?
+                .into_py(py),
+            _ => {
+                return Err(ScpiError::InvalidArgument(format!(
+                    "Unsupported block datatype {:?}",
+                    datatype
+                ))
+                .into())
+            }
+        };
+
+        Ok(values)
+    }
+
+    /// Releases the GIL while sending; Ctrl-C stops the loop and raises KeyboardInterrupt.
+    #[pyo3(signature = (message, repititions=None))]
+    fn send_repeated_message(
+        &mut self,
+        py: Python,
+        message: &str,
+        repititions: Option<usize>,
+    ) -> PyResult<usize> {
+        let limits: RunLimits = RunLimits::new().with_max_cycles(repititions);
+        let statistics: RunStatistics =
+            self.run_repeated(py, message, &limits, &StopHandle::new())?;
+
+        Ok(statistics
+            .get_bytes_sent()
+            .checked_div(statistics.get_messages_sent())
+            .unwrap_or(0))
+    }
+
+    #[pyo3(signature = (message, max_cycles=None, max_duration_ms=None, stop_handle=None))]
+    fn run_repeated_message(
+        &mut self,
+        py: Python,
+        message: &str,
+        max_cycles: Option<usize>,
+        max_duration_ms: Option<u64>,
+        stop_handle: Option<&ScpiStopHandle>,
+    ) -> PyResult<ScpiRunStatistics> {
+        let limits: RunLimits = RunLimits::new()
+            .with_max_cycles(max_cycles)
+            .with_max_duration(max_duration_ms.map(Duration::from_millis));
+        let stop: StopHandle = match stop_handle {
+            Some(x) => x.inner.clone(),
+            None => StopHandle::new(),
+        };
+
+        Ok(self.run_repeated(py, message, &limits, &stop)?.into())
     }
 
-    fn send_list_of_messages(&mut self, messages: Vec<&str>) -> Result<(), Error> {
-        self.inner.send_list_of_messages(&messages)
+    fn send_list_of_messages(&mut self, messages: Vec<&str>) -> Result<(), PyScpiError> {
+        Ok(self.inner.send_list_of_messages(&messages)?)
     }
 
+    /// Releases the GIL while sending; Ctrl-C stops the loop and raises KeyboardInterrupt.
     fn send_duty_cycled_message(
         &mut self,
+        py: Python,
         messages: (&str, &str),
         microsecond_times: (u64, u64),
-    ) -> Result<(), Error> {
+    ) -> PyResult<()> {
+        self.run_duty_cycled(
+            py,
+            messages,
+            microsecond_times,
+            &RunLimits::new(),
+            &StopHandle::new(),
+        )?;
+        Ok(())
+    }
+
+    #[pyo3(signature = (
+        messages,
+        microsecond_times,
+        max_cycles=None,
+        max_duration_ms=None,
+        stop_handle=None
+    ))]
+    fn run_duty_cycled_message(
+        &mut self,
+        py: Python,
+        messages: (&str, &str),
+        microsecond_times: (u64, u64),
+        max_cycles: Option<usize>,
+        max_duration_ms: Option<u64>,
+        stop_handle: Option<&ScpiStopHandle>,
+    ) -> PyResult<ScpiRunStatistics> {
+        let limits: RunLimits = RunLimits::new()
+            .with_max_cycles(max_cycles)
+            .with_max_duration(max_duration_ms.map(Duration::from_millis));
+        let stop: StopHandle = match stop_handle {
+            Some(x) => x.inner.clone(),
+            None => StopHandle::new(),
+        };
+
+        Ok(self
+            .run_duty_cycled(py, messages, microsecond_times, &limits, &stop)?
+            .into())
+    }
+
+    /// Runs the duty cycle on a background thread and returns a handle to it right away. Other
+    /// calls on this messenger fail until `stop_duty_cycle` is called.
+    #[pyo3(signature = (messages, microsecond_times, max_cycles=None, max_duration_ms=None))]
+    fn start_duty_cycle(
+        &mut self,
+        messages: (&str, &str),
+        microsecond_times: (u64, u64),
+        max_cycles: Option<usize>,
+        max_duration_ms: Option<u64>,
+    ) -> Result<ScpiDutyCycleHandle, PyScpiError> {
         let message: DutyCycleMessage = DutyCycleMessage::new(
             microsecond_times.0,
             microsecond_times.1,
             messages.0,
             messages.1,
         );
+        let limits: RunLimits = RunLimits::new()
+            .with_max_cycles(max_cycles)
+            .with_max_duration(max_duration_ms.map(Duration::from_millis));
+
+        Ok(ScpiDutyCycleHandle {
+            inner: self.inner.start_duty_cycle(&message, &limits)?,
+        })
+    }
+
+    fn stop_duty_cycle(&mut self, py: Python) -> Result<ScpiRunStatistics, PyScpiError> {
+        let inner: &mut Messenger = &mut self.inner;
+        let statistics: RunStatistics = py.allow_threads(|| inner.stop_duty_cycle())?;
+        Ok(statistics.into())
+    }
+
+    /// Releases the GIL while sending; Ctrl-C stops the loop and raises KeyboardInterrupt.
+    #[pyo3(signature = (sequence, max_cycles=None, max_duration_ms=None, stop_handle=None))]
+    fn run_sequence(
+        &mut self,
+        py: Python,
+        sequence: &ScpiSequence,
+        max_cycles: Option<usize>,
+        max_duration_ms: Option<u64>,
+        stop_handle: Option<&ScpiStopHandle>,
+    ) -> PyResult<ScpiRunStatistics> {
+        let limits: RunLimits = RunLimits::new()
+            .with_max_cycles(max_cycles)
+            .with_max_duration(max_duration_ms.map(Duration::from_millis));
+        let stop: StopHandle = match stop_handle {
+            Some(x) => x.inner.clone(),
+            None => StopHandle::new(),
+        };
+
+        let inner: &mut Messenger = &mut self.inner;
+        let result: Result<RunStatistics, ScpiError> = run_interruptible(py, &stop, || {
+            inner.run_sequence(&sequence.inner, &limits, &stop)
+        })?;
+
+        Ok(result.map_err(PyScpiError::from)?.into())
+    }
+}
+
+impl ScpiMessenger {
+    fn run_repeated(
+        &mut self,
+        py: Python,
+        message: &str,
+        limits: &RunLimits,
+        stop: &StopHandle,
+    ) -> PyResult<RunStatistics> {
+        let inner: &mut Messenger = &mut self.inner;
+        let result: Result<RunStatistics, ScpiError> = run_interruptible(py, stop, || {
+            inner.run_repeated_message(message, limits, stop)
+        })?;
+
+        Ok(result.map_err(PyScpiError::from)?)
+    }
+
+    fn run_duty_cycled(
+        &mut self,
+        py: Python,
+        messages: (&str, &str),
+        microsecond_times: (u64, u64),
+        limits: &RunLimits,
+        stop: &StopHandle,
+    ) -> PyResult<RunStatistics> {
+        let message: DutyCycleMessage = DutyCycleMessage::new(
+            microsecond_times.0,
+            microsecond_times.1,
+            messages.0,
+            messages.1,
+        );
+
+        let inner: &mut Messenger = &mut self.inner;
+        let result: Result<RunStatistics, ScpiError> = run_interruptible(py, stop, || {
+            inner.run_duty_cycled_message(&message, limits, stop)
+        })?;
+
+        Ok(result.map_err(PyScpiError::from)?)
+    }
+}
+
+fn completion_options(
+    method: ScpiCompletionMethod,
+    timeout_ms: u64,
+    poll_interval_ms: u64,
+) -> CompletionOptions {
+    let completion_method: CompletionMethod = match method {
+        ScpiCompletionMethod::OperationCompleteQuery => CompletionMethod::OperationCompleteQuery,
+        ScpiCompletionMethod::EventStatusPolling => CompletionMethod::EventStatusPolling,
+        ScpiCompletionMethod::StatusBytePolling => CompletionMethod::StatusBytePolling,
+    };
+
+    CompletionOptions::new()
+        .with_method(completion_method)
+        .with_timeout(Duration::from_millis(timeout_ms))
+        .with_poll_interval(Duration::from_millis(poll_interval_ms))
+}
+
+/// The outcome of a group operation on one instrument. `error` is None on success.
+#[pyclass]
+pub struct ScpiInstrumentResult {
+    #[pyo3(get)]
+    address: String,
+    #[pyo3(get)]
+    ok: bool,
+    #[pyo3(get)]
+    value: Option<PyObject>,
+    #[pyo3(get)]
+    error: Option<String>,
+}
+
+impl ScpiInstrumentResult {
+    pub fn from_result<T: IntoPy<PyObject>>(py: Python, result: InstrumentResult<T>) -> Self {
+        let address: String = result.get_address().to_string();
+        match result.into_result() {
+            Ok(x) => Self {
+                address,
+                ok: true,
+                value: Some(x.into_py(py)),
+                error: None,
+            },
+            Err(e) => Self {
+                address,
+                ok: false,
+                value: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+#[pyclass]
+pub struct ScpiTcpMulticastGroup {
+    inner: TcpMulticastGroup,
+}
+
+#[pymethods]
+impl ScpiTcpMulticastGroup {
+    #[new]
+    #[pyo3(signature = (remote_clients, remote_port, parallel=true, options=None))]
+    fn new(
+        remote_clients: Vec<PyRef<IpAddress>>,
+        remote_port: u16,
+        parallel: bool,
+        options: Option<&ScpiConnectionOptions>,
+    ) -> Result<Self, PyScpiError> {
+        let connection_options: ConnectionOptions = match options {
+            Some(x) => x.options.clone(),
+            None => ConnectionOptions::default(),
+        };
+
+        let addresses: Vec<SocketAddr> = remote_clients
+            .iter()
+            .map(|x| SocketAddr::new(x.address, remote_port))
+            .collect();
+        let inner: TcpMulticastGroup =
+            TcpMulticastGroup::connect(&addresses, &connection_options, parallel)?;
+
+        Ok(Self { inner })
+    }
+
+    fn send_message(&mut self, py: Python, message: &str) -> Vec<ScpiInstrumentResult> {
+        let results: Vec<InstrumentResult<usize>> = self.inner.send_message(message);
+        results
+            .into_iter()
+            .map(|x| ScpiInstrumentResult::from_result(py, x))
+            .collect()
+    }
+
+    fn query(&mut self, py: Python, message: &str) -> Vec<ScpiInstrumentResult> {
+        let results: Vec<InstrumentResult<String>> = self.inner.query(message);
+        results
+            .into_iter()
+            .map(|x| ScpiInstrumentResult::from_result(py, x))
+            .collect()
+    }
+
+    fn send_list_of_messages(
+        &mut self,
+        py: Python,
+        messages: Vec<&str>,
+    ) -> Vec<ScpiInstrumentResult> {
+        let results: Vec<InstrumentResult<()>> = self.inner.send_list_of_messages(&messages);
+        results
+            .into_iter()
+            .map(|x| ScpiInstrumentResult::from_result(py, x))
+            .collect()
+    }
+}
+
+/// Lets another Python thread stop a running `run_*` loop.
+#[derive(Clone)]
+#[pyclass]
+pub struct ScpiStopHandle {
+    pub inner: StopHandle,
+}
+
+#[pymethods]
+impl ScpiStopHandle {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: StopHandle::new(),
+        }
+    }
+
+    fn stop(&self) {
+        self.inner.stop();
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.inner.is_stopped()
+    }
+}
+
+/// Changes or stops a duty cycle started with `ScpiMessenger.start_duty_cycle`.
+#[pyclass]
+pub struct ScpiDutyCycleHandle {
+    inner: DutyCycleHandle,
+}
+
+#[pymethods]
+impl ScpiDutyCycleHandle {
+    fn set_messages(&self, messages: (&str, &str)) {
+        self.inner.set_messages(messages.0, messages.1);
+    }
+
+    fn set_times(&self, microsecond_times: (u64, u64)) {
+        self.inner
+            .set_times(microsecond_times.0, microsecond_times.1);
+    }
+
+    fn stop(&self) {
+        self.inner.stop();
+    }
+
+    fn is_running(&self) -> bool {
+        self.inner.is_running()
+    }
+}
+
+/// `stop_reason` is one of "stopped", "max_cycles" or "max_duration". The `period_*` fields
+/// describe the time between the starts of consecutive cycles, in microseconds.
+#[pyclass]
+pub struct ScpiRunStatistics {
+    #[pyo3(get)]
+    cycles: usize,
+    #[pyo3(get)]
+    messages_sent: usize,
+    #[pyo3(get)]
+    bytes_sent: usize,
+    #[pyo3(get)]
+    elapsed_ms: f64,
+    #[pyo3(get)]
+    stop_reason: String,
+    #[pyo3(get)]
+    period_count: usize,
+    #[pyo3(get)]
+    period_mean_us: f64,
+    #[pyo3(get)]
+    period_min_us: f64,
+    #[pyo3(get)]
+    period_max_us: f64,
+    #[pyo3(get)]
+    period_stddev_us: f64,
+    #[pyo3(get)]
+    missed_deadlines: usize,
+}
+
+impl From<RunStatistics> for ScpiRunStatistics {
+    fn from(statistics: RunStatistics) -> Self {
+        let stop_reason: &str = match statistics.get_stop_reason() {
+            StopReason::Stopped => "stopped",
+            StopReason::MaxCycles => "max_cycles",
+            StopReason::MaxDuration => "max_duration",
+        };
+        let timing: &TimingReport = statistics.get_timing();
+
+        Self {
+            cycles: statistics.get_cycles(),
+            messages_sent: statistics.get_messages_sent(),
+            bytes_sent: statistics.get_bytes_sent(),
+            elapsed_ms: statistics.get_elapsed().as_secs_f64() * 1000.0,
+            stop_reason: stop_reason.to_string(),
+            period_count: timing.get_period_count(),
+            period_mean_us: timing.get_mean_period().as_secs_f64() * 1e6,
+            period_min_us: timing.get_min_period().as_secs_f64() * 1e6,
+            period_max_us: timing.get_max_period().as_secs_f64() * 1e6,
+            period_stddev_us: timing.get_period_std_dev().as_secs_f64() * 1e6,
+            missed_deadlines: timing.get_missed_deadlines(),
+        }
+    }
+}
+
+/// A multi-step message sequence, built step by step or loaded from a file.
+#[derive(Clone)]
+#[pyclass]
+pub struct ScpiSequence {
+    inner: Sequence,
+}
+
+#[pymethods]
+impl ScpiSequence {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: Sequence::new(),
+        }
+    }
+
+    #[staticmethod]
+    fn from_file(path: &str) -> Result<Self, PyScpiError> {
+        Ok(Self {
+            inner: Sequence::from_file(path)?,
+        })
+    }
+
+    #[staticmethod]
+    fn parse(text: &str) -> Result<Self, PyScpiError> {
+        Ok(Self {
+            inner: text.parse()?,
+        })
+    }
+
+    #[pyo3(signature = (message, microseconds, repetitions=1))]
+    fn add_step(&mut self, message: &str, microseconds: u64, repetitions: usize) {
+        let sequence: Sequence = std::mem::take(&mut self.inner);
+        self.inner = match repetitions {
+            1 => sequence.with_step(message, microseconds),
+            _ => sequence.with_repeated_step(message, microseconds, repetitions),
+        };
+    }
+
+    fn add_loop(&mut self, sequence: &ScpiSequence, repetitions: usize) {
+        let outer: Sequence = std::mem::take(&mut self.inner);
+        self.inner = outer.with_loop(sequence.inner.clone(), repetitions);
+    }
+
+    fn message_count(&self) -> usize {
+        self.inner.message_count()
+    }
+
+    fn period_ms(&self) -> f64 {
+        self.inner.period().as_secs_f64() * 1000.0
+    }
+}
+
+/// A channel list entry: either a single channel or an inclusive `(first, last)` range.
+#[derive(FromPyObject)]
+pub enum ScpiChannelEntry {
+    Channel(u32),
+    Range((u32, u32)),
+}
+
+/// Builds one SCPI command with correctly formatted parameters.
+#[derive(Clone)]
+#[pyclass]
+pub struct ScpiCommand {
+    inner: Command,
+}
+
+#[pymethods]
+impl ScpiCommand {
+    #[new]
+    #[pyo3(signature = (header, query=false))]
+    fn new(header: &str, query: bool) -> Self {
+        let inner: Command = match query {
+            true => Command::query(header),
+            false => Command::new(header),
+        };
+        Self { inner }
+    }
+
+    #[pyo3(signature = (value, unit=None))]
+    fn add_nr1(&mut self, value: i64, unit: Option<&str>) {
+        self.add_numeric(Parameter::Nr1(value), unit);
+    }
+
+    #[pyo3(signature = (value, unit=None))]
+    fn add_nr2(&mut self, value: f64, unit: Option<&str>) {
+        self.add_numeric(Parameter::Nr2(value), unit);
+    }
+
+    #[pyo3(signature = (value, unit=None))]
+    fn add_nr3(&mut self, value: f64, unit: Option<&str>) {
+        self.add_numeric(Parameter::Nr3(value), unit);
+    }
+
+    fn add_boolean(&mut self, value: bool) {
+        self.add(Parameter::Boolean(value));
+    }
+
+    fn add_character(&mut self, value: &str) {
+        self.add(Parameter::Character(value.to_string()));
+    }
+
+    fn add_string(&mut self, value: &str) {
+        self.add(Parameter::String(value.to_string()));
+    }
+
+    fn add_channel_list(&mut self, channels: Vec<ScpiChannelEntry>) {
+        let list: ChannelList = channels.iter().fold(ChannelList::new(), |list, x| match x {
+            ScpiChannelEntry::Channel(channel) => list.with_channel(*channel),
+            ScpiChannelEntry::Range((first, last)) => list.with_range(*first, *last),
+        });
+        self.add(Parameter::ChannelList(list));
+    }
+
+    fn build(&self) -> Result<String, PyScpiError> {
+        Ok(self.inner.build()?)
+    }
+}
+
+impl ScpiCommand {
+    fn add(&mut self, parameter: Parameter) {
+        self.inner = self.inner.clone().with_parameter(parameter);
+    }
+
+    fn add_numeric(&mut self, parameter: Parameter, unit: Option<&str>) {
+        match unit {
+            Some(x) => self.add(parameter.with_unit(x)),
+            None => self.add(parameter),
+        }
+    }
+}
+
+/// Joins several commands into one message with `;`, or `;:` for commands added with `root=True`.
+#[pyclass]
+pub struct ScpiCompoundCommand {
+    inner: CompoundCommand,
+}
+
+#[pymethods]
+impl ScpiCompoundCommand {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: CompoundCommand::new(),
+        }
+    }
+
+    #[pyo3(signature = (command, root=false))]
+    fn add_command(&mut self, command: &ScpiCommand, root: bool) {
+        let compound: CompoundCommand = std::mem::take(&mut self.inner);
+        self.inner = match root {
+            true => compound.with_root_command(command.inner.clone()),
+            false => compound.with_command(command.inner.clone()),
+        };
+    }
+
+    fn build(&self) -> Result<String, PyScpiError> {
+        Ok(self.inner.build()?)
+    }
+}
+
+/// Converts a parsed response into the matching native Python value.
+pub fn response_value_to_py(py: Python, value: &ResponseValue) -> PyObject {
+    match value {
+        ResponseValue::Integer(x) => x.into_py(py),
+        ResponseValue::Float(x) => x.into_py(py),
+        ResponseValue::String(x) | ResponseValue::Character(x) => x.into_py(py),
+        ResponseValue::ChannelList(x) => x.channels().into_py(py),
+        ResponseValue::List(x) => x
+            .iter()
+            .map(|element| response_value_to_py(py, element))
+            .collect::<Vec<PyObject>>()
+            .into_py(py),
+    }
+}
+
+/// Known command headers of an instrument, e.g. `[SOURce]:VOLTage[:LEVel]`.
+#[pyclass]
+pub struct ScpiCommandTree {
+    inner: CommandTree,
+}
+
+#[pymethods]
+impl ScpiCommandTree {
+    #[new]
+    fn new() -> Self {
+        Self {
+            inner: CommandTree::new(),
+        }
+    }
+
+    #[staticmethod]
+    fn from_file(path: &str) -> Result<Self, PyScpiError> {
+        Ok(Self {
+            inner: CommandTree::from_file(path)?,
+        })
+    }
+
+    #[staticmethod]
+    fn parse(text: &str) -> Result<Self, PyScpiError> {
+        Ok(Self {
+            inner: text.parse()?,
+        })
+    }
+
+    fn add_header(&mut self, definition: &str) -> Result<(), PyScpiError> {
+        self.inner = self.inner.clone().with_header(definition)?;
+        Ok(())
+    }
+
+    fn validate(&self, message: &str) -> Result<(), PyScpiError> {
+        Ok(self.inner.validate(message)?)
+    }
+
+    fn normalize(&self, message: &str, form: ScpiMnemonicForm) -> Result<String, PyScpiError> {
+        Ok(self.inner.normalize(message, MnemonicForm::from(&form))?)
+    }
+}
+
+/// The parsed fields of an `*IDN?` response.
+#[pyclass]
+pub struct ScpiIdentification {
+    #[pyo3(get)]
+    manufacturer: String,
+    #[pyo3(get)]
+    model: String,
+    #[pyo3(get)]
+    serial_number: String,
+    #[pyo3(get)]
+    firmware: String,
+}
+
+impl From<Identification> for ScpiIdentification {
+    fn from(identification: Identification) -> Self {
+        Self {
+            manufacturer: identification.get_manufacturer().to_string(),
+            model: identification.get_model().to_string(),
+            serial_number: identification.get_serial_number().to_string(),
+            firmware: identification.get_firmware().to_string(),
+        }
+    }
+}
+
+/// The Standard Event Status Register read with `*ESR?`, split into its named bits.
+#[pyclass]
+pub struct ScpiEventStatus {
+    #[pyo3(get)]
+    value: u8,
+    #[pyo3(get)]
+    operation_complete: bool,
+    #[pyo3(get)]
+    request_control: bool,
+    #[pyo3(get)]
+    query_error: bool,
+    #[pyo3(get)]
+    device_dependent_error: bool,
+    #[pyo3(get)]
+    execution_error: bool,
+    #[pyo3(get)]
+    command_error: bool,
+    #[pyo3(get)]
+    user_request: bool,
+    #[pyo3(get)]
+    power_on: bool,
+    #[pyo3(get)]
+    has_errors: bool,
+}
+
+impl From<StandardEventStatus> for ScpiEventStatus {
+    fn from(status: StandardEventStatus) -> Self {
+        Self {
+            value: status.get_bits(),
+            operation_complete: status.operation_complete(),
+            request_control: status.request_control(),
+            query_error: status.query_error(),
+            device_dependent_error: status.device_dependent_error(),
+            execution_error: status.execution_error(),
+            command_error: status.command_error(),
+            user_request: status.user_request(),
+            power_on: status.power_on(),
+            has_errors: status.has_errors(),
+        }
+    }
+}
+
+/// The status byte read with `*STB?`, split into its named bits.
+#[pyclass]
+pub struct ScpiStatusByte {
+    #[pyo3(get)]
+    value: u8,
+    #[pyo3(get)]
+    error_queue: bool,
+    #[pyo3(get)]
+    questionable_summary: bool,
+    #[pyo3(get)]
+    message_available: bool,
+    #[pyo3(get)]
+    event_summary: bool,
+    #[pyo3(get)]
+    request_service: bool,
+    #[pyo3(get)]
+    operation_summary: bool,
+}
+
+impl From<StatusByte> for ScpiStatusByte {
+    fn from(status: StatusByte) -> Self {
+        Self {
+            value: status.get_bits(),
+            error_queue: status.error_queue(),
+            questionable_summary: status.questionable_summary(),
+            message_available: status.message_available(),
+            event_summary: status.event_summary(),
+            request_service: status.request_service(),
+            operation_summary: status.operation_summary(),
+        }
+    }
+}
+
+/// Every register of an OPERation or QUEStionable group.
+#[pyclass]
+pub struct ScpiStatusRegister {
+    #[pyo3(get)]
+    condition: u16,
+    #[pyo3(get)]
+    event: u16,
+    #[pyo3(get)]
+    enable: u16,
+    #[pyo3(get)]
+    positive_transition: u16,
+    #[pyo3(get)]
+    negative_transition: u16,
+    #[pyo3(get)]
+    summary: bool,
+}
+
+impl From<StatusRegister> for ScpiStatusRegister {
+    fn from(register: StatusRegister) -> Self {
+        Self {
+            condition: register.get_condition(),
+            event: register.get_event(),
+            enable: register.get_enable(),
+            positive_transition: register.get_positive_transition(),
+            negative_transition: register.get_negative_transition(),
+            summary: register.summary(),
+        }
+    }
+}
+
+/// A scripted TCP or UDP instrument on a localhost ephemeral port, for tests without hardware.
+#[pyclass]
+pub struct ScpiMockInstrument {
+    inner: MockInstrument,
+}
+
+#[pymethods]
+impl ScpiMockInstrument {
+    /// `responses` maps each message to the answers it gets in turn; the last answer repeats.
+    #[new]
+    #[pyo3(signature = (mode, responses=HashMap::new()))]
+    fn new(
+        mode: ScpiNetworkMode,
+        responses: HashMap<String, Vec<String>>,
+    ) -> Result<Self, PyScpiError> {
+        let scpi_mode: NetworkMode = mode.to_network_mode(None, None)?;
+
+        let script: MockScript =
+            responses
+                .iter()
+                .fold(MockScript::new(), |script, (message, answers)| {
+                    let answers: Vec<&str> = answers.iter().map(String::as_str).collect();
+                    script.with_responses(message, &answers)
+                });
+
+        Ok(Self {
+            inner: MockInstrument::start(&scpi_mode, script)?,
+        })
+    }
+
+    fn port(&self) -> u16 {
+        self.inner.get_port()
+    }
+
+    fn received(&self) -> Vec<String> {
+        self.inner.get_received()
+    }
+
+    /// Waits, with the GIL released, until at least `count` messages have arrived.
+    #[pyo3(signature = (count, timeout_ms=5_000))]
+    fn wait_for_received(
+        &self,
+        py: Python,
+        count: usize,
+        timeout_ms: u64,
+    ) -> Result<Vec<String>, PyScpiError> {
+        let timeout: Duration = Duration::from_millis(timeout_ms);
+        let inner: &MockInstrument = &self.inner;
+        Ok(py.allow_threads(|| inner.wait_for_received(count, timeout))?)
+    }
+
+    fn clear_received(&self) {
+        self.inner.clear_received();
+    }
 
-        self.inner.send_duty_cycled_message(&message)
+    fn add_response(&self, message: &str, response: &str) {
+        self.inner.add_response(message, response);
     }
 }
@@ -0,0 +1,64 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use pyo3::{create_exception, exceptions::PyException, PyErr};
+
+use scpi::error::ScpiError;
+
+create_exception!(py_scpi, ScpiException, PyException);
+create_exception!(py_scpi, ScpiConnectionError, ScpiException);
+create_exception!(py_scpi, ScpiTimeoutError, ScpiException);
+create_exception!(py_scpi, ScpiUnsupportedError, ScpiException);
+create_exception!(py_scpi, ScpiInstrumentError, ScpiException);
+create_exception!(py_scpi, ScpiParseError, ScpiException);
+create_exception!(py_scpi, ScpiInvalidArgumentError, ScpiException);
+create_exception!(py_scpi, ScpiGroupError, ScpiException);
+
+/// Wrapper allowing `ScpiError` to be raised as one of the exception classes above.
+pub struct PyScpiError(ScpiError);
+
+impl From<ScpiError> for PyScpiError {
+    fn from(error: ScpiError) -> Self {
+        Self(error)
+    }
+}
+
+impl From<PyScpiError> for PyErr {
+    fn from(error: PyScpiError) -> Self {
+        let message: String = error.0.to_string();
+        match error.0 {
+            ScpiError::Instrument(errors) => {
+                let entries: Vec<(i32, String)> = errors
+                    .iter()
+                    .map(|x| (x.get_code(), x.get_message().to_string()))
+                    .collect();
+                ScpiInstrumentError::new_err((message, entries))
+            }
+            ScpiError::Connection(_) => ScpiConnectionError::new_err(message),
+            ScpiError::Timeout => ScpiTimeoutError::new_err(message),
+            ScpiError::Unsupported(_) => ScpiUnsupportedError::new_err(message),
+            ScpiError::Parse(_) => ScpiParseError::new_err(message),
+            ScpiError::InvalidArgument(_) => ScpiInvalidArgumentError::new_err(message),
+            ScpiError::Group(failures) => {
+                let entries: Vec<(String, String)> = failures
+                    .iter()
+                    .map(|(address, error)| (address.to_string(), error.to_string()))
+                    .collect();
+                ScpiGroupError::new_err((message, entries))
+            }
+        }
+    }
+}
@@ -14,35 +14,49 @@
    limitations under the License.
 */
 
-use pyo3::pyfunction;
-use std::io::Error;
+use pyo3::{pyfunction, PyObject, PyRef, PyResult, Python};
 use std::net::IpAddr;
+use std::time::Duration;
 
 use scpi::duty_cycle::DutyCycleMessage;
+use scpi::error::ScpiError;
 use scpi::networking::NetworkMode;
-use scpi::send_duty_cycled_message as lib_send_duty_cycled_message;
+use scpi::query_scpi_message as lib_query_scpi_message;
+use scpi::query_scpi_message_with_timeout as lib_query_scpi_message_with_timeout;
+use scpi::query_scpi_resource_message as lib_query_scpi_resource_message;
+use scpi::response::parse_value;
+use scpi::run_control::{RunLimits, RunStatistics, StopHandle};
+use scpi::run_duty_cycled_message as lib_run_duty_cycled_message;
+use scpi::run_repeated_scpi_message as lib_run_repeated_scpi_message;
 use scpi::send_list_of_scpi_messages as lib_send_list_of_scpi_messages;
-use scpi::send_repeated_scpi_message as lib_send_repeated_scpi_message;
+use scpi::send_list_of_scpi_messages_to_group as lib_send_list_of_scpi_messages_to_group;
 use scpi::send_scpi_message as lib_send_scpi_message;
+use scpi::send_scpi_resource_message as lib_send_scpi_resource_message;
+use scpi::tcp_multicast::InstrumentResult;
 
+use crate::py_classes::response_value_to_py;
 use crate::py_classes::IpAddress;
+use crate::py_classes::ScpiInstrumentResult;
 use crate::py_classes::ScpiNetworkMode;
+use crate::py_classes::ScpiSerialPort;
+use crate::py_errors::PyScpiError;
+use crate::py_interrupt::run_interruptible;
 
 #[pyfunction]
+#[pyo3(signature = (messages, times, mode, remote_client, remote_port, local_port, group=None, serial=None))]
+#[allow(clippy::too_many_arguments)]
 pub fn send_dutycycled_message(
+    py: Python,
     messages: (&str, &str),
     times: (u64, u64),
     mode: &ScpiNetworkMode,
     remote_client: &IpAddress,
     remote_port: u16,
     local_port: u16,
-) -> Result<(), Error> {
-    let network_mode: NetworkMode = match mode {
-        ScpiNetworkMode::Udp => NetworkMode::Udp,
-        ScpiNetworkMode::Tcp => NetworkMode::Tcp,
-        ScpiNetworkMode::UdpMulticast => NetworkMode::UdpMulticast,
-        ScpiNetworkMode::TcpMulticast => NetworkMode::TcpMulticast,
-    };
+    group: Option<Vec<PyRef<IpAddress>>>,
+    serial: Option<&ScpiSerialPort>,
+) -> PyResult<()> {
+    let network_mode: NetworkMode = mode.to_network_mode(group.as_deref(), serial)?;
 
     let remote_client_address: &IpAddr = &remote_client.address;
 
@@ -52,92 +66,200 @@ pub fn send_dutycycled_message(
     let dutycycled_message: DutyCycleMessage =
         DutyCycleMessage::new(first_time, second_time, first_message, second_message);
 
-    lib_send_duty_cycled_message(
-        &dutycycled_message,
-        &network_mode,
-        remote_client_address,
-        remote_port,
-        local_port,
-    )
+    let stop: StopHandle = StopHandle::new();
+    let result: Result<RunStatistics, ScpiError> = run_interruptible(py, &stop, || {
+        lib_run_duty_cycled_message(
+            &dutycycled_message,
+            &network_mode,
+            remote_client_address,
+            remote_port,
+            local_port,
+            &RunLimits::new(),
+            &stop,
+        )
+    })?;
+    result.map_err(PyScpiError::from)?;
+
+    Ok(())
 }
 
 #[pyfunction]
+#[pyo3(signature = (message, mode, remote_client, remote_port, local_port, group=None, serial=None))]
 pub fn send_message(
     message: &str,
     mode: &ScpiNetworkMode,
     remote_client: &IpAddress,
     remote_port: u16,
     local_port: u16,
-) -> Result<usize, Error> {
-    let network_mode: NetworkMode = match mode {
-        ScpiNetworkMode::Udp => NetworkMode::Udp,
-        ScpiNetworkMode::Tcp => NetworkMode::Tcp,
-        ScpiNetworkMode::UdpMulticast => NetworkMode::UdpMulticast,
-        ScpiNetworkMode::TcpMulticast => NetworkMode::TcpMulticast,
-    };
+    group: Option<Vec<PyRef<IpAddress>>>,
+    serial: Option<&ScpiSerialPort>,
+) -> Result<usize, PyScpiError> {
+    let network_mode: NetworkMode = mode.to_network_mode(group.as_deref(), serial)?;
 
     let remote_client_address: &IpAddr = &remote_client.address;
 
-    lib_send_scpi_message(
+    Ok(lib_send_scpi_message(
         message,
         &network_mode,
         remote_client_address,
         remote_port,
         local_port,
-    )
+    )?)
+}
+
+#[pyfunction]
+pub fn send_resource_message(message: &str, resource: &str) -> Result<usize, PyScpiError> {
+    Ok(lib_send_scpi_resource_message(message, resource)?)
+}
+
+#[pyfunction]
+pub fn query_resource_message(message: &str, resource: &str) -> Result<String, PyScpiError> {
+    Ok(lib_query_scpi_resource_message(message, resource)?)
+}
+
+#[pyfunction]
+#[pyo3(signature = (
+    message,
+    mode,
+    remote_client,
+    remote_port,
+    local_port,
+    timeout_ms=None,
+    group=None,
+    serial=None
+))]
+#[allow(clippy::too_many_arguments)]
+pub fn query_message(
+    message: &str,
+    mode: &ScpiNetworkMode,
+    remote_client: &IpAddress,
+    remote_port: u16,
+    local_port: u16,
+    timeout_ms: Option<u64>,
+    group: Option<Vec<PyRef<IpAddress>>>,
+    serial: Option<&ScpiSerialPort>,
+) -> Result<String, PyScpiError> {
+    let network_mode: NetworkMode = mode.to_network_mode(group.as_deref(), serial)?;
+
+    let remote_client_address: &IpAddr = &remote_client.address;
+
+    match timeout_ms {
+        Some(milliseconds) => Ok(lib_query_scpi_message_with_timeout(
+            message,
+            &network_mode,
+            remote_client_address,
+            remote_port,
+            local_port,
+            Duration::from_millis(milliseconds),
+        )?),
+        None => Ok(lib_query_scpi_message(
+            message,
+            &network_mode,
+            remote_client_address,
+            remote_port,
+            local_port,
+        )?),
+    }
 }
 
+/// With `ScpiNetworkMode.TcpMulticast`, every message goes to each address in `group`, and a
+/// `ScpiGroupError` lists each instrument that failed. With `ScpiNetworkMode.Serial`, messages go
+/// to the `serial` port.
 #[pyfunction]
+#[pyo3(signature = (messages, mode, remote_client, remote_port, local_port, group=None, serial=None))]
 pub fn send_list_of_messages(
     messages: Vec<&str>,
     mode: &ScpiNetworkMode,
     remote_client: &IpAddress,
     remote_port: u16,
     local_port: u16,
-) -> Result<(), Error> {
-    let network_mode: NetworkMode = match mode {
-        ScpiNetworkMode::Udp => NetworkMode::Udp,
-        ScpiNetworkMode::Tcp => NetworkMode::Tcp,
-        ScpiNetworkMode::UdpMulticast => NetworkMode::UdpMulticast,
-        ScpiNetworkMode::TcpMulticast => NetworkMode::TcpMulticast,
-    };
+    group: Option<Vec<PyRef<IpAddress>>>,
+    serial: Option<&ScpiSerialPort>,
+) -> Result<(), PyScpiError> {
+    let network_mode: NetworkMode = mode.to_network_mode(group.as_deref(), serial)?;
 
     let remote_client_address: &IpAddr = &remote_client.address;
 
-    lib_send_list_of_scpi_messages(
+    Ok(lib_send_list_of_scpi_messages(
         &messages,
         &network_mode,
         remote_client_address,
         remote_port,
         local_port,
-    )
+    )?)
+}
+
+/// Sends every message to every instrument over TCP, returning one result per instrument.
+#[pyfunction]
+#[pyo3(signature = (messages, remote_clients, remote_port, parallel=true))]
+pub fn send_list_of_messages_to_group(
+    py: Python,
+    messages: Vec<&str>,
+    remote_clients: Vec<PyRef<IpAddress>>,
+    remote_port: u16,
+    parallel: bool,
+) -> Result<Vec<ScpiInstrumentResult>, PyScpiError> {
+    let addresses: Vec<IpAddr> = remote_clients.iter().map(|x| x.address).collect();
+
+    let results: Vec<InstrumentResult<()>> =
+        lib_send_list_of_scpi_messages_to_group(&messages, &addresses, remote_port, parallel)?;
+
+    Ok(results
+        .into_iter()
+        .map(|x| ScpiInstrumentResult::from_result(py, x))
+        .collect())
 }
 
 #[pyfunction]
-#[pyo3(signature = (message, mode, remote_client, remote_port, local_port, repititions=None))]
+#[pyo3(signature = (
+    message,
+    mode,
+    remote_client,
+    remote_port,
+    local_port,
+    repititions=None,
+    group=None,
+    serial=None
+))]
+#[allow(clippy::too_many_arguments)]
 pub fn send_repeated_message(
+    py: Python,
     message: &str,
     mode: &ScpiNetworkMode,
     remote_client: &IpAddress,
     remote_port: u16,
     local_port: u16,
     repititions: Option<usize>,
-) -> Result<usize, Error> {
-    let network_mode: NetworkMode = match mode {
-        ScpiNetworkMode::Udp => NetworkMode::Udp,
-        ScpiNetworkMode::Tcp => NetworkMode::Tcp,
-        ScpiNetworkMode::UdpMulticast => NetworkMode::UdpMulticast,
-        ScpiNetworkMode::TcpMulticast => NetworkMode::TcpMulticast,
-    };
+    group: Option<Vec<PyRef<IpAddress>>>,
+    serial: Option<&ScpiSerialPort>,
+) -> PyResult<usize> {
+    let network_mode: NetworkMode = mode.to_network_mode(group.as_deref(), serial)?;
 
     let remote_client_address: &IpAddr = &remote_client.address;
 
-    lib_send_repeated_scpi_message(
-        message,
-        &network_mode,
-        remote_client_address,
-        remote_port,
-        local_port,
-        repititions,
-    )
+    let limits: RunLimits = RunLimits::new().with_max_cycles(repititions);
+    let stop: StopHandle = StopHandle::new();
+    let result: Result<RunStatistics, ScpiError> = run_interruptible(py, &stop, || {
+        lib_run_repeated_scpi_message(
+            message,
+            &network_mode,
+            remote_client_address,
+            remote_port,
+            local_port,
+            &limits,
+            &stop,
+        )
+    })?;
+    let statistics: RunStatistics = result.map_err(PyScpiError::from)?;
+
+    Ok(statistics
+        .get_bytes_sent()
+        .checked_div(statistics.get_messages_sent())
+        .unwrap_or(0))
+}
+
+/// Parses a response string into an int, float, str or list, depending on what it looks like.
+#[pyfunction]
+pub fn parse_response(py: Python, response: &str) -> Result<PyObject, PyScpiError> {
+    Ok(response_value_to_py(py, &parse_value(response)?))
 }
@@ -0,0 +1,62 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::{PyErr, PyResult, Python};
+use std::thread::{ScopedJoinHandle, Thread};
+use std::time::Duration;
+
+use scpi::run_control::StopHandle;
+
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Runs `work` on a worker thread with the GIL released, checking for Python signals while it
+/// runs. On Ctrl-C (or any other pending signal) `stop` is set, the worker is joined and the
+/// signal's exception is returned.
+pub fn run_interruptible<T, F>(py: Python, stop: &StopHandle, work: F) -> PyResult<T>
+where
+    T: Send,
+    F: FnOnce() -> T + Send,
+{
+    std::thread::scope(|scope| {
+        let caller: Thread = std::thread::current();
+        let worker: ScopedJoinHandle<T> = scope.spawn(move || {
+            let result: T = work();
+            caller.unpark();
+            result
+        });
+
+        while !worker.is_finished() {
+            py.allow_threads(|| std::thread::park_timeout(SIGNAL_POLL_INTERVAL));
+            if worker.is_finished() {
+                break;
+            }
+
+            if let Err(e) = py.check_signals() {
+                stop.stop();
+                let _ = py.allow_threads(|| worker.join());
+                return Err(e);
+            }
+        }
+
+        match worker.join() {
+            Ok(x) => Ok(x),
+            Err(_) => Err(PyErr::new::<PyRuntimeError, _>(
+                "SCPI worker thread panicked",
+            )),
+        }
+    })
+}
@@ -1,14 +1,212 @@
-pub fn add(left: usize, right: usize) -> usize {
-    left + right
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::{
+    io::{Error, Read, Write},
+    time::Duration,
+};
+
+use serialport::{ClearBuffer, SerialPort};
+
+pub use serialport::{DataBits, FlowControl, Parity, StopBits};
+
+// serialport has no "block forever" setting and adds its timeout to `Instant::now()` when flushing.
+const BLOCKING_TIMEOUT: Duration = Duration::from_secs(60 * 60 * 24 * 365);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SerialConfig {
+    baud_rate: u32,
+    data_bits: DataBits,
+    parity: Parity,
+    stop_bits: StopBits,
+    flow_control: FlowControl,
+}
+
+/// 9600 baud, 8 data bits, no parity, 1 stop bit and no flow control.
+impl Default for SerialConfig {
+    fn default() -> Self {
+        Self {
+            baud_rate: 9600,
+            data_bits: DataBits::Eight,
+            parity: Parity::None,
+            stop_bits: StopBits::One,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+impl SerialConfig {
+    pub fn new(
+        baud_rate: u32,
+        data_bits: DataBits,
+        parity: Parity,
+        stop_bits: StopBits,
+        flow_control: FlowControl,
+    ) -> Self {
+        Self {
+            baud_rate,
+            data_bits,
+            parity,
+            stop_bits,
+            flow_control,
+        }
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl SerialConfig {
+    pub fn get_baud_rate(&self) -> u32 {
+        self.baud_rate
+    }
+
+    pub fn get_data_bits(&self) -> DataBits {
+        self.data_bits
+    }
+
+    pub fn get_parity(&self) -> Parity {
+        self.parity
+    }
+
+    pub fn get_stop_bits(&self) -> StopBits {
+        self.stop_bits
+    }
+
+    pub fn get_flow_control(&self) -> FlowControl {
+        self.flow_control
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                        Serial Connection                                       */
+/* ********************************************************************************************** */
+
+pub struct SerialConnection {
+    port: Box<dyn SerialPort>,
+}
+
+impl SerialConnection {
+    pub fn open(path: &str, config: &SerialConfig) -> Result<Self, Error> {
+        let port: Box<dyn SerialPort> = serialport::new(path, config.baud_rate)
+            .data_bits(config.data_bits)
+            .parity(config.parity)
+            .stop_bits(config.stop_bits)
+            .flow_control(config.flow_control)
+            .timeout(BLOCKING_TIMEOUT)
+            .open()?;
+
+        Ok(Self { port })
+    }
+
+    /// Opens both ends of a pseudo-terminal, useful for exercising a transport without hardware.
+    #[cfg(unix)]
+    pub fn pair(config: &SerialConfig) -> Result<(Self, Self), Error> {
+        let (first, second) = serialport::TTYPort::pair()?;
+        let mut first: Self = Self {
+            port: Box::new(first),
+        };
+        let mut second: Self = Self {
+            port: Box::new(second),
+        };
+
+        first.configure(config)?;
+        second.configure(config)?;
+        first.set_timeout(None)?;
+        second.set_timeout(None)?;
+
+        Ok((first, second))
+    }
+
+    pub fn configure(&mut self, config: &SerialConfig) -> Result<(), Error> {
+        self.port.set_baud_rate(config.baud_rate)?;
+        self.port.set_data_bits(config.data_bits)?;
+        self.port.set_parity(config.parity)?;
+        self.port.set_stop_bits(config.stop_bits)?;
+        self.port.set_flow_control(config.flow_control)?;
+        Ok(())
+    }
+
+    /// A timeout of `None` blocks until data arrives.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), Error> {
+        Ok(self.port.set_timeout(timeout.unwrap_or(BLOCKING_TIMEOUT))?)
+    }
+
+    /// Discards anything waiting in the input and output buffers.
+    pub fn clear(&mut self) -> Result<(), Error> {
+        Ok(self.port.clear(ClearBuffer::All)?)
+    }
+
+    pub fn get_name(&self) -> Option<String> {
+        self.port.name()
+    }
 }
 
-#[cfg(test)]
+impl Read for SerialConnection {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        self.port.read(buf)
+    }
+}
+
+impl Write for SerialConnection {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        self.port.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        self.port.flush()
+    }
+}
+
+#[cfg(all(test, unix))]
 mod tests {
-    use super::*;
+    use std::{
+        io::{Error, Read, Write},
+        time::Duration,
+    };
+
+    use super::{SerialConfig, SerialConnection};
 
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn test_pseudo_terminal_round_trip() -> Result<(), Error> {
+        let (mut host, mut instrument) = SerialConnection::pair(&SerialConfig::default())?;
+        instrument.set_timeout(Some(Duration::from_secs(5)))?;
+
+        host.write_all(b"*IDN?\n")?;
+        host.flush()?;
+
+        let mut request: [u8; 6] = [0; 6];
+        instrument.read_exact(&mut request)?;
+
+        assert_eq!(&request, b"*IDN?\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_timeout() -> Result<(), Error> {
+        let (_host, mut instrument) = SerialConnection::pair(&SerialConfig::default())?;
+        instrument.set_timeout(Some(Duration::from_millis(20)))?;
+
+        let mut response: [u8; 1] = [0; 1];
+        let error: Error = instrument
+            .read(&mut response)
+            .expect_err("Reading an idle port should time out");
+
+        assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+        Ok(())
     }
 }
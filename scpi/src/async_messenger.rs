@@ -0,0 +1,487 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Tokio counterpart of `Messenger`, enabled with the `tokio` feature.
+
+use std::{
+    future::Future,
+    io::{Error, ErrorKind},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    pin::Pin,
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, UdpSocket},
+    time::Instant,
+};
+
+use crate::block::{
+    decode_block_elements, encode_block_elements, encode_definite_block, encode_indefinite_block,
+    BlockElement, ByteOrder,
+};
+use crate::connection_options::ConnectionOptions;
+use crate::duty_cycle::DutyCycleMessage;
+use crate::error::ScpiError;
+use crate::framing::{
+    decode_response, encode_block_message, encode_message, ReadBuffer, READ_CHUNK_SIZE,
+};
+use crate::networking::{is_response_source, NetworkMode, UdpTransport};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Async byte transport. Both I/O futures must be cancellation safe: dropping one before it
+/// completes must leave no bytes written or consumed.
+pub trait AsyncTransport: Send {
+    fn write_bytes<'a>(&'a mut self, bytes: &'a [u8]) -> BoxFuture<'a, Result<usize, ScpiError>>;
+
+    fn read_bytes<'a>(
+        &'a mut self,
+        buffer: &'a mut [u8],
+    ) -> BoxFuture<'a, Result<usize, ScpiError>>;
+
+    /// Discards any input that has already arrived.
+    fn clear(&mut self) -> Result<(), ScpiError>;
+}
+
+/* ********************************************************************************************** */
+/*                                          UDP Transport                                         */
+/* ********************************************************************************************** */
+
+pub struct AsyncUdpTransport {
+    socket: UdpSocket,
+    destination: SocketAddr,
+}
+
+impl AsyncUdpTransport {
+    pub fn new(socket: UdpSocket, destination: SocketAddr) -> Self {
+        Self {
+            socket,
+            destination,
+        }
+    }
+
+    pub async fn bind(local_port: u16, destination: SocketAddr) -> Result<Self, ScpiError> {
+        let local_address: SocketAddr = match destination {
+            SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), local_port),
+            SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), local_port),
+        };
+        let socket: UdpSocket = UdpSocket::bind(local_address).await?;
+        Ok(Self::new(socket, destination))
+    }
+
+    /// Joins `group` with the same options as the blocking `UdpTransport::join_multicast`.
+    pub fn join_multicast(
+        local_port: u16,
+        group: SocketAddr,
+        options: &ConnectionOptions,
+    ) -> Result<Self, ScpiError> {
+        let (socket, destination) =
+            UdpTransport::join_multicast(local_port, group, options.get_multicast())?.into_parts();
+        socket.set_nonblocking(true)?;
+        Ok(Self::new(UdpSocket::from_std(socket)?, destination))
+    }
+}
+
+impl AsyncTransport for AsyncUdpTransport {
+    fn write_bytes<'a>(&'a mut self, bytes: &'a [u8]) -> BoxFuture<'a, Result<usize, ScpiError>> {
+        Box::pin(async move { Ok(self.socket.send_to(bytes, self.destination).await?) })
+    }
+
+    fn read_bytes<'a>(
+        &'a mut self,
+        buffer: &'a mut [u8],
+    ) -> BoxFuture<'a, Result<usize, ScpiError>> {
+        Box::pin(async move {
+            let (received, source): (usize, SocketAddr) = self.socket.recv_from(buffer).await?;
+            match is_response_source(&self.destination, &source) {
+                true => Ok(received),
+                false => Ok(0),
+            }
+        })
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        let mut chunk: Vec<u8> = vec![0; READ_CHUNK_SIZE];
+        loop {
+            match self.socket.try_recv_from(&mut chunk) {
+                Ok(_) => continue,
+                Err(x) if x.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(x) => return Err(x.into()),
+            }
+        }
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                          TCP Transport                                         */
+/* ********************************************************************************************** */
+
+pub struct AsyncTcpTransport {
+    stream: TcpStream,
+}
+
+impl AsyncTcpTransport {
+    pub fn new(stream: TcpStream) -> Self {
+        Self { stream }
+    }
+
+    pub async fn connect(
+        remote_address: SocketAddr,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Self, ScpiError> {
+        let stream: TcpStream = with_timeout(connect_timeout, async {
+            Ok(TcpStream::connect(remote_address).await?)
+        })
+        .await?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new(stream))
+    }
+}
+
+impl AsyncTransport for AsyncTcpTransport {
+    fn write_bytes<'a>(&'a mut self, bytes: &'a [u8]) -> BoxFuture<'a, Result<usize, ScpiError>> {
+        Box::pin(async move { Ok(self.stream.write(bytes).await?) })
+    }
+
+    fn read_bytes<'a>(
+        &'a mut self,
+        buffer: &'a mut [u8],
+    ) -> BoxFuture<'a, Result<usize, ScpiError>> {
+        Box::pin(async move {
+            match self.stream.read(buffer).await? {
+                0 => Err(ScpiError::Connection(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "Instrument closed the connection",
+                ))),
+                x => Ok(x),
+            }
+        })
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        let mut chunk: Vec<u8> = vec![0; READ_CHUNK_SIZE];
+        loop {
+            match self.stream.try_read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(_) => continue,
+                Err(x) if x.kind() == ErrorKind::WouldBlock => return Ok(()),
+                Err(x) => return Err(x.into()),
+            }
+        }
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                         Async Messenger                                        */
+/* ********************************************************************************************** */
+
+/// Futures may be dropped at any await point (for example by `tokio::select!` or
+/// `tokio::time::timeout`): a partially written message is finished before the next one is sent,
+/// and if it was a query, its response is awaited and thrown away first. When a query is dropped
+/// or times out after sending, the input received for it by the time of the next write is
+/// discarded so it cannot answer the next query. A response that arrives later than that still
+/// would; call `clear` before reusing a connection that stalled for long.
+pub struct AsyncMessenger {
+    transport: Box<dyn AsyncTransport>,
+    options: ConnectionOptions,
+    read_buffer: ReadBuffer,
+    pending_write: Vec<u8>,
+    /// The response the message in `pending_write` asks for, if it is a query.
+    pending_response: Option<ResponseKind>,
+}
+
+#[derive(Clone, Copy)]
+enum ResponseKind {
+    Text,
+    Block,
+}
+
+impl AsyncMessenger {
+    pub async fn new(
+        local_port: u16,
+        remote_port: u16,
+        remote_client: &IpAddr,
+        mode: &NetworkMode,
+        options: &ConnectionOptions,
+    ) -> Result<Self, ScpiError> {
+        let remote_address: SocketAddr = SocketAddr::new(*remote_client, remote_port);
+
+        let transport: Box<dyn AsyncTransport> = match mode {
+            NetworkMode::Udp => {
+                Box::new(AsyncUdpTransport::bind(local_port, remote_address).await?)
+            }
+            NetworkMode::Tcp => Box::new(
+                AsyncTcpTransport::connect(remote_address, options.get_connect_timeout()).await?,
+            ),
+            NetworkMode::UdpMulticast => Box::new(AsyncUdpTransport::join_multicast(
+                local_port,
+                remote_address,
+                options,
+            )?),
+            NetworkMode::TcpMulticast { .. } => {
+                return Err(ScpiError::Unsupported(
+                    "TCP multicast is not yet supported by AsyncMessenger",
+                ))
+            }
+            NetworkMode::Hislip => {
+                return Err(ScpiError::Unsupported(
+                    "HiSLIP is not yet supported by AsyncMessenger",
+                ))
+            }
+            NetworkMode::Serial { .. } => {
+                return Err(ScpiError::Unsupported(
+                    "Serial ports are not yet supported by AsyncMessenger",
+                ))
+            }
+        };
+
+        Ok(Self::from_transport(transport, options))
+    }
+
+    pub fn from_transport(transport: Box<dyn AsyncTransport>, options: &ConnectionOptions) -> Self {
+        Self {
+            transport,
+            options: options.clone(),
+            read_buffer: ReadBuffer::default(),
+            pending_write: Vec::new(),
+            pending_response: None,
+        }
+    }
+
+    pub async fn send_message(&mut self, message: &str) -> Result<usize, ScpiError> {
+        self.write_bytes(&encode_message(message, &self.options))
+            .await
+    }
+
+    pub async fn query(&mut self, message: &str) -> Result<String, ScpiError> {
+        let request: Vec<u8> = encode_message(message, &self.options);
+        self.write_query(&request, ResponseKind::Text).await?;
+        self.read_response().await
+    }
+
+    /// Like `query`, but bounds the whole exchange by `timeout` instead of the option timeouts.
+    pub async fn query_with_timeout(
+        &mut self,
+        message: &str,
+        timeout: Duration,
+    ) -> Result<String, ScpiError> {
+        if timeout.is_zero() {
+            return Err(ScpiError::InvalidArgument(
+                "Query timeout must be greater than zero".to_string(),
+            ));
+        }
+
+        with_timeout(Some(timeout), self.query(message)).await
+    }
+
+    pub async fn read_response(&mut self) -> Result<String, ScpiError> {
+        let terminator: String = self.options.get_read_terminator().to_string();
+        let raw_response: Vec<u8> = self.read_until(|x| x.take_response(&terminator)).await?;
+        decode_response(raw_response, &self.options)
+    }
+
+    pub async fn send_list_of_messages(&mut self, messages: &[&str]) -> Result<(), ScpiError> {
+        for message in messages {
+            self.send_message(message).await?;
+        }
+        Ok(())
+    }
+
+    /// Alternates the two messages, sleeping on tokio timers against absolute deadlines instead of
+    /// spinning. Runs `cycles` times, or until the future is dropped when `None`. Returns the
+    /// number of completed cycles.
+    pub async fn send_duty_cycled_message(
+        &mut self,
+        message: &DutyCycleMessage<'_>,
+        cycles: Option<usize>,
+    ) -> Result<usize, ScpiError> {
+        let (first_time, second_time): (u64, u64) = message.get_times();
+        let (first_message, second_message): (&str, &str) = message.get_messages();
+
+        let mut deadline: Instant = Instant::now();
+        let mut completed: usize = 0;
+        while cycles.is_none_or(|x| completed < x) {
+            self.send_message(first_message).await?;
+            deadline += Duration::from_micros(first_time);
+            tokio::time::sleep_until(deadline).await;
+
+            self.send_message(second_message).await?;
+            deadline += Duration::from_micros(second_time);
+            tokio::time::sleep_until(deadline).await;
+
+            completed += 1;
+        }
+
+        Ok(completed)
+    }
+
+    /// Drops buffered input and any unsent remainder of a cancelled write.
+    pub fn clear(&mut self) -> Result<(), ScpiError> {
+        self.read_buffer.clear();
+        self.pending_write.clear();
+        self.pending_response = None;
+        self.transport.clear()
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                           Block Data                                           */
+/* ********************************************************************************************** */
+
+impl AsyncMessenger {
+    pub async fn send_block(&mut self, header: &str, data: &[u8]) -> Result<usize, ScpiError> {
+        let block: Vec<u8> = encode_definite_block(data)?;
+        self.send_raw_block(header, &block).await
+    }
+
+    pub async fn send_indefinite_block(
+        &mut self,
+        header: &str,
+        data: &[u8],
+    ) -> Result<usize, ScpiError> {
+        let block: Vec<u8> = encode_indefinite_block(data);
+        self.send_raw_block(header, &block).await
+    }
+
+    pub async fn send_binary_values<T: BlockElement>(
+        &mut self,
+        header: &str,
+        values: &[T],
+        order: ByteOrder,
+    ) -> Result<usize, ScpiError> {
+        self.send_block(header, &encode_block_elements(values, order))
+            .await
+    }
+
+    pub async fn query_block(&mut self, message: &str) -> Result<Vec<u8>, ScpiError> {
+        let request: Vec<u8> = encode_message(message, &self.options);
+        self.write_query(&request, ResponseKind::Block).await?;
+        self.read_block().await
+    }
+
+    pub async fn query_binary_values<T: BlockElement>(
+        &mut self,
+        message: &str,
+        order: ByteOrder,
+    ) -> Result<Vec<T>, ScpiError> {
+        let data: Vec<u8> = self.query_block(message).await?;
+        decode_block_elements(&data, order)
+    }
+
+    pub async fn read_block(&mut self) -> Result<Vec<u8>, ScpiError> {
+        let terminator: String = self.options.get_read_terminator().to_string();
+        self.read_until(|x| x.take_block(&terminator)).await
+    }
+
+    async fn send_raw_block(&mut self, header: &str, block: &[u8]) -> Result<usize, ScpiError> {
+        let message: Vec<u8> = encode_block_message(header, block, &self.options);
+        self.write_bytes(&message).await
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                         Raw Socket I/O                                         */
+/* ********************************************************************************************** */
+
+impl AsyncMessenger {
+    async fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        self.settle_abandoned_exchange().await?;
+        self.pending_write.extend_from_slice(bytes);
+        self.flush_pending_write().await?;
+        Ok(bytes.len())
+    }
+
+    /// Like `write_bytes`, but the response counts as outstanding from the first byte written, so
+    /// dropping the query at any point keeps its response from answering the next one.
+    async fn write_query(&mut self, bytes: &[u8], response: ResponseKind) -> Result<(), ScpiError> {
+        self.settle_abandoned_exchange().await?;
+        self.pending_write.extend_from_slice(bytes);
+        self.pending_response = Some(response);
+        self.read_buffer.expect_response();
+        self.flush_pending_write().await?;
+        self.pending_response = None;
+        Ok(())
+    }
+
+    /// Cleans up after an earlier call that was dropped or failed. An unsent remainder is sent;
+    /// if it was a query, its response has not been asked for yet, so it is read and thrown away.
+    /// Otherwise any input for an abandoned query is discarded.
+    async fn settle_abandoned_exchange(&mut self) -> Result<(), ScpiError> {
+        self.flush_pending_write().await?;
+        if let Some(response) = self.pending_response {
+            let terminator: String = self.options.get_read_terminator().to_string();
+            let discarded: Result<Vec<u8>, ScpiError> = match response {
+                ResponseKind::Text => self.read_until(|x| x.take_response(&terminator)).await,
+                ResponseKind::Block => self.read_until(|x| x.take_block(&terminator)).await,
+            };
+            self.pending_response = None;
+            // An instrument that never answers should not block every later call.
+            match discarded {
+                Ok(_) | Err(ScpiError::Timeout) => (),
+                Err(x) => return Err(x),
+            }
+        }
+
+        if self.read_buffer.discard_if_stale() {
+            self.transport.clear()?;
+        }
+        Ok(())
+    }
+
+    async fn flush_pending_write(&mut self) -> Result<(), ScpiError> {
+        while !self.pending_write.is_empty() {
+            let timeout: Option<Duration> = self.options.get_write_timeout();
+            let written: usize =
+                with_timeout(timeout, self.transport.write_bytes(&self.pending_write)).await?;
+            self.pending_write.drain(..written);
+        }
+        Ok(())
+    }
+
+    /// Reads until `take` can split a complete response off the read buffer. Bytes are only
+    /// added to the buffer once a read completes, so dropping this future loses nothing.
+    async fn read_until<F: FnMut(&mut ReadBuffer) -> Result<Option<Vec<u8>>, ScpiError>>(
+        &mut self,
+        mut take: F,
+    ) -> Result<Vec<u8>, ScpiError> {
+        loop {
+            if let Some(x) = take(&mut self.read_buffer)? {
+                return Ok(x);
+            }
+
+            let mut chunk: Vec<u8> = vec![0; READ_CHUNK_SIZE];
+            let timeout: Option<Duration> = self.options.get_read_timeout();
+            let received: usize =
+                with_timeout(timeout, self.transport.read_bytes(&mut chunk)).await?;
+            self.read_buffer.extend(&chunk[..received]);
+        }
+    }
+}
+
+async fn with_timeout<T, F>(timeout: Option<Duration>, future: F) -> Result<T, ScpiError>
+where
+    F: Future<Output = Result<T, ScpiError>>,
+{
+    match timeout {
+        Some(x) => match tokio::time::timeout(x, future).await {
+            Ok(result) => result,
+            Err(_) => Err(ScpiError::Timeout),
+        },
+        None => future.await,
+    }
+}
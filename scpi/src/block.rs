@@ -0,0 +1,169 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use crate::error::ScpiError;
+
+const MAX_DEFINITE_LENGTH: usize = 999_999_999;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// Layout of a block header found at the start of a response.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockHeader {
+    /// `#<n><len>`: `header_length` bytes of header followed by `data_length` bytes of data.
+    Definite {
+        header_length: usize,
+        data_length: usize,
+    },
+    /// `#0`: data runs until the response terminator.
+    Indefinite,
+}
+
+/// A fixed-size value that can be packed into or unpacked from block data.
+pub trait BlockElement: Sized {
+    const SIZE: usize;
+
+    fn from_block_bytes(bytes: &[u8], order: ByteOrder) -> Self;
+    fn to_block_bytes(&self, order: ByteOrder) -> Vec<u8>;
+}
+
+macro_rules! impl_block_element {
+    ($($element:ty),*) => {
+        $(
+            impl BlockElement for $element {
+                const SIZE: usize = std::mem::size_of::<$element>();
+
+                fn from_block_bytes(bytes: &[u8], order: ByteOrder) -> Self {
+                    let mut raw: [u8; std::mem::size_of::<$element>()] =
+                        [0; std::mem::size_of::<$element>()];
+                    raw.copy_from_slice(bytes);
+                    match order {
+                        ByteOrder::LittleEndian => <$element>::from_le_bytes(raw),
+                        ByteOrder::BigEndian => <$element>::from_be_bytes(raw),
+                    }
+                }
+
+                fn to_block_bytes(&self, order: ByteOrder) -> Vec<u8> {
+                    match order {
+                        ByteOrder::LittleEndian => self.to_le_bytes().to_vec(),
+                        ByteOrder::BigEndian => self.to_be_bytes().to_vec(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+impl_block_element!(i8, u8, i16, u16, i32, u32, f32, f64);
+
+pub fn encode_definite_block(data: &[u8]) -> Result<Vec<u8>, ScpiError> {
+    if data.len() > MAX_DEFINITE_LENGTH {
+        return Err(ScpiError::InvalidArgument(format!(
+            "Block of {} bytes exceeds the definite-length limit of {} bytes",
+            data.len(),
+            MAX_DEFINITE_LENGTH
+        )));
+    }
+
+    let length: String = data.len().to_string();
+    let mut block: Vec<u8> = format!("#{}{}", length.len(), length).into_bytes();
+    block.extend_from_slice(data);
+    Ok(block)
+}
+
+pub fn encode_indefinite_block(data: &[u8]) -> Vec<u8> {
+    let mut block: Vec<u8> = b"#0".to_vec();
+    block.extend_from_slice(data);
+    block
+}
+
+/// Parses the header at the start of `buffer`, returning `None` if more bytes are needed.
+pub fn parse_block_header(buffer: &[u8]) -> Result<Option<BlockHeader>, ScpiError> {
+    let (marker, digit_count) = match buffer {
+        [] | [_] => return Ok(None),
+        [marker, digit_count, ..] => (*marker, *digit_count),
+    };
+
+    if marker != b'#' {
+        return Err(ScpiError::Parse(format!(
+            "Block data must start with '#', found {:?}",
+            marker as char
+        )));
+    }
+
+    if !digit_count.is_ascii_digit() {
+        return Err(ScpiError::Parse(format!(
+            "Invalid block length digit count {:?}",
+            digit_count as char
+        )));
+    }
+
+    let digit_count: usize = (digit_count - b'0') as usize;
+    if digit_count == 0 {
+        return Ok(Some(BlockHeader::Indefinite));
+    }
+
+    let header_length: usize = 2 + digit_count;
+    if buffer.len() < header_length {
+        return Ok(None);
+    }
+
+    let length_digits: &[u8] = &buffer[2..header_length];
+    if !length_digits.iter().all(u8::is_ascii_digit) {
+        return Err(ScpiError::Parse(format!(
+            "Invalid block length {:?}",
+            String::from_utf8_lossy(length_digits)
+        )));
+    }
+
+    let data_length: usize = length_digits
+        .iter()
+        .fold(0, |total, digit| total * 10 + (digit - b'0') as usize);
+
+    Ok(Some(BlockHeader::Definite {
+        header_length,
+        data_length,
+    }))
+}
+
+pub fn decode_block_elements<T: BlockElement>(
+    data: &[u8],
+    order: ByteOrder,
+) -> Result<Vec<T>, ScpiError> {
+    if !data.len().is_multiple_of(T::SIZE) {
+        return Err(ScpiError::Parse(format!(
+            "Block of {} bytes is not a multiple of the {} byte element size",
+            data.len(),
+            T::SIZE
+        )));
+    }
+
+    Ok(data
+        .chunks_exact(T::SIZE)
+        .map(|chunk| T::from_block_bytes(chunk, order))
+        .collect())
+}
+
+pub fn encode_block_elements<T: BlockElement>(values: &[T], order: ByteOrder) -> Vec<u8> {
+    values
+        .iter()
+        .flat_map(|value| value.to_block_bytes(order))
+        .collect()
+}
@@ -0,0 +1,339 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Typed SCPI command builder, so parameters never have to be formatted by hand. For example
+//! `Command::new("SOUR:VOLT").with_parameter(Parameter::Nr2(3.3).with_unit("V"))` builds
+//! `SOUR:VOLT 3.3V`.
+
+use std::fmt::Write;
+
+use crate::error::ScpiError;
+
+/// IEEE 488.2 limits character program data to 12 characters.
+const MAX_CHARACTER_DATA_LENGTH: usize = 12;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Parameter {
+    /// Integer, e.g. `10`.
+    Nr1(i64),
+    /// Fixed-point decimal, e.g. `3.3`. Non-finite values become `NAN`, `INF` and `NINF`.
+    Nr2(f64),
+    /// Decimal with exponent, e.g. `3.3E-3`. Non-finite values become `NAN`, `INF` and `NINF`.
+    Nr3(f64),
+    /// `ON` or `OFF`.
+    Boolean(bool),
+    /// Unquoted mnemonic such as `MIN`, `MAX`, `DEF` or `BUS`.
+    Character(String),
+    /// Double-quoted string; embedded double quotes are doubled.
+    String(String),
+    ChannelList(ChannelList),
+    /// A numeric parameter followed by a suffix unit, e.g. `100mV`.
+    WithUnit(Box<Parameter>, String),
+}
+
+impl Parameter {
+    /// Attaches a suffix unit such as `V`, `mV` or `HZ`. Only numeric parameters take units.
+    pub fn with_unit(self, unit: &str) -> Self {
+        Self::WithUnit(Box::new(self), unit.to_string())
+    }
+
+    pub fn format(&self) -> Result<String, ScpiError> {
+        match self {
+            Self::Nr1(x) => Ok(x.to_string()),
+            Self::Nr2(x) => Ok(format_float(*x, false)),
+            Self::Nr3(x) => Ok(format_float(*x, true)),
+            Self::Boolean(x) => Ok(match x {
+                true => "ON".to_string(),
+                false => "OFF".to_string(),
+            }),
+            Self::Character(x) => {
+                let valid: bool = x.len() <= MAX_CHARACTER_DATA_LENGTH
+                    && x.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && x.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                match valid {
+                    true => Ok(x.clone()),
+                    false => Err(ScpiError::InvalidArgument(format!(
+                        "'{}' is not valid character data",
+                        x
+                    ))),
+                }
+            }
+            Self::String(x) => Ok(format!("\"{}\"", x.replace('"', "\"\""))),
+            Self::ChannelList(x) => x.format(),
+            Self::WithUnit(parameter, unit) => {
+                if !matches!(**parameter, Self::Nr1(_) | Self::Nr2(_) | Self::Nr3(_)) {
+                    return Err(ScpiError::InvalidArgument(format!(
+                        "Unit '{}' can only follow a numeric parameter",
+                        unit
+                    )));
+                }
+
+                let valid: bool = unit.starts_with(|c: char| c.is_ascii_alphabetic())
+                    && unit.chars().all(|c| c.is_ascii_alphanumeric() || c == '/');
+                match valid {
+                    true => Ok(format!("{}{}", parameter.format()?, unit)),
+                    false => Err(ScpiError::InvalidArgument(format!(
+                        "'{}' is not a valid suffix unit",
+                        unit
+                    ))),
+                }
+            }
+        }
+    }
+}
+
+fn format_float(value: f64, exponent: bool) -> String {
+    match (value.is_nan(), value.is_infinite(), exponent) {
+        (true, _, _) => "NAN".to_string(),
+        (_, true, _) if value > 0.0 => "INF".to_string(),
+        (_, true, _) => "NINF".to_string(),
+        (_, _, true) => format!("{:E}", value),
+        (_, _, false) => format!("{}", value),
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                          Channel Lists                                         */
+/* ********************************************************************************************** */
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelEntry {
+    Channel(u32),
+    /// Inclusive range, written `first:last`.
+    Range(u32, u32),
+}
+
+/// A channel list such as `(@1,2:5)`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChannelList {
+    entries: Vec<ChannelEntry>,
+}
+
+impl ChannelList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_channel(mut self, channel: u32) -> Self {
+        self.entries.push(ChannelEntry::Channel(channel));
+        self
+    }
+
+    pub fn with_range(mut self, first: u32, last: u32) -> Self {
+        self.entries.push(ChannelEntry::Range(first, last));
+        self
+    }
+
+    pub fn get_entries(&self) -> &[ChannelEntry] {
+        &self.entries
+    }
+
+    /// Every channel in the list, with ranges expanded in the order they were written.
+    pub fn channels(&self) -> Vec<u32> {
+        self.entries
+            .iter()
+            .flat_map(|x| -> Box<dyn Iterator<Item = u32>> {
+                match *x {
+                    ChannelEntry::Channel(channel) => Box::new(std::iter::once(channel)),
+                    ChannelEntry::Range(first, last) if first <= last => Box::new(first..=last),
+                    ChannelEntry::Range(first, last) => Box::new((last..=first).rev()),
+                }
+            })
+            .collect()
+    }
+
+    pub fn format(&self) -> Result<String, ScpiError> {
+        if self.entries.is_empty() {
+            return Err(ScpiError::InvalidArgument(
+                "Channel list is empty".to_string(),
+            ));
+        }
+
+        let entries: Vec<String> = self
+            .entries
+            .iter()
+            .map(|x| match x {
+                ChannelEntry::Channel(channel) => channel.to_string(),
+                ChannelEntry::Range(first, last) => format!("{}:{}", first, last),
+            })
+            .collect();
+        Ok(format!("(@{})", entries.join(",")))
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                            Commands                                            */
+/* ********************************************************************************************** */
+
+/// A single program header with its parameters.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Command {
+    header: String,
+    query: bool,
+    parameters: Vec<Parameter>,
+}
+
+impl Command {
+    pub fn new(header: &str) -> Self {
+        Self {
+            header: header.to_string(),
+            query: false,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// A query: `header` gets a `?` appended, before any parameters.
+    pub fn query(header: &str) -> Self {
+        Self {
+            query: true,
+            ..Self::new(header)
+        }
+    }
+
+    pub fn with_parameter(mut self, parameter: Parameter) -> Self {
+        self.parameters.push(parameter);
+        self
+    }
+
+    pub fn with_nr1(self, value: i64) -> Self {
+        self.with_parameter(Parameter::Nr1(value))
+    }
+
+    pub fn with_nr2(self, value: f64) -> Self {
+        self.with_parameter(Parameter::Nr2(value))
+    }
+
+    pub fn with_nr3(self, value: f64) -> Self {
+        self.with_parameter(Parameter::Nr3(value))
+    }
+
+    pub fn with_boolean(self, value: bool) -> Self {
+        self.with_parameter(Parameter::Boolean(value))
+    }
+
+    pub fn with_character(self, value: &str) -> Self {
+        self.with_parameter(Parameter::Character(value.to_string()))
+    }
+
+    pub fn with_string(self, value: &str) -> Self {
+        self.with_parameter(Parameter::String(value.to_string()))
+    }
+
+    pub fn with_channel_list(self, value: ChannelList) -> Self {
+        self.with_parameter(Parameter::ChannelList(value))
+    }
+
+    pub fn build(&self) -> Result<String, ScpiError> {
+        let valid: bool = !self.header.is_empty()
+            && self
+                .header
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, ':' | '*' | '_'));
+        if !valid {
+            return Err(ScpiError::InvalidArgument(format!(
+                "'{}' is not a valid command header",
+                self.header
+            )));
+        }
+
+        let mut command: String = self.header.clone();
+        if self.query {
+            command.push('?');
+        }
+
+        for (index, parameter) in self.parameters.iter().enumerate() {
+            let separator: char = match index {
+                0 => ' ',
+                _ => ',',
+            };
+            // Writing to a String cannot fail.
+            let _ = write!(command, "{}{}", separator, parameter.format()?);
+        }
+
+        Ok(command)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl Command {
+    pub fn get_header(&self) -> &str {
+        &self.header
+    }
+
+    pub fn is_query(&self) -> bool {
+        self.query
+    }
+
+    pub fn get_parameters(&self) -> &[Parameter] {
+        &self.parameters
+    }
+}
+
+/// Several commands sent as one message.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CompoundCommand {
+    // Each command with whether it restarts from the root of the command tree.
+    commands: Vec<(Command, bool)>,
+}
+
+impl CompoundCommand {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends with `;`, so a header is resolved relative to the previous command's subsystem.
+    pub fn with_command(mut self, command: Command) -> Self {
+        self.commands.push((command, false));
+        self
+    }
+
+    /// Appends with `;:`, so the header is resolved from the root of the command tree.
+    pub fn with_root_command(mut self, command: Command) -> Self {
+        self.commands.push((command, true));
+        self
+    }
+
+    pub fn is_query(&self) -> bool {
+        self.commands.iter().any(|(x, _)| x.is_query())
+    }
+
+    pub fn build(&self) -> Result<String, ScpiError> {
+        if self.commands.is_empty() {
+            return Err(ScpiError::InvalidArgument(
+                "Compound command is empty".to_string(),
+            ));
+        }
+
+        let mut message: String = String::new();
+        for (index, (command, root)) in self.commands.iter().enumerate() {
+            let built: String = command.build()?;
+            // Common commands and headers that already start at the root need no extra colon.
+            let needs_colon: bool = *root && index > 0 && !built.starts_with([':', '*']);
+            if index > 0 {
+                message.push(';');
+            }
+            if needs_colon {
+                message.push(':');
+            }
+            message.push_str(&built);
+        }
+
+        Ok(message)
+    }
+}
@@ -0,0 +1,383 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Optional model of an instrument's command headers, used to reject typos and to rewrite headers
+//! in short (`VOLT`) or long (`VOLTAGE`) form before anything is sent.
+//!
+//! Headers are defined the way instrument manuals write them: the upper-case part of a mnemonic is
+//! its short form, `[...]` marks optional nodes, `<n>` allows a numeric suffix and a trailing `?`
+//! marks a query-only header. Definition files hold one header per line, with `#` comments:
+//!
+//! ```text
+//! *RST
+//! *IDN?
+//! [SOURce]:VOLTage[:LEVel]
+//! MEASure<n>:VOLTage?
+//! ```
+
+use std::{path::Path, str::FromStr};
+
+use crate::error::ScpiError;
+
+/// How `CommandTree::normalize` rewrites the mnemonics of a header.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MnemonicForm {
+    /// Validate only, leaving the header as written.
+    #[default]
+    AsWritten,
+    Short,
+    Long,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Mnemonic {
+    short: String,
+    long: String,
+    optional: bool,
+    numeric_suffix: bool,
+}
+
+impl Mnemonic {
+    fn parse(definition: &str, optional: bool) -> Result<Self, ScpiError> {
+        let (name, numeric_suffix): (&str, bool) = match definition.strip_suffix("<n>") {
+            Some(x) => (x, true),
+            None => (definition, false),
+        };
+
+        let short: String = match name.strip_prefix('*') {
+            Some(x) if !x.is_empty() && x.chars().all(|c| c.is_ascii_alphabetic()) => {
+                name.to_ascii_uppercase()
+            }
+            _ => name
+                .chars()
+                .take_while(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+                .collect(),
+        };
+
+        let valid: bool = !short.is_empty()
+            && (name.starts_with('*') || name.chars().all(|c| c.is_ascii_alphanumeric()));
+        match valid {
+            true => Ok(Self {
+                short,
+                long: name.to_ascii_uppercase(),
+                optional,
+                numeric_suffix,
+            }),
+            false => Err(ScpiError::Parse(format!(
+                "'{}' is not a valid mnemonic",
+                definition
+            ))),
+        }
+    }
+
+    /// Returns the numeric suffix (possibly empty) if `token` is this mnemonic.
+    fn match_token<'a>(&self, token: &'a str) -> Option<&'a str> {
+        let upper: String = token.to_ascii_uppercase();
+        [&self.long, &self.short].into_iter().find_map(|form| {
+            match upper.strip_prefix(form.as_str()) {
+                Some("") => Some(""),
+                Some(x) if self.numeric_suffix && x.chars().all(|c| c.is_ascii_digit()) => {
+                    Some(&token[token.len() - x.len()..])
+                }
+                _ => None,
+            }
+        })
+    }
+
+    fn format(&self, token: &str, suffix: &str, form: MnemonicForm) -> String {
+        match form {
+            MnemonicForm::AsWritten => token.to_string(),
+            MnemonicForm::Short => format!("{}{}", self.short, suffix),
+            MnemonicForm::Long => format!("{}{}", self.long, suffix),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct HeaderPattern {
+    mnemonics: Vec<Mnemonic>,
+    query_only: bool,
+}
+
+impl HeaderPattern {
+    fn parse(definition: &str) -> Result<Self, ScpiError> {
+        let definition: &str = definition.trim();
+        let (path, query_only): (&str, bool) = match definition.strip_suffix('?') {
+            Some(x) => (x, true),
+            None => (definition, false),
+        };
+
+        let error = || ScpiError::Parse(format!("'{}' is not a valid header", definition));
+        let mut mnemonics: Vec<Mnemonic> = Vec::new();
+        let mut optional: bool = false;
+        let mut current: String = String::new();
+
+        for c in path.chars() {
+            if matches!(c, ':' | '[' | ']') && !current.is_empty() {
+                mnemonics.push(Mnemonic::parse(&current, optional)?);
+                current.clear();
+            }
+
+            match (c, optional) {
+                (':', _) => {}
+                ('[', false) => optional = true,
+                (']', true) => optional = false,
+                ('[' | ']', _) => return Err(error()),
+                _ => current.push(c),
+            }
+        }
+
+        if optional {
+            return Err(error());
+        }
+        if !current.is_empty() {
+            mnemonics.push(Mnemonic::parse(&current, false)?);
+        }
+        if mnemonics.iter().all(|x| x.optional) {
+            return Err(error());
+        }
+
+        Ok(Self {
+            mnemonics,
+            query_only,
+        })
+    }
+
+    /// Maps each token to the mnemonic it matched, skipping optional mnemonics as needed.
+    fn match_tokens(&self, tokens: &[&str]) -> Option<Vec<usize>> {
+        fn walk(pattern: &[Mnemonic], start: usize, tokens: &[&str]) -> Option<Vec<usize>> {
+            let Some(token) = tokens.first() else {
+                return pattern[start..].iter().all(|x| x.optional).then(Vec::new);
+            };
+            let mnemonic: &Mnemonic = pattern.get(start)?;
+
+            if mnemonic.match_token(token).is_some() {
+                if let Some(mut rest) = walk(pattern, start + 1, &tokens[1..]) {
+                    rest.insert(0, start);
+                    return Some(rest);
+                }
+            }
+
+            match mnemonic.optional {
+                true => walk(pattern, start + 1, tokens),
+                false => None,
+            }
+        }
+
+        walk(&self.mnemonics, 0, tokens)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                          Command Tree                                          */
+/* ********************************************************************************************** */
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CommandTree {
+    headers: Vec<HeaderPattern>,
+}
+
+impl CommandTree {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a header such as `[SOURce]:VOLTage[:LEVel]` or `MEASure<n>:VOLTage?`.
+    pub fn with_header(mut self, definition: &str) -> Result<Self, ScpiError> {
+        self.headers.push(HeaderPattern::parse(definition)?);
+        Ok(self)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ScpiError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    pub fn validate(&self, message: &str) -> Result<(), ScpiError> {
+        self.normalize(message, MnemonicForm::AsWritten).map(|_| ())
+    }
+
+    /// Checks every header of a possibly compound `message` and rewrites it in `form`. Parameters
+    /// are left untouched. Headers after a `;` are resolved relative to the previous one, as an
+    /// instrument would.
+    pub fn normalize(&self, message: &str, form: MnemonicForm) -> Result<String, ScpiError> {
+        let mut normalized: Vec<String> = Vec::new();
+
+        for unit in self.resolve(message) {
+            let unit: ResolvedUnit = unit?;
+            let pattern: &HeaderPattern = &self.headers[unit.header];
+            let rewritten: Vec<String> = unit.tokens[unit.inherited..]
+                .iter()
+                .map(|(token, index)| {
+                    let mnemonic: &Mnemonic = &pattern.mnemonics[*index];
+                    let suffix: &str = mnemonic.match_token(token).unwrap_or("");
+                    mnemonic.format(token, suffix, form)
+                })
+                .collect();
+
+            normalized.push(format!(
+                "{}{}{}{}",
+                match unit.rooted {
+                    true => ":",
+                    false => "",
+                },
+                rewritten.join(":"),
+                match unit.query {
+                    true => "?",
+                    false => "",
+                },
+                unit.parameters
+            ));
+        }
+
+        Ok(normalized.join(";"))
+    }
+
+    /// Matches each program message unit of `message` against the tree, stopping after the first
+    /// one that does not match.
+    pub(crate) fn resolve<'a>(&self, message: &'a str) -> Vec<Result<ResolvedUnit<'a>, ScpiError>> {
+        let mut resolved: Vec<Result<ResolvedUnit<'a>, ScpiError>> = Vec::new();
+        let mut path: Vec<&str> = Vec::new();
+
+        for unit in split_program_units(message) {
+            let trimmed: &str = unit.trim_start();
+            let (header, parameters): (&str, &str) = match trimmed.find(char::is_whitespace) {
+                Some(x) => trimmed.split_at(x),
+                None => (trimmed, ""),
+            };
+            let (header, query): (&str, bool) = match header.strip_suffix('?') {
+                Some(x) => (x, true),
+                None => (header, false),
+            };
+
+            let rooted: bool = header.starts_with(':');
+            let common: bool = header.starts_with('*');
+            let tokens: Vec<&str> = header.trim_start_matches(':').split(':').collect();
+            let prefix: Vec<&str> = match rooted || common {
+                true => Vec::new(),
+                false => path.clone(),
+            };
+            let full: Vec<&str> = prefix.iter().chain(tokens.iter()).copied().collect();
+
+            let matched: Option<(usize, Vec<usize>)> = self
+                .headers
+                .iter()
+                .enumerate()
+                .find_map(|(index, pattern)| pattern.match_tokens(&full).map(|x| (index, x)));
+            let (index, indices): (usize, Vec<usize>) = match matched {
+                Some(x) => x,
+                None => {
+                    resolved.push(Err(ScpiError::InvalidArgument(format!(
+                        "Unknown command header '{}'",
+                        unit.trim()
+                    ))));
+                    break;
+                }
+            };
+            if self.headers[index].query_only && !query {
+                resolved.push(Err(ScpiError::InvalidArgument(format!(
+                    "'{}' can only be queried",
+                    header
+                ))));
+                break;
+            }
+
+            if !common {
+                path = full[..full.len() - 1].to_vec();
+            }
+            resolved.push(Ok(ResolvedUnit {
+                header: index,
+                rooted,
+                query,
+                tokens: full.into_iter().zip(indices).collect(),
+                inherited: prefix.len(),
+                parameters,
+            }));
+        }
+
+        resolved
+    }
+
+    /// Numeric suffixes of the mnemonics in `unit` that accept one, in order. Mnemonics written
+    /// without a suffix, or skipped because they are optional, count as 1.
+    pub(crate) fn get_suffixes(&self, unit: &ResolvedUnit) -> Vec<u32> {
+        let pattern: &HeaderPattern = &self.headers[unit.header];
+        pattern
+            .mnemonics
+            .iter()
+            .enumerate()
+            .filter(|(_, mnemonic)| mnemonic.numeric_suffix)
+            .map(|(index, mnemonic)| {
+                unit.tokens
+                    .iter()
+                    .find(|(_, x)| *x == index)
+                    .and_then(|(token, _)| mnemonic.match_token(token))
+                    .and_then(|x| x.parse().ok())
+                    .unwrap_or(1)
+            })
+            .collect()
+    }
+}
+
+/// One program message unit matched against a `CommandTree`.
+pub(crate) struct ResolvedUnit<'a> {
+    /// Index of the matched header, in the order headers were added.
+    pub(crate) header: usize,
+    pub(crate) rooted: bool,
+    pub(crate) query: bool,
+    /// Every token of the full header, including the ones inherited from the previous unit, with
+    /// the index of the mnemonic each matched.
+    tokens: Vec<(&'a str, usize)>,
+    inherited: usize,
+    /// Everything after the header, including the separating whitespace.
+    pub(crate) parameters: &'a str,
+}
+
+impl FromStr for CommandTree {
+    type Err = ScpiError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        text.lines()
+            .map(|x| match x.split_once('#') {
+                Some((definition, _)) => definition.trim(),
+                None => x.trim(),
+            })
+            .filter(|x| !x.is_empty())
+            .try_fold(CommandTree::new(), |tree, x| tree.with_header(x))
+    }
+}
+
+/// Splits a message at the `;` separating program message units, ignoring those inside quotes.
+fn split_program_units(message: &str) -> Vec<&str> {
+    let mut units: Vec<&str> = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut start: usize = 0;
+
+    for (index, c) in message.char_indices() {
+        match (quote, c) {
+            (Some(x), _) if c == x => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ';') => {
+                units.push(&message[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    units.push(&message[start..]);
+    units
+}
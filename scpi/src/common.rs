@@ -0,0 +1,245 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Results of the IEEE 488.2 common queries.
+
+use std::{
+    fmt::{Display, Formatter},
+    time::Duration,
+};
+
+use crate::error::ScpiError;
+use crate::response::{parse_integer, FromResponse};
+
+/// The four fields of an `*IDN?` response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identification {
+    manufacturer: String,
+    model: String,
+    serial_number: String,
+    firmware: String,
+}
+
+impl Identification {
+    /// Parses `<manufacturer>,<model>,<serial>,<firmware>`. Anything after the third comma counts as
+    /// firmware, since some instruments list several versions there.
+    pub fn parse(response: &str) -> Result<Self, ScpiError> {
+        let fields: Vec<&str> = response.trim().splitn(4, ',').map(str::trim).collect();
+        match fields.as_slice() {
+            [manufacturer, model, serial_number, firmware] => Ok(Self {
+                manufacturer: manufacturer.to_string(),
+                model: model.to_string(),
+                serial_number: serial_number.to_string(),
+                firmware: firmware.to_string(),
+            }),
+            _ => Err(ScpiError::Parse(format!(
+                "'{}' is not an *IDN? response",
+                response.trim()
+            ))),
+        }
+    }
+}
+
+impl FromResponse for Identification {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        Self::parse(response)
+    }
+}
+
+impl Display for Identification {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{},{},{},{}",
+            self.manufacturer, self.model, self.serial_number, self.firmware
+        )
+    }
+}
+
+/// The Standard Event Status Register, as read with `*ESR?`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StandardEventStatus {
+    bits: u8,
+}
+
+impl StandardEventStatus {
+    pub const OPERATION_COMPLETE: u8 = 1 << 0;
+    pub const REQUEST_CONTROL: u8 = 1 << 1;
+    pub const QUERY_ERROR: u8 = 1 << 2;
+    pub const DEVICE_DEPENDENT_ERROR: u8 = 1 << 3;
+    pub const EXECUTION_ERROR: u8 = 1 << 4;
+    pub const COMMAND_ERROR: u8 = 1 << 5;
+    pub const USER_REQUEST: u8 = 1 << 6;
+    pub const POWER_ON: u8 = 1 << 7;
+
+    /// Every bit that reports an error.
+    pub const ERRORS: u8 = Self::QUERY_ERROR
+        | Self::DEVICE_DEPENDENT_ERROR
+        | Self::EXECUTION_ERROR
+        | Self::COMMAND_ERROR;
+
+    pub fn new(bits: u8) -> Self {
+        Self { bits }
+    }
+
+    pub fn is_set(&self, mask: u8) -> bool {
+        self.bits & mask != 0
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.is_set(Self::ERRORS)
+    }
+
+    pub fn operation_complete(&self) -> bool {
+        self.is_set(Self::OPERATION_COMPLETE)
+    }
+
+    pub fn request_control(&self) -> bool {
+        self.is_set(Self::REQUEST_CONTROL)
+    }
+
+    pub fn query_error(&self) -> bool {
+        self.is_set(Self::QUERY_ERROR)
+    }
+
+    pub fn device_dependent_error(&self) -> bool {
+        self.is_set(Self::DEVICE_DEPENDENT_ERROR)
+    }
+
+    pub fn execution_error(&self) -> bool {
+        self.is_set(Self::EXECUTION_ERROR)
+    }
+
+    pub fn command_error(&self) -> bool {
+        self.is_set(Self::COMMAND_ERROR)
+    }
+
+    pub fn user_request(&self) -> bool {
+        self.is_set(Self::USER_REQUEST)
+    }
+
+    pub fn power_on(&self) -> bool {
+        self.is_set(Self::POWER_ON)
+    }
+}
+
+impl FromResponse for StandardEventStatus {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        Ok(Self::new(parse_register(response)?))
+    }
+}
+
+const DEFAULT_COMPLETION_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How `Messenger::send_and_wait` finds out that the instrument has finished.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompletionMethod {
+    /// Sends `*OPC?` and blocks on its answer. Simplest, but ties up the connection.
+    #[default]
+    OperationCompleteQuery,
+    /// Sends `*OPC` and polls `*ESR?` until the Operation Complete bit is set.
+    EventStatusPolling,
+    /// Sends `*OPC` with Operation Complete enabled in `*ESE`, then polls `*STB?` for the event
+    /// summary bit. The previous `*ESE` mask is restored afterwards.
+    StatusBytePolling,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompletionOptions {
+    method: CompletionMethod,
+    timeout: Duration,
+    poll_interval: Duration,
+}
+
+impl Default for CompletionOptions {
+    fn default() -> Self {
+        Self {
+            method: CompletionMethod::default(),
+            timeout: DEFAULT_COMPLETION_TIMEOUT,
+            poll_interval: DEFAULT_POLL_INTERVAL,
+        }
+    }
+}
+
+impl CompletionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_method(mut self, method: CompletionMethod) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Only used by the polling methods.
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn get_method(&self) -> CompletionMethod {
+        self.method
+    }
+
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    pub fn get_poll_interval(&self) -> Duration {
+        self.poll_interval
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl Identification {
+    pub fn get_manufacturer(&self) -> &str {
+        &self.manufacturer
+    }
+
+    pub fn get_model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn get_serial_number(&self) -> &str {
+        &self.serial_number
+    }
+
+    pub fn get_firmware(&self) -> &str {
+        &self.firmware
+    }
+}
+
+impl StandardEventStatus {
+    pub fn get_bits(&self) -> u8 {
+        self.bits
+    }
+}
+
+/// Parses an 8-bit register value such as an `*ESR?` or `*STB?` response.
+pub(crate) fn parse_register(response: &str) -> Result<u8, ScpiError> {
+    let value: i64 = parse_integer(response)?;
+    u8::try_from(value)
+        .map_err(|_| ScpiError::Parse(format!("Register value {} does not fit in 8 bits", value)))
+}
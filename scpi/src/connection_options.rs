@@ -0,0 +1,190 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::{net::Ipv4Addr, time::Duration};
+
+const DEFAULT_WRITE_TERMINATOR: &str = "\r\n";
+const DEFAULT_READ_TERMINATOR: &str = "\n";
+
+/// Per-connection settings used by a `Messenger`. Timeouts of `None` block indefinitely.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    write_terminator: String,
+    read_terminator: String,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    connect_timeout: Option<Duration>,
+    trim: bool,
+    multicast: MulticastOptions,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            write_terminator: DEFAULT_WRITE_TERMINATOR.to_string(),
+            read_terminator: DEFAULT_READ_TERMINATOR.to_string(),
+            read_timeout: None,
+            write_timeout: None,
+            connect_timeout: None,
+            trim: true,
+            multicast: MulticastOptions::default(),
+        }
+    }
+}
+
+impl ConnectionOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_write_terminator(mut self, terminator: &str) -> Self {
+        self.write_terminator = terminator.to_string();
+        self
+    }
+
+    pub fn with_read_terminator(mut self, terminator: &str) -> Self {
+        self.read_terminator = terminator.to_string();
+        self
+    }
+
+    pub fn with_read_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.read_timeout = timeout;
+        self
+    }
+
+    pub fn with_write_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    pub fn with_connect_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// Whether outgoing messages and incoming responses have surrounding whitespace removed.
+    pub fn with_trim(mut self, trim: bool) -> Self {
+        self.trim = trim;
+        self
+    }
+
+    pub fn with_multicast(mut self, multicast: MulticastOptions) -> Self {
+        self.multicast = multicast;
+        self
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl ConnectionOptions {
+    pub fn get_write_terminator(&self) -> &str {
+        &self.write_terminator
+    }
+
+    pub fn get_read_terminator(&self) -> &str {
+        &self.read_terminator
+    }
+
+    pub fn get_read_timeout(&self) -> Option<Duration> {
+        self.read_timeout
+    }
+
+    pub fn get_write_timeout(&self) -> Option<Duration> {
+        self.write_timeout
+    }
+
+    pub fn get_connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
+
+    pub fn get_trim(&self) -> bool {
+        self.trim
+    }
+
+    pub fn get_multicast(&self) -> &MulticastOptions {
+        &self.multicast
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                        Multicast Options                                       */
+/* ********************************************************************************************** */
+
+/// Settings used when joining a multicast group. IPv4 selects the interface by address, IPv6 by
+/// index; the defaults let the OS choose. `hops` is the IPv4 TTL or the IPv6 hop limit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MulticastOptions {
+    interface_v4: Ipv4Addr,
+    interface_index: u32,
+    hops: Option<u32>,
+    loopback: bool,
+}
+
+impl Default for MulticastOptions {
+    fn default() -> Self {
+        Self {
+            interface_v4: Ipv4Addr::UNSPECIFIED,
+            interface_index: 0,
+            hops: None,
+            loopback: true,
+        }
+    }
+}
+
+impl MulticastOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_interface_v4(mut self, interface: Ipv4Addr) -> Self {
+        self.interface_v4 = interface;
+        self
+    }
+
+    pub fn with_interface_index(mut self, index: u32) -> Self {
+        self.interface_index = index;
+        self
+    }
+
+    pub fn with_hops(mut self, hops: Option<u32>) -> Self {
+        self.hops = hops;
+        self
+    }
+
+    /// Whether our own multicast messages are looped back to this host.
+    pub fn with_loopback(mut self, loopback: bool) -> Self {
+        self.loopback = loopback;
+        self
+    }
+
+    pub fn get_interface_v4(&self) -> Ipv4Addr {
+        self.interface_v4
+    }
+
+    pub fn get_interface_index(&self) -> u32 {
+        self.interface_index
+    }
+
+    pub fn get_hops(&self) -> Option<u32> {
+        self.hops
+    }
+
+    pub fn get_loopback(&self) -> bool {
+        self.loopback
+    }
+}
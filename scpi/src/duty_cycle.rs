@@ -14,6 +14,13 @@
    limitations under the License.
 */
 
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex, MutexGuard,
+};
+
+use crate::run_control::StopHandle;
+
 pub struct DutyCycleMessage<'a> {
     first_microsecond_period: u64,
     second_microsecond_period: u64,
@@ -53,3 +60,97 @@ impl<'a> DutyCycleMessage<'a> {
         (self.first_message, self.second_message)
     }
 }
+
+/* ********************************************************************************************** */
+/*                                       Background Runner                                        */
+/* ********************************************************************************************** */
+
+/// Owned copy of a `DutyCycleMessage` that a background runner re-reads before every message.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct LiveDutyCycle {
+    first_microsecond_period: u64,
+    second_microsecond_period: u64,
+    first_message: String,
+    second_message: String,
+}
+
+impl From<&DutyCycleMessage<'_>> for LiveDutyCycle {
+    fn from(message: &DutyCycleMessage<'_>) -> Self {
+        Self {
+            first_microsecond_period: message.first_microsecond_period,
+            second_microsecond_period: message.second_microsecond_period,
+            first_message: message.first_message.to_string(),
+            second_message: message.second_message.to_string(),
+        }
+    }
+}
+
+/// Controls a duty cycle running on a `Messenger`'s background thread. Changes apply from the
+/// next message the runner sends.
+#[derive(Clone, Debug)]
+pub struct DutyCycleHandle {
+    settings: Arc<Mutex<LiveDutyCycle>>,
+    stop: StopHandle,
+    running: Arc<AtomicBool>,
+}
+
+impl DutyCycleHandle {
+    pub(crate) fn new(message: &DutyCycleMessage) -> Self {
+        Self {
+            settings: Arc::new(Mutex::new(LiveDutyCycle::from(message))),
+            stop: StopHandle::new(),
+            running: Arc::new(AtomicBool::new(true)),
+        }
+    }
+
+    pub fn set_messages(&self, first_message: &str, second_message: &str) {
+        let mut settings: MutexGuard<LiveDutyCycle> = self.lock();
+        settings.first_message = first_message.to_string();
+        settings.second_message = second_message.to_string();
+    }
+
+    pub fn set_times(&self, first_time: u64, second_time: u64) {
+        let mut settings: MutexGuard<LiveDutyCycle> = self.lock();
+        settings.first_microsecond_period = first_time;
+        settings.second_microsecond_period = second_time;
+    }
+
+    /// Asks the runner to stop. Use `Messenger::stop_duty_cycle` to wait for it and get the
+    /// connection back.
+    pub fn stop(&self) {
+        self.stop.stop();
+    }
+
+    /// False once the runner has finished, whether it was stopped, hit a limit or failed.
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn get_stop_handle(&self) -> &StopHandle {
+        &self.stop
+    }
+
+    pub(crate) fn mark_finished(&self) {
+        self.running.store(false, Ordering::SeqCst);
+    }
+
+    /// Current message and microsecond period of the first (`true`) or second half of the cycle.
+    pub(crate) fn current(&self, first: bool) -> (String, u64) {
+        let settings: MutexGuard<LiveDutyCycle> = self.lock();
+        match first {
+            true => (
+                settings.first_message.clone(),
+                settings.first_microsecond_period,
+            ),
+            false => (
+                settings.second_message.clone(),
+                settings.second_microsecond_period,
+            ),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, LiveDutyCycle> {
+        // The settings are plain data, so a panic elsewhere cannot leave them half-written.
+        self.settings.lock().unwrap_or_else(|x| x.into_inner())
+    }
+}
@@ -0,0 +1,82 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::{
+    fmt::{Display, Formatter},
+    io::{Error, ErrorKind},
+    net::SocketAddr,
+};
+
+use crate::error_queue::InstrumentError;
+
+#[derive(Debug)]
+pub enum ScpiError {
+    /// The socket could not be opened, or failed while talking to the instrument.
+    Connection(Error),
+    /// The instrument did not answer before the configured timeout elapsed.
+    Timeout,
+    /// The requested network mode or feature is not available.
+    Unsupported(&'static str),
+    /// The instrument reported errors through its error queue.
+    Instrument(Vec<InstrumentError>),
+    /// A response from the instrument could not be decoded.
+    Parse(String),
+    /// An argument passed to the library was rejected before anything was sent.
+    InvalidArgument(String),
+    /// Some instruments of a `TcpMulticastGroup` failed, each listed with its own error.
+    Group(Vec<(SocketAddr, ScpiError)>),
+}
+
+impl Display for ScpiError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Connection(x) => write!(f, "Connection error: {}", x),
+            Self::Timeout => write!(f, "Timed out waiting for the instrument"),
+            Self::Unsupported(x) => write!(f, "Unsupported: {}", x),
+            Self::Instrument(x) => {
+                let errors: Vec<String> = x.iter().map(InstrumentError::to_string).collect();
+                write!(f, "Instrument reported errors: {}", errors.join("; "))
+            }
+            Self::Parse(x) => write!(f, "Parse error: {}", x),
+            Self::InvalidArgument(x) => write!(f, "Invalid argument: {}", x),
+            Self::Group(x) => {
+                let failures: Vec<String> = x
+                    .iter()
+                    .map(|(address, error)| format!("{}: {}", address, error))
+                    .collect();
+                write!(f, "Instruments failed: {}", failures.join("; "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScpiError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Connection(x) => Some(x),
+            _ => None,
+        }
+    }
+}
+
+impl From<Error> for ScpiError {
+    fn from(error: Error) -> Self {
+        match error.kind() {
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Self::Timeout,
+            _ => Self::Connection(error),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::fmt::{Display, Formatter};
+
+use crate::error::ScpiError;
+
+pub const ERROR_QUEUE_QUERY: &str = "SYST:ERR?";
+
+/// When a `Messenger` drains the instrument error queue on its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorCheckMode {
+    #[default]
+    Disabled,
+    AfterEachCommand,
+    AfterList,
+}
+
+/// A single `<code>,"<message>"` entry read from `SYSTem:ERRor?`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InstrumentError {
+    code: i32,
+    message: String,
+}
+
+impl InstrumentError {
+    pub fn new(code: i32, message: &str) -> Self {
+        Self {
+            code,
+            message: message.to_string(),
+        }
+    }
+
+    /// Code 0 (`0,"No error"`) marks an empty queue.
+    pub fn is_no_error(&self) -> bool {
+        self.code == 0
+    }
+}
+
+impl Display for InstrumentError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{},\"{}\"", self.code, self.message)
+    }
+}
+
+pub fn parse_error_queue_entry(response: &str) -> Result<InstrumentError, ScpiError> {
+    let (code, message) = match response.trim().split_once(',') {
+        Some(x) => x,
+        None => {
+            return Err(ScpiError::Parse(format!(
+                "Error queue entry {:?} is missing a ','",
+                response
+            )))
+        }
+    };
+
+    let code: i32 = match code.trim().parse::<i32>() {
+        Ok(x) => x,
+        Err(_) => {
+            return Err(ScpiError::Parse(format!(
+                "Error queue code {:?} is not an integer",
+                code
+            )))
+        }
+    };
+
+    let message: &str = message.trim();
+    let message: String = match message.strip_prefix('"').and_then(|x| x.strip_suffix('"')) {
+        Some(x) => x.replace("\"\"", "\""),
+        None => message.to_string(),
+    };
+
+    Ok(InstrumentError { code, message })
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl InstrumentError {
+    pub fn get_code(&self) -> i32 {
+        self.code
+    }
+
+    pub fn get_message(&self) -> &str {
+        &self.message
+    }
+}
@@ -0,0 +1,175 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Message framing shared by `Messenger` and `AsyncMessenger`. Nothing here does I/O: the
+//! messengers read bytes into a `ReadBuffer` until it can hand back a complete response.
+
+use crate::block::{parse_block_header, BlockHeader};
+use crate::connection_options::ConnectionOptions;
+use crate::error::ScpiError;
+
+pub(crate) const READ_CHUNK_SIZE: usize = 65_536;
+
+/// Bytes received but not yet consumed as a response.
+#[derive(Debug, Default)]
+pub(crate) struct ReadBuffer {
+    bytes: Vec<u8>,
+    /// Set while a response is expected but not yet taken. If the query is abandoned (timed out,
+    /// failed or cancelled), the rest of the response may still arrive and must not answer the next
+    /// query.
+    stale: bool,
+}
+
+impl ReadBuffer {
+    pub(crate) fn extend(&mut self, bytes: &[u8]) {
+        self.bytes.extend_from_slice(bytes);
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.bytes.clear();
+        self.stale = false;
+    }
+
+    /// Marks a query's response as outstanding from the moment the query starts writing, which
+    /// only matters where a write can be cancelled halfway.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn expect_response(&mut self) {
+        self.stale = true;
+    }
+
+    /// Called before writing: drops the input of an abandoned read and returns whether there was
+    /// one, in which case the caller should also clear the transport.
+    pub(crate) fn discard_if_stale(&mut self) -> bool {
+        let stale: bool = self.stale;
+        if stale {
+            self.clear();
+        }
+        stale
+    }
+
+    /// Takes one response up to and including `terminator`, or `None` if it has not all arrived.
+    pub(crate) fn take_response(&mut self, terminator: &str) -> Result<Option<Vec<u8>>, ScpiError> {
+        let terminator: &[u8] = terminator.as_bytes();
+        if terminator.is_empty() {
+            return Err(ScpiError::InvalidArgument(
+                "Read terminator must not be empty".to_string(),
+            ));
+        }
+
+        let response: Option<Vec<u8>> = self
+            .find_terminator(terminator, 0)
+            .map(|end| self.bytes.drain(..end).collect());
+        self.stale = response.is_none();
+        Ok(response)
+    }
+
+    /// Takes one definite or indefinite block and its terminator, or `None` if it has not all
+    /// arrived. An indefinite block ends at the first read terminator.
+    pub(crate) fn take_block(&mut self, terminator: &str) -> Result<Option<Vec<u8>>, ScpiError> {
+        self.stale = true;
+        let terminator: &[u8] = terminator.as_bytes();
+        if terminator.is_empty() {
+            return Err(ScpiError::InvalidArgument(
+                "Read terminator must not be empty".to_string(),
+            ));
+        }
+
+        let (data_start, data_end, end): (usize, usize, usize) =
+            match parse_block_header(&self.bytes)? {
+                Some(BlockHeader::Definite {
+                    header_length,
+                    data_length,
+                }) => {
+                    let block_length: usize = header_length + data_length;
+                    match self.bytes.len() >= block_length {
+                        true => match self.find_terminator(terminator, block_length) {
+                            Some(end) => (header_length, block_length, end),
+                            None => return Ok(None),
+                        },
+                        false => return Ok(None),
+                    }
+                }
+                Some(BlockHeader::Indefinite) => match self.find_terminator(terminator, 2) {
+                    Some(end) => (2, end - terminator.len(), end),
+                    None => return Ok(None),
+                },
+                None => return Ok(None),
+            };
+
+        let data: Vec<u8> = self.bytes[data_start..data_end].to_vec();
+        self.bytes.drain(..end);
+        self.stale = false;
+        Ok(Some(data))
+    }
+
+    /// Index just past the first `terminator` at or after `start`.
+    fn find_terminator(&self, terminator: &[u8], start: usize) -> Option<usize> {
+        self.bytes
+            .get(start..)?
+            .windows(terminator.len())
+            .position(|window| window == terminator)
+            .map(|x| start + x + terminator.len())
+    }
+}
+
+/// Appends the write terminator, trimming the message first if the options ask for it.
+pub(crate) fn encode_message(message: &str, options: &ConnectionOptions) -> Vec<u8> {
+    let body: &str = match options.get_trim() {
+        true => message.trim(),
+        false => message,
+    };
+    format!("{}{}", body, options.get_write_terminator()).into_bytes()
+}
+
+/// Joins `header` and an encoded block into one message.
+pub(crate) fn encode_block_message(
+    header: &str,
+    block: &[u8],
+    options: &ConnectionOptions,
+) -> Vec<u8> {
+    let header: &str = match options.get_trim() {
+        true => header.trim_start(),
+        false => header,
+    };
+    let separator: &str = match header.ends_with([' ', ',']) || header.is_empty() {
+        true => "",
+        false => " ",
+    };
+
+    let mut message: Vec<u8> = format!("{}{}", header, separator).into_bytes();
+    message.extend_from_slice(block);
+    message.extend_from_slice(options.get_write_terminator().as_bytes());
+    message
+}
+
+/// Turns a response taken by `ReadBuffer::take_response` into text.
+pub(crate) fn decode_response(
+    raw_response: Vec<u8>,
+    options: &ConnectionOptions,
+) -> Result<String, ScpiError> {
+    match String::from_utf8(raw_response) {
+        Ok(x) => {
+            let response: &str = x.strip_suffix(options.get_read_terminator()).unwrap_or(&x);
+            match options.get_trim() {
+                true => Ok(response.trim().to_string()),
+                false => Ok(response.to_string()),
+            }
+        }
+        Err(_) => Err(ScpiError::Parse(
+            "Instrument response was not valid UTF-8".to_string(),
+        )),
+    }
+}
@@ -0,0 +1,444 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! HiSLIP (IVI-6.1 High-Speed LAN Instrument Protocol) client.
+
+use std::{
+    io::{Error, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use crate::error::ScpiError;
+use crate::transport::Transport;
+
+pub const HISLIP_PORT: u16 = 4880;
+pub const DEFAULT_SUB_ADDRESS: &str = "hislip0";
+
+const PROLOGUE: &[u8; 2] = b"HS";
+const HEADER_SIZE: usize = 16;
+const PROTOCOL_VERSION: u16 = 0x0100;
+const VENDOR_ID: &[u8; 2] = b"ZZ";
+const FIRST_MESSAGE_ID: u32 = 0xFFFF_FF00;
+const CLIENT_MAX_MESSAGE_SIZE: u64 = 1 << 20;
+const MAX_PAYLOAD_SIZE: u64 = 1 << 30;
+
+pub(crate) const INITIALIZE: u8 = 0;
+pub(crate) const INITIALIZE_RESPONSE: u8 = 1;
+pub(crate) const FATAL_ERROR: u8 = 2;
+pub(crate) const ERROR: u8 = 3;
+pub(crate) const DATA: u8 = 6;
+pub(crate) const DATA_END: u8 = 7;
+pub(crate) const DEVICE_CLEAR_COMPLETE: u8 = 8;
+pub(crate) const DEVICE_CLEAR_ACKNOWLEDGE: u8 = 9;
+pub(crate) const ASYNC_REMOTE_LOCAL_CONTROL: u8 = 10;
+pub(crate) const ASYNC_REMOTE_LOCAL_RESPONSE: u8 = 11;
+pub(crate) const INTERRUPTED: u8 = 13;
+pub(crate) const ASYNC_INTERRUPTED: u8 = 14;
+pub(crate) const ASYNC_MAXIMUM_MESSAGE_SIZE: u8 = 15;
+pub(crate) const ASYNC_MAXIMUM_MESSAGE_SIZE_RESPONSE: u8 = 16;
+pub(crate) const ASYNC_INITIALIZE: u8 = 17;
+pub(crate) const ASYNC_INITIALIZE_RESPONSE: u8 = 18;
+pub(crate) const ASYNC_DEVICE_CLEAR: u8 = 19;
+pub(crate) const ASYNC_SERVICE_REQUEST: u8 = 20;
+pub(crate) const ASYNC_DEVICE_CLEAR_ACKNOWLEDGE: u8 = 23;
+
+const RMT_DELIVERED: u8 = 0x01;
+const OVERLAPPED: u8 = 0x01;
+// Sockets reject a zero read timeout.
+const MIN_WAIT: Duration = Duration::from_millis(1);
+
+/// Request codes for `HislipClient::remote_local`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemoteLocalControl {
+    DisableRemote = 0,
+    EnableRemote = 1,
+    DisableRemoteGoToLocal = 2,
+    EnableRemoteGoToRemote = 3,
+    EnableRemoteLockOutLocal = 4,
+    EnableRemoteGoToRemoteLockOutLocal = 5,
+    GoToLocal = 6,
+}
+
+/* ********************************************************************************************** */
+/*                                         Message Framing                                        */
+/* ********************************************************************************************** */
+
+pub(crate) struct HislipMessage {
+    pub(crate) message_type: u8,
+    pub(crate) control_code: u8,
+    pub(crate) parameter: u32,
+    pub(crate) payload: Vec<u8>,
+}
+
+impl HislipMessage {
+    pub(crate) fn new(message_type: u8, control_code: u8, parameter: u32, payload: &[u8]) -> Self {
+        Self {
+            message_type,
+            control_code,
+            parameter,
+            payload: payload.to_vec(),
+        }
+    }
+
+    pub(crate) fn write_to<W: Write>(&self, stream: &mut W) -> Result<(), ScpiError> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_SIZE + self.payload.len());
+        bytes.extend_from_slice(PROLOGUE);
+        bytes.push(self.message_type);
+        bytes.push(self.control_code);
+        bytes.extend_from_slice(&self.parameter.to_be_bytes());
+        bytes.extend_from_slice(&(self.payload.len() as u64).to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        stream.write_all(&bytes)?;
+        Ok(())
+    }
+
+    pub(crate) fn read_from<R: Read>(stream: &mut R) -> Result<Self, ScpiError> {
+        let mut header: [u8; HEADER_SIZE] = [0; HEADER_SIZE];
+        stream.read_exact(&mut header)?;
+
+        if &header[..2] != PROLOGUE {
+            return Err(ScpiError::Parse(
+                "HiSLIP message did not start with \"HS\"".to_string(),
+            ));
+        }
+
+        let parameter: u32 = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let mut length: [u8; 8] = [0; 8];
+        length.copy_from_slice(&header[8..]);
+        let length: u64 = u64::from_be_bytes(length);
+        if length > MAX_PAYLOAD_SIZE {
+            return Err(ScpiError::Parse(format!(
+                "HiSLIP payload of {} bytes is too large",
+                length
+            )));
+        }
+
+        let mut payload: Vec<u8> = vec![0; length as usize];
+        stream.read_exact(&mut payload)?;
+
+        Ok(Self {
+            message_type: header[2],
+            control_code: header[3],
+            parameter,
+            payload,
+        })
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                          HiSLIP Client                                         */
+/* ********************************************************************************************** */
+
+/// A HiSLIP session: the synchronous channel carries SCPI traffic, the asynchronous channel carries
+/// device clear, remote/local control and service requests.
+pub struct HislipClient {
+    sync_channel: TcpStream,
+    async_channel: TcpStream,
+    session_id: u16,
+    server_protocol_version: u16,
+    max_message_size: u64,
+    message_id: u32,
+    rmt_delivered: bool,
+    overlapped: bool,
+    service_requested: bool,
+    pending: Vec<u8>,
+}
+
+impl HislipClient {
+    pub fn connect(
+        address: SocketAddr,
+        sub_address: &str,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Self, ScpiError> {
+        let mut sync_channel: TcpStream = open_channel(address, connect_timeout)?;
+        let parameter: u32 =
+            ((PROTOCOL_VERSION as u32) << 16) | u16::from_be_bytes(*VENDOR_ID) as u32;
+        HislipMessage::new(INITIALIZE, 0, parameter, sub_address.as_bytes())
+            .write_to(&mut sync_channel)?;
+        let response: HislipMessage = expect_message(&mut sync_channel, INITIALIZE_RESPONSE)?;
+        let server_protocol_version: u16 = (response.parameter >> 16) as u16;
+        let session_id: u16 = response.parameter as u16;
+        let overlapped: bool = response.control_code & OVERLAPPED != 0;
+
+        let mut async_channel: TcpStream = open_channel(address, connect_timeout)?;
+        HislipMessage::new(ASYNC_INITIALIZE, 0, session_id as u32, &[])
+            .write_to(&mut async_channel)?;
+        expect_message(&mut async_channel, ASYNC_INITIALIZE_RESPONSE)?;
+
+        HislipMessage::new(
+            ASYNC_MAXIMUM_MESSAGE_SIZE,
+            0,
+            0,
+            &CLIENT_MAX_MESSAGE_SIZE.to_be_bytes(),
+        )
+        .write_to(&mut async_channel)?;
+        let response: HislipMessage =
+            expect_message(&mut async_channel, ASYNC_MAXIMUM_MESSAGE_SIZE_RESPONSE)?;
+        let max_message_size: u64 = match <[u8; 8]>::try_from(response.payload.as_slice()) {
+            Ok(x) => u64::from_be_bytes(x),
+            Err(_) => {
+                return Err(ScpiError::Parse(
+                    "HiSLIP maximum message size response must be 8 bytes".to_string(),
+                ))
+            }
+        };
+
+        Ok(Self {
+            sync_channel,
+            async_channel,
+            session_id,
+            server_protocol_version,
+            max_message_size,
+            message_id: FIRST_MESSAGE_ID,
+            rmt_delivered: false,
+            overlapped,
+            service_requested: false,
+            pending: Vec::new(),
+        })
+    }
+
+    /// Sends `data` as one HiSLIP message, splitting it into Data frames ending with DataEnd.
+    pub fn write_message(&mut self, data: &[u8]) -> Result<usize, ScpiError> {
+        let chunk_size: usize = self
+            .max_message_size
+            .saturating_sub(HEADER_SIZE as u64)
+            .clamp(1, MAX_PAYLOAD_SIZE) as usize;
+        let chunks: Vec<&[u8]> = match data.is_empty() {
+            true => vec![data],
+            false => data.chunks(chunk_size).collect(),
+        };
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            let message_type: u8 = match index + 1 == chunks.len() {
+                true => DATA_END,
+                false => DATA,
+            };
+            let control_code: u8 = match std::mem::take(&mut self.rmt_delivered) {
+                true => RMT_DELIVERED,
+                false => 0,
+            };
+            HislipMessage::new(message_type, control_code, self.message_id, chunk)
+                .write_to(&mut self.sync_channel)?;
+        }
+
+        self.message_id = self.message_id.wrapping_add(2);
+        Ok(data.len())
+    }
+
+    /// Returns the payload of the next Data or DataEnd message and whether it was a DataEnd.
+    pub fn read_message(&mut self) -> Result<(Vec<u8>, bool), ScpiError> {
+        loop {
+            let message: HislipMessage = HislipMessage::read_from(&mut self.sync_channel)?;
+            match message.message_type {
+                DATA => return Ok((message.payload, false)),
+                DATA_END => {
+                    self.rmt_delivered = true;
+                    return Ok((message.payload, true));
+                }
+                INTERRUPTED => continue,
+                _ => return Err(unexpected_message(&message)),
+            }
+        }
+    }
+
+    /// Runs the HiSLIP device clear handshake, discarding anything still in flight.
+    pub fn device_clear(&mut self) -> Result<(), ScpiError> {
+        let feature_request: u8 = match self.overlapped {
+            true => OVERLAPPED,
+            false => 0,
+        };
+        self.device_clear_with_features(feature_request)
+    }
+
+    /// Asks the server to switch between overlapped and synchronized mode, which HiSLIP only
+    /// allows as part of a device clear. Returns the mode the server actually selected.
+    pub fn set_overlapped(&mut self, overlapped: bool) -> Result<bool, ScpiError> {
+        let feature_request: u8 = match overlapped {
+            true => OVERLAPPED,
+            false => 0,
+        };
+        self.device_clear_with_features(feature_request)?;
+        Ok(self.overlapped)
+    }
+
+    pub fn remote_local(&mut self, control: RemoteLocalControl) -> Result<(), ScpiError> {
+        HislipMessage::new(
+            ASYNC_REMOTE_LOCAL_CONTROL,
+            control as u8,
+            self.message_id.wrapping_sub(2),
+            &[],
+        )
+        .write_to(&mut self.async_channel)?;
+        self.expect_async(ASYNC_REMOTE_LOCAL_RESPONSE)?;
+        Ok(())
+    }
+
+    /// Waits for an AsyncServiceRequest, returning false if none arrived within `timeout`. A request
+    /// that arrived while waiting for another asynchronous response counts as well.
+    pub fn wait_for_service_request(&mut self, timeout: Duration) -> Result<bool, ScpiError> {
+        if std::mem::take(&mut self.service_requested) {
+            return Ok(true);
+        }
+
+        let previous_timeout: Option<Duration> = self.async_channel.read_timeout()?;
+        self.async_channel
+            .set_read_timeout(Some(timeout.max(MIN_WAIT)))?;
+        let result: Result<bool, ScpiError> = loop {
+            match HislipMessage::read_from(&mut self.async_channel) {
+                Ok(x) if x.message_type == ASYNC_SERVICE_REQUEST => break Ok(true),
+                Ok(x) if x.message_type == ASYNC_INTERRUPTED => continue,
+                Ok(x) => break Err(unexpected_message(&x)),
+                Err(ScpiError::Timeout) => break Ok(false),
+                Err(x) => break Err(x),
+            }
+        };
+        self.async_channel.set_read_timeout(previous_timeout)?;
+
+        result
+    }
+
+    fn device_clear_with_features(&mut self, feature_request: u8) -> Result<(), ScpiError> {
+        HislipMessage::new(ASYNC_DEVICE_CLEAR, 0, 0, &[]).write_to(&mut self.async_channel)?;
+        self.expect_async(ASYNC_DEVICE_CLEAR_ACKNOWLEDGE)?;
+
+        HislipMessage::new(DEVICE_CLEAR_COMPLETE, feature_request, 0, &[])
+            .write_to(&mut self.sync_channel)?;
+        let acknowledge: HislipMessage = loop {
+            let message: HislipMessage = HislipMessage::read_from(&mut self.sync_channel)?;
+            match message.message_type {
+                DEVICE_CLEAR_ACKNOWLEDGE => break message,
+                DATA | DATA_END | INTERRUPTED => continue,
+                _ => return Err(unexpected_message(&message)),
+            }
+        };
+
+        self.overlapped = acknowledge.control_code & OVERLAPPED != 0;
+        self.message_id = FIRST_MESSAGE_ID;
+        self.rmt_delivered = false;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn expect_async(&mut self, message_type: u8) -> Result<HislipMessage, ScpiError> {
+        loop {
+            let message: HislipMessage = expect_message(&mut self.async_channel, message_type)?;
+            match message.message_type {
+                ASYNC_SERVICE_REQUEST => self.service_requested = true,
+                ASYNC_INTERRUPTED => continue,
+                _ => return Ok(message),
+            }
+        }
+    }
+
+    /* ****************************************************************************************** */
+    /*                                   Boilerplate Getters                                      */
+    /* ****************************************************************************************** */
+
+    pub fn get_session_id(&self) -> u16 {
+        self.session_id
+    }
+
+    pub fn get_server_protocol_version(&self) -> u16 {
+        self.server_protocol_version
+    }
+
+    pub fn get_max_message_size(&self) -> u64 {
+        self.max_message_size
+    }
+
+    pub fn get_overlapped(&self) -> bool {
+        self.overlapped
+    }
+}
+
+impl Transport for HislipClient {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        self.write_message(bytes)
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<usize, ScpiError> {
+        if self.pending.is_empty() {
+            let (payload, _): (Vec<u8>, bool) = self.read_message()?;
+            self.pending = payload;
+        }
+
+        let received: usize = self.pending.len().min(buffer.len());
+        buffer[..received].copy_from_slice(&self.pending[..received]);
+        self.pending.drain(..received);
+        Ok(received)
+    }
+
+    fn set_timeout(
+        &mut self,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError> {
+        for channel in [&self.sync_channel, &self.async_channel] {
+            channel.set_read_timeout(read_timeout)?;
+            channel.set_write_timeout(write_timeout)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ScpiError> {
+        self.sync_channel.flush()?;
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        self.device_clear()
+    }
+
+    fn wait_for_service_request(&mut self, timeout: Duration) -> Result<bool, ScpiError> {
+        HislipClient::wait_for_service_request(self, timeout)
+    }
+}
+
+fn open_channel(
+    address: SocketAddr,
+    connect_timeout: Option<Duration>,
+) -> Result<TcpStream, ScpiError> {
+    let stream: TcpStream = match connect_timeout {
+        Some(timeout) => TcpStream::connect_timeout(&address, timeout)?,
+        None => TcpStream::connect(address)?,
+    };
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+/// Reads one message, turning Error/FatalError into a `ScpiError`. Messages of other types than
+/// `message_type` are only let through if they are asynchronous notifications.
+fn expect_message(stream: &mut TcpStream, message_type: u8) -> Result<HislipMessage, ScpiError> {
+    let message: HislipMessage = HislipMessage::read_from(stream)?;
+    match message.message_type {
+        x if x == message_type => Ok(message),
+        ASYNC_SERVICE_REQUEST | ASYNC_INTERRUPTED => Ok(message),
+        _ => Err(unexpected_message(&message)),
+    }
+}
+
+fn unexpected_message(message: &HislipMessage) -> ScpiError {
+    let description: String = match message.message_type {
+        FATAL_ERROR | ERROR => format!(
+            "HiSLIP error {}: {}",
+            message.control_code,
+            String::from_utf8_lossy(&message.payload)
+        ),
+        x => format!("Unexpected HiSLIP message type {}", x),
+    };
+    ScpiError::Connection(Error::other(description))
+}
@@ -14,16 +14,47 @@
    limitations under the License.
 */
 
+#[cfg(feature = "tokio")]
+pub mod async_messenger;
+pub mod block;
+pub mod command;
+pub mod command_tree;
+pub mod common;
+pub mod connection_options;
 pub mod duty_cycle;
+pub mod error;
+pub mod error_queue;
+mod framing;
+pub mod hislip;
 pub mod messenger;
+pub mod mock;
 pub mod networking;
+mod onc_rpc;
+pub mod resource;
+pub mod response;
+pub mod run_control;
+pub mod sequence;
+pub mod simulator;
+pub mod status;
+pub mod tcp_multicast;
+pub mod timing;
+pub mod transport;
 mod unit_tests;
+pub mod vxi11;
 
-use std::{io::Error, net::IpAddr};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
 
+use connection_options::ConnectionOptions;
 use duty_cycle::DutyCycleMessage;
+use error::ScpiError;
 use messenger::Messenger;
 use networking::NetworkMode;
+use run_control::{RunLimits, RunStatistics, StopHandle};
+use sequence::Sequence;
+use tcp_multicast::{into_group_result, InstrumentResult, TcpMulticastGroup};
 
 pub fn send_scpi_message(
     message: &str,
@@ -31,22 +62,111 @@ pub fn send_scpi_message(
     remote_client: &IpAddr,
     remote_port: u16,
     local_port: u16,
-) -> Result<usize, Error> {
-    let mut messenger: Messenger = Messenger::new(local_port, remote_port, remote_client, mode)?;
+) -> Result<usize, ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
     messenger.send_message(message)
 }
 
+pub fn query_scpi_message(
+    message: &str,
+    mode: &NetworkMode,
+    remote_client: &IpAddr,
+    remote_port: u16,
+    local_port: u16,
+) -> Result<String, ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
+    messenger.query(message)
+}
+
+/// Sends `message` to a VISA-style resource string, e.g. `TCPIP0::192.168.1.70::5025::SOCKET`.
+pub fn send_scpi_resource_message(message: &str, resource: &str) -> Result<usize, ScpiError> {
+    let mut messenger: Messenger = Messenger::open(resource, &ConnectionOptions::default())?;
+    messenger.send_message(message)
+}
+
+pub fn query_scpi_resource_message(message: &str, resource: &str) -> Result<String, ScpiError> {
+    let mut messenger: Messenger = Messenger::open(resource, &ConnectionOptions::default())?;
+    messenger.query(message)
+}
+
+pub fn query_scpi_message_with_timeout(
+    message: &str,
+    mode: &NetworkMode,
+    remote_client: &IpAddr,
+    remote_port: u16,
+    local_port: u16,
+    timeout: Duration,
+) -> Result<String, ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
+    messenger.query_with_timeout(message, timeout)
+}
+
+/// With `NetworkMode::TcpMulticast`, every reachable member gets the whole list even when others
+/// fail, and the error is `ScpiError::Group` with an entry for each member that failed.
 pub fn send_list_of_scpi_messages(
     messages: &[&str],
     mode: &NetworkMode,
     remote_client: &IpAddr,
     remote_port: u16,
     local_port: u16,
-) -> Result<(), Error> {
-    let mut messenger: Messenger = Messenger::new(local_port, remote_port, remote_client, mode)?;
+) -> Result<(), ScpiError> {
+    if let NetworkMode::TcpMulticast { members, parallel } = mode {
+        let addresses: Vec<SocketAddr> = members
+            .iter()
+            .map(|x| SocketAddr::new(*x, remote_port))
+            .collect();
+        let (mut group, failures) = TcpMulticastGroup::connect_reachable(
+            &addresses,
+            &ConnectionOptions::default(),
+            *parallel,
+        )?;
+        return into_group_result(failures, group.send_list_of_messages(messages));
+    }
+
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
     messenger.send_list_of_messages(messages)
 }
 
+/// Sends every message to every instrument over TCP, reporting the outcome per instrument.
+pub fn send_list_of_scpi_messages_to_group(
+    messages: &[&str],
+    remote_clients: &[IpAddr],
+    remote_port: u16,
+    parallel: bool,
+) -> Result<Vec<InstrumentResult<()>>, ScpiError> {
+    let addresses: Vec<SocketAddr> = remote_clients
+        .iter()
+        .map(|x| SocketAddr::new(*x, remote_port))
+        .collect();
+    let mut group: TcpMulticastGroup =
+        TcpMulticastGroup::connect(&addresses, &ConnectionOptions::default(), parallel)?;
+    Ok(group.send_list_of_messages(messages))
+}
+
 pub fn send_repeated_scpi_message(
     message: &str,
     mode: &NetworkMode,
@@ -54,29 +174,15 @@ pub fn send_repeated_scpi_message(
     remote_port: u16,
     local_port: u16,
     repititions: Option<usize>,
-) -> Result<usize, Error> {
-    let mut messenger: Messenger = Messenger::new(local_port, remote_port, remote_client, mode)?;
-
-    match repititions {
-        Some(number) => {
-            if number == 0 {
-                return Ok(0);
-            }
-
-            let mut result: usize = 0;
-            for i in 0..number {
-                if i == 0 {
-                    result = messenger.send_message(message)?;
-                } else {
-                    messenger.send_message(message)?;
-                }
-            }
-            Ok(result)
-        }
-        None => loop {
-            messenger.send_message(message)?;
-        },
-    }
+) -> Result<usize, ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
+    messenger.send_repeated_message(message, repititions)
 }
 
 pub fn send_duty_cycled_message(
@@ -85,7 +191,73 @@ pub fn send_duty_cycled_message(
     remote_client: &IpAddr,
     remote_port: u16,
     local_port: u16,
-) -> Result<(), Error> {
-    let mut messenger: Messenger = Messenger::new(local_port, remote_port, remote_client, mode)?;
+) -> Result<(), ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
     messenger.send_duty_cycled_message(duty_cycle_message)
 }
+
+/// Like `send_repeated_scpi_message`, but stops on `stop` or `limits` and returns statistics.
+pub fn run_repeated_scpi_message(
+    message: &str,
+    mode: &NetworkMode,
+    remote_client: &IpAddr,
+    remote_port: u16,
+    local_port: u16,
+    limits: &RunLimits,
+    stop: &StopHandle,
+) -> Result<RunStatistics, ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
+    messenger.run_repeated_message(message, limits, stop)
+}
+
+/// Like `send_duty_cycled_message`, but stops on `stop` or `limits` and returns statistics.
+pub fn run_duty_cycled_message(
+    duty_cycle_message: &DutyCycleMessage,
+    mode: &NetworkMode,
+    remote_client: &IpAddr,
+    remote_port: u16,
+    local_port: u16,
+    limits: &RunLimits,
+    stop: &StopHandle,
+) -> Result<RunStatistics, ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
+    messenger.run_duty_cycled_message(duty_cycle_message, limits, stop)
+}
+
+/// Runs `sequence` until `stop` or `limits` end it; see `Messenger::run_sequence`.
+pub fn run_scpi_sequence(
+    sequence: &Sequence,
+    mode: &NetworkMode,
+    remote_client: &IpAddr,
+    remote_port: u16,
+    local_port: u16,
+    limits: &RunLimits,
+    stop: &StopHandle,
+) -> Result<RunStatistics, ScpiError> {
+    let mut messenger: Messenger = Messenger::new(
+        local_port,
+        remote_port,
+        remote_client,
+        mode,
+        &ConnectionOptions::default(),
+    )?;
+    messenger.run_sequence(sequence, limits, stop)
+}
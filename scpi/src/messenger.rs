@@ -14,18 +14,57 @@
    limitations under the License.
 */
 
+use rs232::SerialConnection;
 use std::{
-    io::{Error, ErrorKind, Write},
-    net::{IpAddr, SocketAddr, TcpStream, UdpSocket},
-    str::FromStr,
+    borrow::Cow,
+    io::Error,
+    net::{IpAddr, SocketAddr},
+    sync::mpsc::{self, Receiver, Sender},
+    thread::{Builder, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use crate::duty_cycle::DutyCycleMessage;
-use crate::networking::{NetworkMode, NetworkSender};
+use crate::block::{
+    decode_block_elements, encode_block_elements, encode_definite_block, encode_indefinite_block,
+    BlockElement, ByteOrder,
+};
+use crate::command::Command;
+use crate::command_tree::{CommandTree, MnemonicForm};
+use crate::common::{
+    parse_register, CompletionMethod, CompletionOptions, Identification, StandardEventStatus,
+};
+use crate::connection_options::ConnectionOptions;
+use crate::duty_cycle::{DutyCycleHandle, DutyCycleMessage};
+use crate::error::ScpiError;
+use crate::error_queue::{
+    parse_error_queue_entry, ErrorCheckMode, InstrumentError, ERROR_QUEUE_QUERY,
+};
+use crate::framing::{
+    decode_response, encode_block_message, encode_message, ReadBuffer, READ_CHUNK_SIZE,
+};
+use crate::hislip::{HislipClient, DEFAULT_SUB_ADDRESS};
+use crate::networking::{NetworkMode, SerialConfig, TcpTransport, UdpTransport};
+use crate::resource::Resource;
+use crate::response::{parse_boolean, parse_integer, FromResponse};
+use crate::run_control::{RunLimits, RunStatistics, RunTracker, StopHandle, StopReason};
+use crate::sequence::{Sequence, SequenceStep};
+use crate::status::{parse_register16, StatusByte, StatusRegister, StatusRegisterGroup};
+use crate::tcp_multicast::TcpMulticastGroup;
+use crate::timing::TimingStrategy;
+use crate::transport::Transport;
+use crate::vxi11::{Vxi11Client, PORTMAPPER_PORT};
+
+const MAX_ERROR_QUEUE_READS: usize = 100;
 
 pub struct Messenger {
-    destination_address: SocketAddr,
-    sending_socket: NetworkSender,
+    transport: Box<dyn Transport>,
+    options: ConnectionOptions,
+    error_check_mode: ErrorCheckMode,
+    timing_strategy: TimingStrategy,
+    read_buffer: ReadBuffer,
+    duty_cycle_runner: Option<DutyCycleRunner>,
+    command_tree: Option<CommandTree>,
+    mnemonic_form: MnemonicForm,
 }
 
 impl Messenger {
@@ -34,98 +73,965 @@ impl Messenger {
         remote_port: u16,
         remote_client: &IpAddr,
         mode: &NetworkMode,
-    ) -> Result<Self, Error> {
-        let local_host: IpAddr = match IpAddr::from_str("0.0.0.0") {
-            Ok(x) => x,
-            Err(_) => {
-                let msg: &str = "Creating localhost object failed...";
-                eprint!("{}", msg);
-                return Err(Error::new(ErrorKind::Interrupted, msg));
+        options: &ConnectionOptions,
+    ) -> Result<Self, ScpiError> {
+        let remote_address = SocketAddr::new(*remote_client, remote_port);
+
+        let transport: Box<dyn Transport> = match mode {
+            NetworkMode::Udp => Box::new(UdpTransport::bind(local_port, remote_address)?),
+            NetworkMode::Tcp => Box::new(TcpTransport::connect(
+                remote_address,
+                options.get_connect_timeout(),
+            )?),
+            NetworkMode::UdpMulticast => Box::new(UdpTransport::join_multicast(
+                local_port,
+                remote_address,
+                options.get_multicast(),
+            )?),
+            NetworkMode::TcpMulticast { members, parallel } => {
+                let addresses: Vec<SocketAddr> = members
+                    .iter()
+                    .map(|x| SocketAddr::new(*x, remote_port))
+                    .collect();
+                Box::new(TcpMulticastGroup::connect(&addresses, options, *parallel)?)
             }
+            NetworkMode::Hislip => Box::new(HislipClient::connect(
+                remote_address,
+                DEFAULT_SUB_ADDRESS,
+                options.get_connect_timeout(),
+            )?),
+            NetworkMode::Serial { path, config } => Box::new(SerialConnection::open(path, config)?),
         };
-        let local_address = SocketAddr::new(local_host, local_port);
-        let remote_address = SocketAddr::new(*remote_client, remote_port);
 
-        match mode {
-            NetworkMode::Udp => {
-                let local_socket: UdpSocket = UdpSocket::bind(local_address)?;
-                Ok(Self {
-                    destination_address: remote_address,
-                    sending_socket: NetworkSender::Udp(local_socket),
-                })
+        Self::from_transport(transport, options)
+    }
+
+    pub fn new_serial(
+        path: &str,
+        config: &SerialConfig,
+        options: &ConnectionOptions,
+    ) -> Result<Self, ScpiError> {
+        let connection: SerialConnection = SerialConnection::open(path, config)?;
+        Self::from_transport(Box::new(connection), options)
+    }
+
+    /// Links to `device_name` (usually "inst0") over VXI-11, via the portmapper.
+    pub fn new_vxi11(
+        remote_client: &IpAddr,
+        device_name: &str,
+        options: &ConnectionOptions,
+    ) -> Result<Self, ScpiError> {
+        let client: Vxi11Client =
+            Vxi11Client::connect(remote_client, device_name, options.get_connect_timeout())?;
+        Self::from_transport(Box::new(client), options)
+    }
+
+    /// Opens a VISA-style resource string such as `TCPIP0::192.168.1.70::5025::SOCKET`,
+    /// `TCPIP::host::inst0::INSTR`, `TCPIP::host::hislip0::INSTR` or `ASRL/dev/ttyUSB0::INSTR`.
+    /// Every address the host resolves to is tried in turn.
+    pub fn open(resource: &str, options: &ConnectionOptions) -> Result<Self, ScpiError> {
+        let resource: Resource = resource.parse()?;
+        let connect_timeout: Option<Duration> = options.get_connect_timeout();
+
+        let addresses: Vec<SocketAddr> = match &resource {
+            Resource::Serial { path } => {
+                return Self::new_serial(path, &SerialConfig::default(), options)
             }
-            NetworkMode::Tcp => {
-                let local_socket: TcpStream = TcpStream::connect(remote_address)?;
-                Ok(Self {
-                    destination_address: remote_address,
-                    sending_socket: NetworkSender::Tcp(local_socket),
-                })
+            _ => resource.resolve(PORTMAPPER_PORT)?,
+        };
+
+        let mut last_error: Option<ScpiError> = None;
+        for address in addresses {
+            let transport: Result<Box<dyn Transport>, ScpiError> = match &resource {
+                Resource::Socket { .. } => TcpTransport::connect(address, connect_timeout)
+                    .map(|x| Box::new(x) as Box<dyn Transport>),
+                Resource::Vxi11 { device_name, .. } => Vxi11Client::connect_with_portmapper(
+                    &address.ip(),
+                    address.port(),
+                    device_name,
+                    connect_timeout,
+                )
+                .map(|x| Box::new(x) as Box<dyn Transport>),
+                Resource::Hislip { sub_address, .. } => {
+                    HislipClient::connect(address, sub_address, connect_timeout)
+                        .map(|x| Box::new(x) as Box<dyn Transport>)
+                }
+                Resource::Serial { .. } => Err(ScpiError::InvalidArgument(
+                    "Serial resources have no network address".to_string(),
+                )),
+            };
+
+            match transport {
+                Ok(x) => return Self::from_transport(x, options),
+                Err(e) => last_error = Some(e),
             }
-            NetworkMode::UdpMulticast => {
-                let local_socket: UdpSocket = UdpSocket::bind(local_address)?;
-                match [&local_host, remote_client] {
-                    [IpAddr::V4(x), IpAddr::V4(y)] => {
-                        local_socket.join_multicast_v4(y, x)?;
+        }
+
+        Err(last_error.unwrap_or(ScpiError::InvalidArgument(
+            "Resource did not resolve to any address".to_string(),
+        )))
+    }
+
+    /// Wraps any `Transport`, applying the timeouts from `options` to it.
+    pub fn from_transport(
+        mut transport: Box<dyn Transport>,
+        options: &ConnectionOptions,
+    ) -> Result<Self, ScpiError> {
+        transport.set_timeout(options.get_read_timeout(), options.get_write_timeout())?;
+
+        Ok(Self {
+            transport,
+            options: options.clone(),
+            error_check_mode: ErrorCheckMode::Disabled,
+            timing_strategy: TimingStrategy::default(),
+            read_buffer: ReadBuffer::default(),
+            duty_cycle_runner: None,
+            command_tree: None,
+            mnemonic_form: MnemonicForm::AsWritten,
+        })
+    }
+
+    pub fn send_message(&mut self, message: &str) -> Result<usize, ScpiError> {
+        if self.error_check_mode == ErrorCheckMode::AfterEachCommand {
+            self.check_can_read()?;
+        }
+        let message: Cow<str> = self.check_command_tree(message)?;
+        let sent: usize = self.write_message(&message)?;
+        self.check_after_command()?;
+        Ok(sent)
+    }
+
+    pub fn query(&mut self, message: &str) -> Result<String, ScpiError> {
+        self.check_can_read()?;
+        let message: Cow<str> = self.check_command_tree(message)?;
+        self.write_message(&message)?;
+        let response: String = self.read_response()?;
+        self.check_after_command()?;
+        Ok(response)
+    }
+
+    /// Builds `command` and sends it. Nothing is sent if a parameter is invalid.
+    pub fn send_command(&mut self, command: &Command) -> Result<usize, ScpiError> {
+        self.send_message(&command.build()?)
+    }
+
+    pub fn query_command(&mut self, command: &Command) -> Result<String, ScpiError> {
+        self.query(&command.build()?)
+    }
+
+    /// Queries and decodes the response, e.g. `query_as::<f64>("MEAS:VOLT?")`.
+    pub fn query_as<T: FromResponse>(&mut self, message: &str) -> Result<T, ScpiError> {
+        T::from_response(&self.query(message)?)
+    }
+
+    /// Like `query`, but waits at most `timeout` for the response. If it times out, whatever the
+    /// instrument sends before the next exchange is discarded, so a late answer cannot be read as
+    /// the answer to a later query.
+    pub fn query_with_timeout(
+        &mut self,
+        message: &str,
+        timeout: Duration,
+    ) -> Result<String, ScpiError> {
+        if timeout.is_zero() {
+            return Err(ScpiError::InvalidArgument(
+                "Query timeout must be greater than zero".to_string(),
+            ));
+        }
+
+        self.with_read_timeout(timeout, |x| x.query(message))
+    }
+
+    pub(crate) fn get_transport_mut(&mut self) -> &mut dyn Transport {
+        self.transport.as_mut()
+    }
+
+    /// Drops any buffered input and clears the transport.
+    pub fn clear(&mut self) -> Result<(), ScpiError> {
+        self.read_buffer.clear();
+        self.transport.clear()
+    }
+
+    pub fn read_response(&mut self) -> Result<String, ScpiError> {
+        let terminator: String = self.options.get_read_terminator().to_string();
+        let raw_response: Vec<u8> = self.read_until(|x| x.take_response(&terminator))?;
+        decode_response(raw_response, &self.options)
+    }
+
+    /// Sends `message` `repetitions` times, or forever when `None`. Returns the size of one send.
+    pub fn send_repeated_message(
+        &mut self,
+        message: &str,
+        repetitions: Option<usize>,
+    ) -> Result<usize, ScpiError> {
+        let limits: RunLimits = RunLimits::new().with_max_cycles(repetitions);
+        let statistics: RunStatistics =
+            self.run_repeated_message(message, &limits, &StopHandle::new())?;
+
+        Ok(statistics
+            .get_bytes_sent()
+            .checked_div(statistics.get_messages_sent())
+            .unwrap_or(0))
+    }
+
+    /// Sends `message` back to back until `stop` is signalled or a limit in `limits` is reached.
+    pub fn run_repeated_message(
+        &mut self,
+        message: &str,
+        limits: &RunLimits,
+        stop: &StopHandle,
+    ) -> Result<RunStatistics, ScpiError> {
+        let mut run: RunTracker = RunTracker::start(limits, stop, self.timing_strategy);
+        loop {
+            if let Some(reason) = run.stop_reason() {
+                return Ok(run.into_statistics(reason));
+            }
+
+            run.begin_cycle();
+            let sent: usize = self.send_message(message)?;
+            run.record_message(sent);
+            run.finish_cycle();
+        }
+    }
+
+    pub fn send_list_of_messages(&mut self, messages: &[&str]) -> Result<(), ScpiError> {
+        if self.error_check_mode != ErrorCheckMode::Disabled {
+            self.check_can_read()?;
+        }
+        for message in messages {
+            self.send_message(message)?;
+        }
+
+        match self.error_check_mode {
+            ErrorCheckMode::AfterList => self.check_error_queue(),
+            _ => Ok(()),
+        }
+    }
+
+    /// How the duty-cycle and sequence loops wait between messages. Defaults to spinning.
+    pub fn set_timing_strategy(&mut self, strategy: TimingStrategy) {
+        self.timing_strategy = strategy;
+    }
+
+    pub fn get_timing_strategy(&self) -> TimingStrategy {
+        self.timing_strategy
+    }
+
+    /// Alternates the two messages forever. See `run_duty_cycled_message` for a stoppable loop.
+    pub fn send_duty_cycled_message(
+        &mut self,
+        message: &DutyCycleMessage,
+    ) -> Result<(), ScpiError> {
+        self.run_duty_cycled_message(message, &RunLimits::new(), &StopHandle::new())?;
+        Ok(())
+    }
+
+    /// Alternates the two messages until `stop` is signalled or a limit in `limits` is reached. One
+    /// cycle is one first and one second message.
+    pub fn run_duty_cycled_message(
+        &mut self,
+        message: &DutyCycleMessage,
+        limits: &RunLimits,
+        stop: &StopHandle,
+    ) -> Result<RunStatistics, ScpiError> {
+        self.run_sequence(&Sequence::from(message), limits, stop)
+    }
+
+    /// Runs `sequence` over and over until `stop` is signalled or a limit in `limits` is reached.
+    /// One cycle is one full pass through the sequence.
+    pub fn run_sequence(
+        &mut self,
+        sequence: &Sequence,
+        limits: &RunLimits,
+        stop: &StopHandle,
+    ) -> Result<RunStatistics, ScpiError> {
+        if sequence.message_count() == 0 {
+            return Err(ScpiError::InvalidArgument(
+                "sequence does not send any messages".to_string(),
+            ));
+        }
+
+        let mut run: RunTracker = RunTracker::start(limits, stop, self.timing_strategy);
+        loop {
+            if let Some(reason) = run.stop_reason() {
+                return Ok(run.into_statistics(reason));
+            }
+
+            run.begin_cycle();
+            if let Some(reason) = self.run_steps(sequence.get_steps(), &mut run)? {
+                return Ok(run.into_statistics(reason));
+            }
+            run.finish_cycle();
+        }
+    }
+
+    fn run_steps(
+        &mut self,
+        steps: &[SequenceStep],
+        run: &mut RunTracker,
+    ) -> Result<Option<StopReason>, ScpiError> {
+        for step in steps {
+            match step {
+                SequenceStep::Message { message, hold } => {
+                    let start: Instant = Instant::now();
+                    let sent: usize = self.send_message(message)?;
+                    run.record_message(sent);
+                    if let Some(reason) = run.hold(start, *hold) {
+                        return Ok(Some(reason));
                     }
-                    [_, _] => {
-                        const MESSAGE: &str = "Ipv6 Addresses not yet supported";
-                        eprint!("Multicast Join Failed: {}", MESSAGE);
-                        return Err(Error::new(ErrorKind::Interrupted, MESSAGE));
+                }
+                SequenceStep::Loop {
+                    sequence,
+                    repetitions,
+                } => {
+                    for _ in 0..*repetitions {
+                        if let Some(reason) = self.run_steps(sequence.get_steps(), run)? {
+                            return Ok(Some(reason));
+                        }
                     }
                 }
+            }
+        }
 
-                Ok(Self {
-                    destination_address: remote_address,
-                    sending_socket: NetworkSender::Udp(local_socket),
-                })
+        Ok(None)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                     Background Duty Cycle                                      */
+/* ********************************************************************************************** */
+
+/// A duty cycle running on its own thread with the connection it borrowed from its `Messenger`.
+/// Dropping it stops the thread.
+struct DutyCycleRunner {
+    handle: DutyCycleHandle,
+    thread: Option<DutyCycleThread>,
+}
+
+/// Hands the connection back once the run ends, or `None` if the thread never received it.
+type DutyCycleThread = JoinHandle<Option<(Messenger, Result<RunStatistics, ScpiError>)>>;
+
+impl Drop for DutyCycleRunner {
+    fn drop(&mut self) {
+        self.handle.stop();
+        if let Some(x) = self.thread.take() {
+            let _ = x.join();
+        }
+    }
+}
+
+/// Stands in for the real transport while a background duty cycle has it.
+struct BusyTransport;
+
+const BUSY_MESSAGE: &str = "a background duty cycle is using this connection; stop it first";
+
+impl Transport for BusyTransport {
+    fn write_bytes(&mut self, _bytes: &[u8]) -> Result<usize, ScpiError> {
+        Err(ScpiError::Unsupported(BUSY_MESSAGE))
+    }
+
+    fn read_bytes(&mut self, _buffer: &mut [u8]) -> Result<usize, ScpiError> {
+        Err(ScpiError::Unsupported(BUSY_MESSAGE))
+    }
+
+    fn set_timeout(
+        &mut self,
+        _read_timeout: Option<Duration>,
+        _write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError> {
+        Err(ScpiError::Unsupported(BUSY_MESSAGE))
+    }
+
+    fn flush(&mut self) -> Result<(), ScpiError> {
+        Err(ScpiError::Unsupported(BUSY_MESSAGE))
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        Err(ScpiError::Unsupported(BUSY_MESSAGE))
+    }
+}
+
+impl Messenger {
+    /// Starts alternating the two messages on a background thread and returns right away. The
+    /// thread takes over the connection, so every other call on this `Messenger` fails with
+    /// `ScpiError::Unsupported` until `stop_duty_cycle` hands it back.
+    pub fn start_duty_cycle(
+        &mut self,
+        message: &DutyCycleMessage,
+        limits: &RunLimits,
+    ) -> Result<DutyCycleHandle, ScpiError> {
+        self.start_duty_cycle_on(Builder::new(), message, limits)
+    }
+
+    /// `start_duty_cycle` on a thread from `builder`. The thread is given the connection only once
+    /// it is running, so a failed spawn leaves this `Messenger` as it was.
+    pub(crate) fn start_duty_cycle_on(
+        &mut self,
+        builder: Builder,
+        message: &DutyCycleMessage,
+        limits: &RunLimits,
+    ) -> Result<DutyCycleHandle, ScpiError> {
+        if self.duty_cycle_runner.is_some() {
+            return Err(ScpiError::InvalidArgument(
+                "A background duty cycle is already running".to_string(),
+            ));
+        }
+
+        let handle: DutyCycleHandle = DutyCycleHandle::new(message);
+        let worker_handle: DutyCycleHandle = handle.clone();
+        let limits: RunLimits = *limits;
+        let (sender, receiver): (Sender<Messenger>, Receiver<Messenger>) = mpsc::channel();
+        let thread: DutyCycleThread = builder
+            .name("scpi-duty-cycle".to_string())
+            .spawn(move || {
+                let mut worker: Messenger = receiver.recv().ok()?;
+                let result: Result<RunStatistics, ScpiError> =
+                    worker.run_live_duty_cycle(&worker_handle, &limits);
+                worker_handle.mark_finished();
+                Some((worker, result))
+            })
+            .map_err(ScpiError::Connection)?;
+
+        let worker: Messenger = Messenger {
+            transport: std::mem::replace(&mut self.transport, Box::new(BusyTransport)),
+            options: self.options.clone(),
+            error_check_mode: self.error_check_mode,
+            timing_strategy: self.timing_strategy,
+            read_buffer: std::mem::take(&mut self.read_buffer),
+            duty_cycle_runner: None,
+            command_tree: self.command_tree.clone(),
+            mnemonic_form: self.mnemonic_form,
+        };
+        if let Err(x) = sender.send(worker) {
+            self.transport = x.0.transport;
+            self.read_buffer = x.0.read_buffer;
+            return Err(ScpiError::Connection(Error::other(
+                "The duty cycle thread exited before it started",
+            )));
+        }
+
+        self.duty_cycle_runner = Some(DutyCycleRunner {
+            handle: handle.clone(),
+            thread: Some(thread),
+        });
+        Ok(handle)
+    }
+
+    /// Stops the background duty cycle, waits for its thread and takes the connection back. Returns
+    /// the run statistics, or the error that ended the run early.
+    pub fn stop_duty_cycle(&mut self) -> Result<RunStatistics, ScpiError> {
+        let mut runner: DutyCycleRunner = self.duty_cycle_runner.take().ok_or_else(|| {
+            ScpiError::InvalidArgument("No background duty cycle is running".to_string())
+        })?;
+        runner.handle.stop();
+
+        let (worker, result): (Messenger, Result<RunStatistics, ScpiError>) =
+            match runner.thread.take().map(JoinHandle::join) {
+                Some(Ok(Some(x))) => x,
+                _ => {
+                    return Err(ScpiError::Connection(Error::other(
+                        "The duty cycle thread panicked and the connection was lost",
+                    )))
+                }
+            };
+
+        self.transport = worker.transport;
+        self.read_buffer = worker.read_buffer;
+        result
+    }
+
+    /// The handle of the running background duty cycle, if there is one.
+    pub fn get_duty_cycle_handle(&self) -> Option<DutyCycleHandle> {
+        self.duty_cycle_runner.as_ref().map(|x| x.handle.clone())
+    }
+
+    fn run_live_duty_cycle(
+        &mut self,
+        handle: &DutyCycleHandle,
+        limits: &RunLimits,
+    ) -> Result<RunStatistics, ScpiError> {
+        let mut run: RunTracker =
+            RunTracker::start(limits, handle.get_stop_handle(), self.timing_strategy);
+        loop {
+            if let Some(reason) = run.stop_reason() {
+                return Ok(run.into_statistics(reason));
+            }
+
+            run.begin_cycle();
+            for first in [true, false] {
+                let (message, microseconds): (String, u64) = handle.current(first);
+                let start: Instant = Instant::now();
+                let sent: usize = self.send_message(&message)?;
+                run.record_message(sent);
+                if let Some(reason) = run.hold(start, Duration::from_micros(microseconds)) {
+                    return Ok(run.into_statistics(reason));
+                }
             }
-            NetworkMode::TcpMulticast => {
-                const MESSAGE: &str = "Tcp Multicast not yet supported";
-                eprint!("Multicast Join Failed: {}", MESSAGE);
-                Err(Error::new(ErrorKind::Interrupted, MESSAGE))
+            run.finish_cycle();
+        }
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                           Block Data                                           */
+/* ********************************************************************************************** */
+
+impl Messenger {
+    /// Sends `header` followed by `data` as an IEEE 488.2 definite-length block.
+    pub fn send_block(&mut self, header: &str, data: &[u8]) -> Result<usize, ScpiError> {
+        let block: Vec<u8> = encode_definite_block(data)?;
+        self.send_raw_block(header, &block)
+    }
+
+    /// Sends `header` followed by `data` as an indefinite-length (`#0`) block.
+    pub fn send_indefinite_block(&mut self, header: &str, data: &[u8]) -> Result<usize, ScpiError> {
+        let block: Vec<u8> = encode_indefinite_block(data);
+        self.send_raw_block(header, &block)
+    }
+
+    pub fn send_binary_values<T: BlockElement>(
+        &mut self,
+        header: &str,
+        values: &[T],
+        order: ByteOrder,
+    ) -> Result<usize, ScpiError> {
+        self.send_block(header, &encode_block_elements(values, order))
+    }
+
+    pub fn query_block(&mut self, message: &str) -> Result<Vec<u8>, ScpiError> {
+        let message: Cow<str> = self.check_command_tree(message)?;
+        self.write_message(&message)?;
+        let data: Vec<u8> = self.read_block()?;
+        self.check_after_command()?;
+        Ok(data)
+    }
+
+    pub fn query_binary_values<T: BlockElement>(
+        &mut self,
+        message: &str,
+        order: ByteOrder,
+    ) -> Result<Vec<T>, ScpiError> {
+        let data: Vec<u8> = self.query_block(message)?;
+        decode_block_elements(&data, order)
+    }
+
+    /// Reads a definite or indefinite block. An indefinite block ends at the first read terminator.
+    pub fn read_block(&mut self) -> Result<Vec<u8>, ScpiError> {
+        let terminator: String = self.options.get_read_terminator().to_string();
+        self.read_until(|x| x.take_block(&terminator))
+    }
+
+    fn send_raw_block(&mut self, header: &str, block: &[u8]) -> Result<usize, ScpiError> {
+        let message: Vec<u8> = encode_block_message(header, block, &self.options);
+        let sent: usize = self.write_bytes(&message)?;
+        self.check_after_command()?;
+        Ok(sent)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                           Error Queue                                          */
+/* ********************************************************************************************** */
+
+impl Messenger {
+    /// Error checking reads `SYST:ERR?`, so on a write-only transport such as
+    /// `NetworkMode::TcpMulticast` every send fails up front unless this is `Disabled`.
+    pub fn set_error_check_mode(&mut self, mode: ErrorCheckMode) {
+        self.error_check_mode = mode;
+    }
+
+    pub fn get_error_check_mode(&self) -> ErrorCheckMode {
+        self.error_check_mode
+    }
+
+    /// Drains `SYST:ERR?` until the instrument reports `0,"No error"`.
+    pub fn read_error_queue(&mut self) -> Result<Vec<InstrumentError>, ScpiError> {
+        self.check_can_read()?;
+        let mut errors: Vec<InstrumentError> = Vec::new();
+        for _ in 0..MAX_ERROR_QUEUE_READS {
+            self.write_message(ERROR_QUEUE_QUERY)?;
+            let entry: InstrumentError = parse_error_queue_entry(&self.read_response()?)?;
+            if entry.is_no_error() {
+                return Ok(errors);
             }
+
+            errors.push(entry);
+        }
+
+        Err(ScpiError::Parse(format!(
+            "Error queue was not empty after {} reads",
+            MAX_ERROR_QUEUE_READS
+        )))
+    }
+
+    /// Drains the error queue, failing with `ScpiError::Instrument` if it held any errors.
+    pub fn check_error_queue(&mut self) -> Result<(), ScpiError> {
+        let errors: Vec<InstrumentError> = self.read_error_queue()?;
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(ScpiError::Instrument(errors)),
         }
     }
 
-    pub fn send_message(&mut self, message: &str) -> Result<usize, Error> {
-        let clean_message: String = format!("{}\r\n", message.trim());
-        let scpi_message: &[u8] = clean_message.as_bytes();
-        match &mut self.sending_socket {
-            NetworkSender::Udp(x) => Ok(x.send_to(scpi_message, self.destination_address)?),
-            NetworkSender::Tcp(y) => {
-                y.write_all(scpi_message)?;
-                Ok(scpi_message.len())
+    fn check_can_read(&self) -> Result<(), ScpiError> {
+        match self.transport.can_read() {
+            true => Ok(()),
+            false => Err(ScpiError::Unsupported(
+                "Responses cannot be read over this transport; use TcpMulticastGroup to query a group",
+            )),
+        }
+    }
+
+    fn check_after_command(&mut self) -> Result<(), ScpiError> {
+        match self.error_check_mode {
+            ErrorCheckMode::AfterEachCommand => self.check_error_queue(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                         Common Commands                                        */
+/* ********************************************************************************************** */
+
+impl Messenger {
+    /// `*RST`
+    pub fn reset(&mut self) -> Result<(), ScpiError> {
+        self.send_common("*RST")
+    }
+
+    /// `*CLS`: clears the event registers and the error queue.
+    pub fn clear_status(&mut self) -> Result<(), ScpiError> {
+        self.send_common("*CLS")
+    }
+
+    /// `*WAI`: the instrument finishes pending operations before executing later commands.
+    pub fn wait_to_continue(&mut self) -> Result<(), ScpiError> {
+        self.send_common("*WAI")
+    }
+
+    /// `*OPC?`: blocks until pending operations are complete.
+    pub fn query_operation_complete(&mut self) -> Result<bool, ScpiError> {
+        let response: String = self.query_common("*OPC?")?;
+        parse_boolean(&response)
+    }
+
+    /// `*TST?`: runs the self-test. Zero means it passed; other codes are instrument specific.
+    pub fn self_test(&mut self) -> Result<i64, ScpiError> {
+        let response: String = self.query_common("*TST?")?;
+        parse_integer(&response)
+    }
+
+    /// `*ESR?`: reads and clears the Standard Event Status Register.
+    pub fn query_event_status(&mut self) -> Result<StandardEventStatus, ScpiError> {
+        let response: String = self.query_common("*ESR?")?;
+        StandardEventStatus::from_response(&response)
+    }
+
+    /// `*STB?`
+    pub fn query_status_byte(&mut self) -> Result<StatusByte, ScpiError> {
+        let response: String = self.query_common("*STB?")?;
+        StatusByte::from_response(&response)
+    }
+
+    /// `*IDN?`
+    pub fn identify(&mut self) -> Result<Identification, ScpiError> {
+        let response: String = self.query_common("*IDN?")?;
+        Identification::parse(&response)
+    }
+
+    /// Sends `message` and blocks until the instrument reports that all pending operations,
+    /// including the ones it started, are complete. Fails with `ScpiError::Timeout` if that takes
+    /// longer than the timeout in `options`, in which case a late `*OPC?` answer is discarded like
+    /// in `query_with_timeout`.
+    pub fn send_and_wait(
+        &mut self,
+        message: &str,
+        options: &CompletionOptions,
+    ) -> Result<(), ScpiError> {
+        match options.get_method() {
+            CompletionMethod::OperationCompleteQuery => {
+                self.send_message(message)?;
+                self.with_read_timeout(options.get_timeout(), |x| {
+                    let response: String = x.query_common("*OPC?")?;
+                    parse_boolean(&response).map(|_| ())
+                })
+            }
+            CompletionMethod::EventStatusPolling => {
+                // Reading the register clears any Operation Complete bit left from earlier.
+                self.query_event_status()?;
+                self.send_message(message)?;
+                self.send_common("*OPC")?;
+                self.poll_until(options, |x| {
+                    Ok(x.query_event_status()?.operation_complete())
+                })
+            }
+            CompletionMethod::StatusBytePolling => {
+                let enable: u8 = parse_register(&self.query_common("*ESE?")?)?;
+                self.query_event_status()?;
+                self.send_common(&format!(
+                    "*ESE {}",
+                    enable | StandardEventStatus::OPERATION_COMPLETE
+                ))?;
+                self.send_message(message)?;
+                self.send_common("*OPC")?;
+
+                // Other enabled events also raise the summary bit, so confirm with *ESR?.
+                let result: Result<(), ScpiError> =
+                    self.poll_until(options, |x| match x.query_status_byte()?.event_summary() {
+                        true => Ok(x.query_event_status()?.operation_complete()),
+                        false => Ok(false),
+                    });
+                self.send_common(&format!("*ESE {}", enable))?;
+                result
             }
         }
     }
 
-    pub fn send_list_of_messages(&mut self, messages: &[&str]) -> Result<(), Error> {
+    /// Like `send_list_of_messages`, but waits for each message to complete before the next.
+    pub fn send_list_of_messages_and_wait(
+        &mut self,
+        messages: &[&str],
+        options: &CompletionOptions,
+    ) -> Result<(), ScpiError> {
         for message in messages {
-            self.send_message(message)?;
+            self.send_and_wait(message, options)?;
         }
 
         Ok(())
     }
 
-    pub fn send_duty_cycled_message(&mut self, message: &DutyCycleMessage) -> Result<(), Error> {
-        let (first_time, second_time): (u64, u64) = message.get_times();
-        let (first_message, second_message): (&str, &str) = message.get_messages();
+    fn poll_until<F: FnMut(&mut Self) -> Result<bool, ScpiError>>(
+        &mut self,
+        options: &CompletionOptions,
+        mut is_done: F,
+    ) -> Result<(), ScpiError> {
+        let start: Instant = Instant::now();
+        self.with_read_timeout(options.get_timeout(), |x| loop {
+            if is_done(x)? {
+                return Ok(());
+            }
 
-        let first_interval = std::time::Duration::from_micros(first_time);
-        let second_interval = std::time::Duration::from_micros(second_time);
+            let remaining: Duration = options.get_timeout().saturating_sub(start.elapsed());
+            if remaining.is_zero() {
+                return Err(ScpiError::Timeout);
+            }
+            std::thread::sleep(options.get_poll_interval().min(remaining));
+        })
+    }
+
+    // Common commands and the STATus subsystem are mandatory on every instrument, so they skip the
+    // command tree.
+    fn send_common(&mut self, command: &str) -> Result<(), ScpiError> {
+        self.write_message(command)?;
+        self.check_after_command()
+    }
+
+    fn query_common(&mut self, command: &str) -> Result<String, ScpiError> {
+        self.write_message(command)?;
+        let response: String = self.read_response()?;
+        self.check_after_command()?;
+        Ok(response)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                        Status Reporting                                        */
+/* ********************************************************************************************** */
+
+impl Messenger {
+    /// `*ESE`: selects which `*ESR?` bits raise the event summary bit of the status byte.
+    pub fn set_event_status_enable(&mut self, mask: u8) -> Result<(), ScpiError> {
+        self.send_common(&format!("*ESE {}", mask))
+    }
+
+    pub fn query_event_status_enable(&mut self) -> Result<u8, ScpiError> {
+        let response: String = self.query_common("*ESE?")?;
+        parse_register(&response)
+    }
+
+    /// `*SRE`: selects which status byte bits request service.
+    pub fn set_service_request_enable(&mut self, mask: u8) -> Result<(), ScpiError> {
+        self.send_common(&format!("*SRE {}", mask))
+    }
+
+    pub fn query_service_request_enable(&mut self) -> Result<u8, ScpiError> {
+        let response: String = self.query_common("*SRE?")?;
+        parse_register(&response)
+    }
+
+    /// `STAT:PRES`: restores the enable and transition filters of every SCPI register group.
+    pub fn preset_status(&mut self) -> Result<(), ScpiError> {
+        self.send_common("STAT:PRES")
+    }
+
+    pub fn query_status_condition(&mut self, group: StatusRegisterGroup) -> Result<u16, ScpiError> {
+        let response: String = self.query_common(&format!("{}:COND?", group.get_node()))?;
+        parse_register16(&response)
+    }
+
+    /// Reads and clears the event register of `group`.
+    pub fn query_status_event(&mut self, group: StatusRegisterGroup) -> Result<u16, ScpiError> {
+        let response: String = self.query_common(&format!("{}:EVEN?", group.get_node()))?;
+        parse_register16(&response)
+    }
+
+    pub fn set_status_enable(
+        &mut self,
+        group: StatusRegisterGroup,
+        mask: u16,
+    ) -> Result<(), ScpiError> {
+        self.send_common(&format!("{}:ENAB {}", group.get_node(), mask))
+    }
+
+    pub fn query_status_enable(&mut self, group: StatusRegisterGroup) -> Result<u16, ScpiError> {
+        let response: String = self.query_common(&format!("{}:ENAB?", group.get_node()))?;
+        parse_register16(&response)
+    }
+
+    /// Selects which condition changes set event bits: `positive` for bits going from 0 to 1,
+    /// `negative` for bits going from 1 to 0.
+    pub fn set_status_transitions(
+        &mut self,
+        group: StatusRegisterGroup,
+        positive: u16,
+        negative: u16,
+    ) -> Result<(), ScpiError> {
+        self.send_common(&format!("{}:PTR {}", group.get_node(), positive))?;
+        self.send_common(&format!("{}:NTR {}", group.get_node(), negative))
+    }
+
+    /// Reads every register of `group`. Like `query_status_event`, this clears the event register.
+    pub fn query_status_register(
+        &mut self,
+        group: StatusRegisterGroup,
+    ) -> Result<StatusRegister, ScpiError> {
+        let node: &str = group.get_node();
+        let mut registers: Vec<u16> = Vec::new();
+        for part in ["COND", "EVEN", "ENAB", "PTR", "NTR"] {
+            let response: String = self.query_common(&format!("{}:{}?", node, part))?;
+            registers.push(parse_register16(&response)?);
+        }
+
+        Ok(StatusRegister::new(group)
+            .with_condition(registers[0])
+            .with_event(registers[1])
+            .with_enable(registers[2])
+            .with_transitions(registers[3], registers[4]))
+    }
+
+    /// Blocks until the instrument requests service and returns the status byte read afterwards.
+    /// VXI-11 and HiSLIP are notified through their interrupt and asynchronous channels; other
+    /// transports, and VXI-11 servers that cannot open an interrupt channel, poll `*STB?` every
+    /// `poll_interval`. Which bits request service is selected with
+    /// `set_service_request_enable`. Fails with `ScpiError::Timeout` after `timeout`.
+    pub fn wait_for_service_request(
+        &mut self,
+        timeout: Duration,
+        poll_interval: Duration,
+    ) -> Result<StatusByte, ScpiError> {
+        match self.transport.wait_for_service_request(timeout) {
+            Ok(true) => return self.query_status_byte(),
+            Ok(false) => return Err(ScpiError::Timeout),
+            Err(ScpiError::Unsupported(_)) => (),
+            Err(x) => return Err(x),
+        }
+
+        let options: CompletionOptions = CompletionOptions::new()
+            .with_timeout(timeout)
+            .with_poll_interval(poll_interval);
+        let mut status_byte: StatusByte = StatusByte::default();
+        self.poll_until(&options, |x| {
+            status_byte = x.query_status_byte()?;
+            Ok(status_byte.request_service())
+        })?;
+
+        Ok(status_byte)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                          Command Tree                                          */
+/* ********************************************************************************************** */
+
+impl Messenger {
+    /// With a tree set, `send_message`, `query` and `query_block` reject headers the tree does not
+    /// know and rewrite the rest in the configured `MnemonicForm`. Internal queries such as the
+    /// error queue check bypass it.
+    pub fn set_command_tree(&mut self, tree: Option<CommandTree>) {
+        self.command_tree = tree;
+    }
 
+    pub fn get_command_tree(&self) -> Option<&CommandTree> {
+        self.command_tree.as_ref()
+    }
+
+    pub fn set_mnemonic_form(&mut self, form: MnemonicForm) {
+        self.mnemonic_form = form;
+    }
+
+    pub fn get_mnemonic_form(&self) -> MnemonicForm {
+        self.mnemonic_form
+    }
+
+    fn check_command_tree<'a>(&self, message: &'a str) -> Result<Cow<'a, str>, ScpiError> {
+        match &self.command_tree {
+            Some(x) => Ok(Cow::Owned(x.normalize(message, self.mnemonic_form)?)),
+            None => Ok(Cow::Borrowed(message)),
+        }
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                         Raw Socket I/O                                         */
+/* ********************************************************************************************** */
+
+impl Messenger {
+    fn write_message(&mut self, message: &str) -> Result<usize, ScpiError> {
+        self.write_bytes(&encode_message(message, &self.options))
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        // The answer to an abandoned read may still arrive; drop it so it is not taken as the
+        // answer to this message.
+        if self.read_buffer.discard_if_stale() {
+            self.transport.clear()?;
+        }
+        self.transport.write_bytes(bytes)
+    }
+
+    /// Runs `f` with the read timeout temporarily set to `timeout`.
+    fn with_read_timeout<T, F: FnOnce(&mut Self) -> Result<T, ScpiError>>(
+        &mut self,
+        timeout: Duration,
+        f: F,
+    ) -> Result<T, ScpiError> {
+        let previous_timeout: Option<Duration> = self.options.get_read_timeout();
+
+        self.set_read_timeout(Some(timeout))?;
+        let result: Result<T, ScpiError> = f(self);
+        self.set_read_timeout(previous_timeout)?;
+
+        result
+    }
+
+    fn set_read_timeout(&mut self, timeout: Option<Duration>) -> Result<(), ScpiError> {
+        let write_timeout: Option<Duration> = self.options.get_write_timeout();
+        self.transport.set_timeout(timeout, write_timeout)
+    }
+
+    /// Reads until `take` can split a complete response off the read buffer.
+    fn read_until<F: FnMut(&mut ReadBuffer) -> Result<Option<Vec<u8>>, ScpiError>>(
+        &mut self,
+        mut take: F,
+    ) -> Result<Vec<u8>, ScpiError> {
         loop {
-            let start: std::time::Instant = std::time::Instant::now();
-            self.send_message(first_message)?;
-            while start.elapsed() < first_interval {
-                std::hint::spin_loop();
+            if let Some(x) = take(&mut self.read_buffer)? {
+                return Ok(x);
             }
 
-            let start: std::time::Instant = std::time::Instant::now();
-            self.send_message(second_message)?;
-            while start.elapsed() < second_interval {
-                std::hint::spin_loop();
-            }
+            let mut chunk: Vec<u8> = vec![0; READ_CHUNK_SIZE];
+            let received: usize = self.transport.read_bytes(&mut chunk)?;
+            self.read_buffer.extend(&chunk[..received]);
         }
     }
 }
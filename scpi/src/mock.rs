@@ -0,0 +1,298 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! A scripted instrument for tests. It records every message it receives and answers the ones
+//! listed in a `MockScript`, or whatever another `Responder` decides, so tests can assert the exact
+//! traffic without any hardware.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    io::{ErrorKind, Read, Write},
+    net::{IpAddr, Ipv4Addr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::error::ScpiError;
+use crate::networking::NetworkMode;
+
+// How often idle server threads check whether the instrument was stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(5);
+const RESPONSE_TERMINATOR: &str = "\n";
+const MAX_DATAGRAM_SIZE: usize = 65_507;
+
+/// Decides how a `MockInstrument` answers each message. `None` leaves the message unanswered.
+pub trait Responder: Send {
+    fn respond(&mut self, message: &str) -> Option<String>;
+}
+
+/// The answers a `MockInstrument` gives. Messages are matched exactly after trimming surrounding
+/// whitespace; anything not listed is recorded but never answered.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MockScript {
+    responses: HashMap<String, VecDeque<String>>,
+}
+
+impl MockScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues `response` for `message`. Queued responses are given in order, and the last one is
+    /// repeated for every further copy of the message.
+    pub fn with_response(mut self, message: &str, response: &str) -> Self {
+        self.responses
+            .entry(message.trim().to_string())
+            .or_default()
+            .push_back(response.to_string());
+        self
+    }
+
+    pub fn with_responses(self, message: &str, responses: &[&str]) -> Self {
+        responses
+            .iter()
+            .fold(self, |script, x| script.with_response(message, x))
+    }
+}
+
+impl Responder for MockScript {
+    fn respond(&mut self, message: &str) -> Option<String> {
+        let queue: &mut VecDeque<String> = self.responses.get_mut(message)?;
+        match queue.len() {
+            0 => None,
+            1 => queue.front().cloned(),
+            _ => queue.pop_front(),
+        }
+    }
+}
+
+struct MockState<R> {
+    responder: Mutex<R>,
+    received: Mutex<Vec<String>>,
+    stopped: AtomicBool,
+}
+
+impl<R: Responder> MockState<R> {
+    fn handle(&self, message: &str) -> Option<String> {
+        let message: &str = message.trim();
+        if message.is_empty() {
+            return None;
+        }
+
+        lock(&self.received).push(message.to_string());
+        lock(&self.responder)
+            .respond(message)
+            .map(|x| format!("{}{}", x, RESPONSE_TERMINATOR))
+    }
+
+    fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::Relaxed)
+    }
+}
+
+/// A TCP or UDP instrument listening on an ephemeral port. Incoming messages are split at newlines.
+/// The server threads stop when the instrument is dropped.
+pub struct MockInstrument<R: Responder + 'static = MockScript> {
+    address: SocketAddr,
+    state: Arc<MockState<R>>,
+    server: Option<JoinHandle<()>>,
+}
+
+impl<R: Responder + 'static> MockInstrument<R> {
+    /// Starts an instrument on the IPv4 loopback address.
+    pub fn start(mode: &NetworkMode, responder: R) -> Result<Self, ScpiError> {
+        Self::start_on(&IpAddr::V4(Ipv4Addr::LOCALHOST), mode, responder)
+    }
+
+    /// Starts an instrument on `address`. Only `NetworkMode::Tcp` and `NetworkMode::Udp` are mocked.
+    pub fn start_on(address: &IpAddr, mode: &NetworkMode, responder: R) -> Result<Self, ScpiError> {
+        let state: Arc<MockState<R>> = Arc::new(MockState {
+            responder: Mutex::new(responder),
+            received: Mutex::new(Vec::new()),
+            stopped: AtomicBool::new(false),
+        });
+        let server_state: Arc<MockState<R>> = Arc::clone(&state);
+
+        let (address, server): (SocketAddr, JoinHandle<()>) = match mode {
+            NetworkMode::Tcp => {
+                let listener: TcpListener = TcpListener::bind((*address, 0))?;
+                listener.set_nonblocking(true)?;
+                let local_address: SocketAddr = listener.local_addr()?;
+                let server: JoinHandle<()> = std::thread::Builder::new()
+                    .name("scpi-mock-tcp".to_string())
+                    .spawn(move || serve_tcp(listener, server_state))?;
+                (local_address, server)
+            }
+            NetworkMode::Udp => {
+                let socket: UdpSocket = UdpSocket::bind((*address, 0))?;
+                socket.set_read_timeout(Some(POLL_INTERVAL))?;
+                let local_address: SocketAddr = socket.local_addr()?;
+                let server: JoinHandle<()> = std::thread::Builder::new()
+                    .name("scpi-mock-udp".to_string())
+                    .spawn(move || serve_udp(socket, server_state))?;
+                (local_address, server)
+            }
+            _ => {
+                return Err(ScpiError::Unsupported(
+                    "Mock instruments only support TCP and UDP",
+                ))
+            }
+        };
+
+        Ok(Self {
+            address,
+            state,
+            server: Some(server),
+        })
+    }
+
+    /// Every message received so far, in order.
+    pub fn get_received(&self) -> Vec<String> {
+        lock(&self.state.received).clone()
+    }
+
+    /// Waits until at least `count` messages have arrived, then returns all of them. Fails with
+    /// `ScpiError::Timeout` if that takes longer than `timeout`.
+    pub fn wait_for_received(
+        &self,
+        count: usize,
+        timeout: Duration,
+    ) -> Result<Vec<String>, ScpiError> {
+        let deadline: Instant = Instant::now() + timeout;
+        loop {
+            let received: Vec<String> = self.get_received();
+            if received.len() >= count {
+                return Ok(received);
+            }
+
+            if Instant::now() >= deadline {
+                return Err(ScpiError::Timeout);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    pub fn clear_received(&self) {
+        lock(&self.state.received).clear();
+    }
+
+    /// Gives `f` the responder while no message is being handled, e.g. to inspect simulator state.
+    pub fn with_responder<T, F: FnOnce(&mut R) -> T>(&self, f: F) -> T {
+        f(&mut lock(&self.state.responder))
+    }
+
+    pub fn get_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn get_port(&self) -> u16 {
+        self.address.port()
+    }
+}
+
+impl MockInstrument<MockScript> {
+    /// Queues another response, as `MockScript::with_response` does, while the instrument runs.
+    pub fn add_response(&self, message: &str, response: &str) {
+        self.with_responder(|x| *x = std::mem::take(x).with_response(message, response));
+    }
+}
+
+impl<R: Responder + 'static> Drop for MockInstrument<R> {
+    fn drop(&mut self) {
+        self.state.stopped.store(true, Ordering::Relaxed);
+        if let Some(server) = self.server.take() {
+            let _ = server.join();
+        }
+    }
+}
+
+fn serve_tcp<R: Responder + 'static>(listener: TcpListener, state: Arc<MockState<R>>) {
+    let mut connections: Vec<JoinHandle<()>> = Vec::new();
+    while !state.is_stopped() {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                let connection_state: Arc<MockState<R>> = Arc::clone(&state);
+                connections.push(std::thread::spawn(move || {
+                    serve_connection(stream, connection_state)
+                }));
+            }
+            Err(x) if x.kind() == ErrorKind::WouldBlock => std::thread::sleep(POLL_INTERVAL),
+            Err(_) => break,
+        }
+    }
+
+    for connection in connections {
+        let _ = connection.join();
+    }
+}
+
+fn serve_connection<R: Responder>(mut stream: TcpStream, state: Arc<MockState<R>>) {
+    if stream.set_nonblocking(false).is_err()
+        || stream.set_read_timeout(Some(POLL_INTERVAL)).is_err()
+    {
+        return;
+    }
+
+    let mut pending: Vec<u8> = Vec::new();
+    let mut chunk: [u8; 1024] = [0; 1024];
+    while !state.is_stopped() {
+        let received: usize = match stream.read(&mut chunk) {
+            Ok(0) => return,
+            Ok(x) => x,
+            Err(x) if matches!(x.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            Err(_) => return,
+        };
+        pending.extend_from_slice(&chunk[..received]);
+
+        while let Some(position) = pending.iter().position(|x| *x == b'\n') {
+            let line: Vec<u8> = pending.drain(..=position).collect();
+            if let Some(response) = state.handle(&String::from_utf8_lossy(&line)) {
+                if stream.write_all(response.as_bytes()).is_err() {
+                    return;
+                }
+            }
+        }
+    }
+}
+
+fn serve_udp<R: Responder>(socket: UdpSocket, state: Arc<MockState<R>>) {
+    let mut datagram: Vec<u8> = vec![0; MAX_DATAGRAM_SIZE];
+    while !state.is_stopped() {
+        let (received, sender): (usize, SocketAddr) = match socket.recv_from(&mut datagram) {
+            Ok(x) => x,
+            Err(x) if matches!(x.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            // Windows reports an earlier response that could not be delivered here.
+            Err(x) if x.kind() == ErrorKind::ConnectionReset => continue,
+            Err(_) => return,
+        };
+
+        let text: String = String::from_utf8_lossy(&datagram[..received]).to_string();
+        for message in text.split('\n') {
+            if let Some(response) = state.handle(message) {
+                let _ = socket.send_to(response.as_bytes(), sender);
+            }
+        }
+    }
+}
+
+// A panicking test thread must not hide the traffic from the assertions that follow.
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|x| x.into_inner())
+}
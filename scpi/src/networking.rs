@@ -14,16 +14,276 @@
    limitations under the License.
 */
 
-use std::net::{TcpStream, UdpSocket};
+use std::{
+    io::{Error, ErrorKind, Read, Write},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket},
+    time::Duration,
+};
+
+use rs232::SerialConnection;
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::connection_options::MulticastOptions;
+use crate::error::ScpiError;
+use crate::transport::Transport;
+
+pub use rs232::{DataBits, FlowControl, Parity, SerialConfig, StopBits};
+
+const DRAIN_CHUNK_SIZE: usize = 4096;
 
 pub enum NetworkMode {
     Udp,
     Tcp,
     UdpMulticast,
-    TcpMulticast,
+    /// Writes every message to each of `members` on the remote port, instead of to the remote
+    /// client. Only sending works in this mode, so queries and error checking are refused before
+    /// anything is sent; `TcpMulticastGroup` also queries each instrument.
+    /// `send_list_of_scpi_messages` sends the whole list to every member it can reach and then
+    /// lists each failed member, while a `Messenger` stops at the first write any member fails.
+    TcpMulticast {
+        members: Vec<IpAddr>,
+        parallel: bool,
+    },
+    Hislip,
+    /// Talks RS-232 over the serial port at `path`. The remote client and ports are not used.
+    Serial {
+        path: String,
+        config: SerialConfig,
+    },
+}
+
+/* ********************************************************************************************** */
+/*                                          UDP Transport                                         */
+/* ********************************************************************************************** */
+
+pub struct UdpTransport {
+    socket: UdpSocket,
+    destination: SocketAddr,
 }
 
-pub enum NetworkSender {
-    Udp(UdpSocket),
-    Tcp(TcpStream),
+impl UdpTransport {
+    pub fn new(socket: UdpSocket, destination: SocketAddr) -> Self {
+        Self {
+            socket,
+            destination,
+        }
+    }
+
+    /// Binds the unspecified address of the same family as `destination`.
+    pub fn bind(local_port: u16, destination: SocketAddr) -> Result<Self, ScpiError> {
+        let socket: UdpSocket = UdpSocket::bind(unspecified_address(&destination, local_port))?;
+        Ok(Self::new(socket, destination))
+    }
+
+    pub fn join_multicast(
+        local_port: u16,
+        group: SocketAddr,
+        options: &MulticastOptions,
+    ) -> Result<Self, ScpiError> {
+        let local_address: SocketAddr = unspecified_address(&group, local_port);
+        let socket: Socket = Socket::new(
+            Domain::for_address(local_address),
+            Type::DGRAM,
+            Some(Protocol::UDP),
+        )?;
+        socket.set_reuse_address(true)?;
+
+        match group.ip() {
+            IpAddr::V4(x) => {
+                socket.set_multicast_if_v4(&options.get_interface_v4())?;
+                socket.set_multicast_loop_v4(options.get_loopback())?;
+                if let Some(hops) = options.get_hops() {
+                    socket.set_multicast_ttl_v4(hops)?;
+                }
+                socket.bind(&local_address.into())?;
+                socket.join_multicast_v4(&x, &options.get_interface_v4())?;
+            }
+            IpAddr::V6(x) => {
+                socket.set_only_v6(true)?;
+                socket.set_multicast_if_v6(options.get_interface_index())?;
+                socket.set_multicast_loop_v6(options.get_loopback())?;
+                if let Some(hops) = options.get_hops() {
+                    socket.set_multicast_hops_v6(hops)?;
+                }
+                socket.bind(&local_address.into())?;
+                socket.join_multicast_v6(&x, options.get_interface_index())?;
+            }
+        }
+
+        Ok(Self::new(socket.into(), group))
+    }
+
+    pub fn local_address(&self) -> Result<SocketAddr, ScpiError> {
+        Ok(self.socket.local_addr()?)
+    }
+
+    pub fn into_parts(self) -> (UdpSocket, SocketAddr) {
+        (self.socket, self.destination)
+    }
+}
+
+impl Transport for UdpTransport {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        Ok(self.socket.send_to(bytes, self.destination)?)
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<usize, ScpiError> {
+        let (received, source): (usize, SocketAddr) = self.socket.recv_from(buffer)?;
+        match is_response_source(&self.destination, &source) {
+            true => Ok(received),
+            false => Ok(0),
+        }
+    }
+
+    fn set_timeout(
+        &mut self,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError> {
+        self.socket.set_read_timeout(read_timeout)?;
+        self.socket.set_write_timeout(write_timeout)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ScpiError> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        self.socket.set_nonblocking(true)?;
+        let mut chunk: [u8; DRAIN_CHUNK_SIZE] = [0; DRAIN_CHUNK_SIZE];
+        let result: Result<(), Error> = loop {
+            match self.socket.recv_from(&mut chunk) {
+                Ok(_) => continue,
+                Err(x) if x.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(x) => break Err(x),
+            }
+        };
+        self.socket.set_nonblocking(false)?;
+
+        Ok(result?)
+    }
+}
+
+/// Whether a datagram from `source` may answer what was sent to `destination`. Multicast and
+/// broadcast destinations are answered by each instrument from its own address.
+pub(crate) fn is_response_source(destination: &SocketAddr, source: &SocketAddr) -> bool {
+    let shared: bool = match destination.ip() {
+        IpAddr::V4(x) => x.is_multicast() || x.is_broadcast(),
+        IpAddr::V6(x) => x.is_multicast(),
+    };
+    shared || source == destination
+}
+
+fn unspecified_address(destination: &SocketAddr, port: u16) -> SocketAddr {
+    match destination {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), port),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), port),
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                          TCP Transport                                         */
+/* ********************************************************************************************** */
+
+pub struct TcpTransport {
+    stream: TcpStream,
+}
+
+impl TcpTransport {
+    pub fn new(stream: TcpStream) -> Self {
+        Self { stream }
+    }
+
+    pub fn connect(
+        remote_address: SocketAddr,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Self, ScpiError> {
+        let stream: TcpStream = match connect_timeout {
+            Some(timeout) => TcpStream::connect_timeout(&remote_address, timeout)?,
+            None => TcpStream::connect(remote_address)?,
+        };
+
+        Ok(Self::new(stream))
+    }
+}
+
+impl Transport for TcpTransport {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        self.stream.write_all(bytes)?;
+        Ok(bytes.len())
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<usize, ScpiError> {
+        match self.stream.read(buffer)? {
+            0 => Err(ScpiError::Connection(Error::new(
+                ErrorKind::UnexpectedEof,
+                "Connection closed before a complete response was received",
+            ))),
+            n => Ok(n),
+        }
+    }
+
+    fn set_timeout(
+        &mut self,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError> {
+        self.stream.set_read_timeout(read_timeout)?;
+        self.stream.set_write_timeout(write_timeout)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<(), ScpiError> {
+        Ok(self.stream.flush()?)
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        self.stream.set_nonblocking(true)?;
+        let mut chunk: [u8; DRAIN_CHUNK_SIZE] = [0; DRAIN_CHUNK_SIZE];
+        let result: Result<(), Error> = loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => break Ok(()),
+                Ok(_) => continue,
+                Err(x) if x.kind() == ErrorKind::WouldBlock => break Ok(()),
+                Err(x) => break Err(x),
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+
+        Ok(result?)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                        Serial Transport                                        */
+/* ********************************************************************************************** */
+
+impl Transport for SerialConnection {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        self.write_all(bytes)?;
+        Write::flush(self)?;
+        Ok(bytes.len())
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<usize, ScpiError> {
+        Ok(self.read(buffer)?)
+    }
+
+    /// Serial ports have a single timeout, so only the read timeout is applied.
+    fn set_timeout(
+        &mut self,
+        read_timeout: Option<Duration>,
+        _write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError> {
+        Ok(SerialConnection::set_timeout(self, read_timeout)?)
+    }
+
+    fn flush(&mut self) -> Result<(), ScpiError> {
+        Ok(Write::flush(self)?)
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        Ok(SerialConnection::clear(self)?)
+    }
 }
@@ -0,0 +1,375 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Minimal ONC RPC (RFC 5531) over TCP with XDR (RFC 4506) encoding, as needed by VXI-11.
+
+use std::{
+    io::{Error, Read, Write},
+    net::{SocketAddr, TcpStream},
+    time::Duration,
+};
+
+use crate::error::ScpiError;
+
+const RPC_VERSION: u32 = 2;
+const CALL: u32 = 0;
+const REPLY: u32 = 1;
+const MSG_ACCEPTED: u32 = 0;
+const SUCCESS: u32 = 0;
+const AUTH_NONE: u32 = 0;
+const LAST_FRAGMENT: u32 = 0x8000_0000;
+const MAX_RECORD_SIZE: usize = 16 * 1024 * 1024;
+
+pub(crate) const PORTMAPPER_PROGRAM: u32 = 100_000;
+pub(crate) const PORTMAPPER_VERSION: u32 = 2;
+pub(crate) const PORTMAPPER_GETPORT: u32 = 3;
+pub(crate) const IPPROTO_TCP: u32 = 6;
+
+/* ********************************************************************************************** */
+/*                                          XDR Encoding                                          */
+/* ********************************************************************************************** */
+
+#[derive(Default)]
+pub(crate) struct XdrWriter {
+    buffer: Vec<u8>,
+}
+
+impl XdrWriter {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn put_u32(mut self, value: u32) -> Self {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn put_i32(mut self, value: i32) -> Self {
+        self.buffer.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub(crate) fn put_bool(self, value: bool) -> Self {
+        self.put_u32(value as u32)
+    }
+
+    pub(crate) fn put_opaque(mut self, data: &[u8]) -> Self {
+        self.buffer
+            .extend_from_slice(&(data.len() as u32).to_be_bytes());
+        self.buffer.extend_from_slice(data);
+        self.buffer
+            .resize(self.buffer.len() + padding(data.len()), 0);
+        self
+    }
+
+    pub(crate) fn put_string(self, value: &str) -> Self {
+        self.put_opaque(value.as_bytes())
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.buffer
+    }
+}
+
+pub(crate) struct XdrReader<'a> {
+    buffer: &'a [u8],
+    position: usize,
+}
+
+impl<'a> XdrReader<'a> {
+    pub(crate) fn new(buffer: &'a [u8]) -> Self {
+        Self {
+            buffer,
+            position: 0,
+        }
+    }
+
+    pub(crate) fn get_u32(&mut self) -> Result<u32, ScpiError> {
+        let bytes: &[u8] = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub(crate) fn get_i32(&mut self) -> Result<i32, ScpiError> {
+        Ok(self.get_u32()? as i32)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn get_bool(&mut self) -> Result<bool, ScpiError> {
+        Ok(self.get_u32()? != 0)
+    }
+
+    pub(crate) fn get_opaque(&mut self) -> Result<Vec<u8>, ScpiError> {
+        let length: usize = self.get_u32()? as usize;
+        let data: Vec<u8> = self.take(length)?.to_vec();
+        self.take(padding(length))?;
+        Ok(data)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn get_string(&mut self) -> Result<String, ScpiError> {
+        match String::from_utf8(self.get_opaque()?) {
+            Ok(x) => Ok(x),
+            Err(_) => Err(ScpiError::Parse(
+                "XDR string was not valid UTF-8".to_string(),
+            )),
+        }
+    }
+
+    pub(crate) fn remaining(&self) -> &'a [u8] {
+        &self.buffer[self.position..]
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ScpiError> {
+        if self.buffer.len() - self.position < length {
+            return Err(ScpiError::Parse("XDR data ended unexpectedly".to_string()));
+        }
+
+        let bytes: &'a [u8] = &self.buffer[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+}
+
+fn padding(length: usize) -> usize {
+    (4 - length % 4) % 4
+}
+
+/* ********************************************************************************************** */
+/*                                         Record Marking                                         */
+/* ********************************************************************************************** */
+
+pub(crate) fn write_record<W: Write>(stream: &mut W, record: &[u8]) -> Result<(), ScpiError> {
+    let mut message: Vec<u8> = (LAST_FRAGMENT | record.len() as u32).to_be_bytes().to_vec();
+    message.extend_from_slice(record);
+    stream.write_all(&message)?;
+    Ok(())
+}
+
+pub(crate) fn read_record<R: Read>(stream: &mut R) -> Result<Vec<u8>, ScpiError> {
+    let mut record: Vec<u8> = Vec::new();
+    loop {
+        let mut header: [u8; 4] = [0; 4];
+        stream.read_exact(&mut header)?;
+        let header: u32 = u32::from_be_bytes(header);
+        let length: usize = (header & !LAST_FRAGMENT) as usize;
+
+        if record.len() + length > MAX_RECORD_SIZE {
+            return Err(ScpiError::Parse(format!(
+                "RPC record exceeds {} bytes",
+                MAX_RECORD_SIZE
+            )));
+        }
+
+        let start: usize = record.len();
+        record.resize(start + length, 0);
+        stream.read_exact(&mut record[start..])?;
+
+        if header & LAST_FRAGMENT != 0 {
+            return Ok(record);
+        }
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                           RPC Client                                           */
+/* ********************************************************************************************** */
+
+pub(crate) struct RpcClient {
+    stream: TcpStream,
+    next_xid: u32,
+}
+
+impl RpcClient {
+    pub(crate) fn connect(
+        address: SocketAddr,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Self, ScpiError> {
+        let stream: TcpStream = match connect_timeout {
+            Some(timeout) => TcpStream::connect_timeout(&address, timeout)?,
+            None => TcpStream::connect(address)?,
+        };
+        stream.set_nodelay(true)?;
+
+        Ok(Self {
+            stream,
+            next_xid: 1,
+        })
+    }
+
+    pub(crate) fn local_addr(&self) -> Result<SocketAddr, ScpiError> {
+        Ok(self.stream.local_addr()?)
+    }
+
+    pub(crate) fn set_timeout(&mut self, timeout: Option<Duration>) -> Result<(), ScpiError> {
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)?;
+        Ok(())
+    }
+
+    /// Sends one call and returns the encoded results of a successful reply.
+    pub(crate) fn call(
+        &mut self,
+        program: u32,
+        version: u32,
+        procedure: u32,
+        params: &[u8],
+    ) -> Result<Vec<u8>, ScpiError> {
+        let xid: u32 = self.next_xid;
+        self.next_xid = self.next_xid.wrapping_add(1);
+
+        let mut message: Vec<u8> = XdrWriter::new()
+            .put_u32(xid)
+            .put_u32(CALL)
+            .put_u32(RPC_VERSION)
+            .put_u32(program)
+            .put_u32(version)
+            .put_u32(procedure)
+            .put_u32(AUTH_NONE)
+            .put_opaque(&[])
+            .put_u32(AUTH_NONE)
+            .put_opaque(&[])
+            .into_bytes();
+        message.extend_from_slice(params);
+        write_record(&mut self.stream, &message)?;
+
+        loop {
+            let reply: Vec<u8> = read_record(&mut self.stream)?;
+            let mut reader: XdrReader = XdrReader::new(&reply);
+            if reader.get_u32()? != xid {
+                continue;
+            }
+
+            if reader.get_u32()? != REPLY {
+                return Err(rpc_error("RPC peer sent a call instead of a reply"));
+            }
+
+            if reader.get_u32()? != MSG_ACCEPTED {
+                return Err(rpc_error("RPC call was denied"));
+            }
+
+            reader.get_u32()?;
+            reader.get_opaque()?;
+            let accept_status: u32 = reader.get_u32()?;
+            if accept_status != SUCCESS {
+                return Err(rpc_error(&format!(
+                    "RPC call was not executed (accept status {})",
+                    accept_status
+                )));
+            }
+
+            return Ok(reader.remaining().to_vec());
+        }
+    }
+}
+
+/// Looks up the TCP port of `program` through the portmapper listening at `portmapper`.
+pub(crate) fn get_port(
+    portmapper: SocketAddr,
+    program: u32,
+    version: u32,
+    connect_timeout: Option<Duration>,
+) -> Result<u16, ScpiError> {
+    let mut client: RpcClient = RpcClient::connect(portmapper, connect_timeout)?;
+    client.set_timeout(connect_timeout)?;
+
+    let params: Vec<u8> = XdrWriter::new()
+        .put_u32(program)
+        .put_u32(version)
+        .put_u32(IPPROTO_TCP)
+        .put_u32(0)
+        .into_bytes();
+    let results: Vec<u8> = client.call(
+        PORTMAPPER_PROGRAM,
+        PORTMAPPER_VERSION,
+        PORTMAPPER_GETPORT,
+        &params,
+    )?;
+
+    match XdrReader::new(&results).get_u32()? {
+        0 => Err(rpc_error(&format!(
+            "RPC program {:#x} version {} is not registered with the portmapper",
+            program, version
+        ))),
+        port => match u16::try_from(port) {
+            Ok(x) => Ok(x),
+            Err(_) => Err(ScpiError::Parse(format!(
+                "Portmapper returned port {}",
+                port
+            ))),
+        },
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                           RPC Server                                           */
+/* ********************************************************************************************** */
+
+pub(crate) struct RpcCall {
+    #[cfg_attr(not(test), allow(dead_code))]
+    pub(crate) xid: u32,
+    pub(crate) program: u32,
+    pub(crate) procedure: u32,
+    pub(crate) params: Vec<u8>,
+}
+
+/// Reads the next call from a client.
+pub(crate) fn read_call<R: Read>(stream: &mut R) -> Result<RpcCall, ScpiError> {
+    let record: Vec<u8> = read_record(stream)?;
+    let mut reader: XdrReader = XdrReader::new(&record);
+
+    let xid: u32 = reader.get_u32()?;
+    if reader.get_u32()? != CALL {
+        return Err(rpc_error("RPC peer sent a reply instead of a call"));
+    }
+
+    reader.get_u32()?;
+    let program: u32 = reader.get_u32()?;
+    reader.get_u32()?;
+    let procedure: u32 = reader.get_u32()?;
+    reader.get_u32()?;
+    reader.get_opaque()?;
+    reader.get_u32()?;
+    reader.get_opaque()?;
+
+    Ok(RpcCall {
+        xid,
+        program,
+        procedure,
+        params: reader.remaining().to_vec(),
+    })
+}
+
+#[cfg(test)]
+pub(crate) fn write_reply<W: Write>(
+    stream: &mut W,
+    xid: u32,
+    results: &[u8],
+) -> Result<(), ScpiError> {
+    let mut message: Vec<u8> = XdrWriter::new()
+        .put_u32(xid)
+        .put_u32(REPLY)
+        .put_u32(MSG_ACCEPTED)
+        .put_u32(AUTH_NONE)
+        .put_opaque(&[])
+        .put_u32(SUCCESS)
+        .into_bytes();
+    message.extend_from_slice(results);
+    write_record(stream, &message)
+}
+
+fn rpc_error(message: &str) -> ScpiError {
+    ScpiError::Connection(Error::other(message))
+}
@@ -0,0 +1,208 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! VISA-style resource strings, e.g. `TCPIP0::192.168.1.70::5025::SOCKET`.
+
+use std::{
+    io::{Error, ErrorKind},
+    net::{SocketAddr, ToSocketAddrs},
+    str::FromStr,
+};
+
+use crate::error::ScpiError;
+use crate::hislip::HISLIP_PORT;
+use crate::vxi11::DEFAULT_DEVICE_NAME;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Resource {
+    /// `TCPIP[board]::host::port::SOCKET`
+    Socket { host: String, port: u16 },
+    /// `TCPIP[board]::host[::device_name][::INSTR]`
+    Vxi11 { host: String, device_name: String },
+    /// `TCPIP[board]::host::hislipN[,port][::INSTR]`
+    Hislip {
+        host: String,
+        sub_address: String,
+        port: u16,
+    },
+    /// `ASRL[board | path]::INSTR`
+    Serial { path: String },
+}
+
+impl FromStr for Resource {
+    type Err = ScpiError;
+
+    fn from_str(resource: &str) -> Result<Self, Self::Err> {
+        let resource: &str = resource.trim();
+        let upper: String = resource.to_ascii_uppercase();
+
+        if upper.starts_with("TCPIP") {
+            parse_tcpip(resource, &resource["TCPIP".len()..])
+        } else if upper.starts_with("ASRL") {
+            parse_serial(resource, &resource["ASRL".len()..])
+        } else {
+            Err(invalid_resource(resource, "unknown interface type"))
+        }
+    }
+}
+
+impl Resource {
+    /// Resolves the host of a network resource to every address it maps to. The port is the one
+    /// the resource connects to first (the portmapper for VXI-11).
+    pub fn resolve(&self, vxi11_portmapper_port: u16) -> Result<Vec<SocketAddr>, ScpiError> {
+        let (host, port): (&str, u16) = match self {
+            Self::Socket { host, port } => (host, *port),
+            Self::Vxi11 { host, .. } => (host, vxi11_portmapper_port),
+            Self::Hislip { host, port, .. } => (host, *port),
+            Self::Serial { .. } => {
+                return Err(ScpiError::InvalidArgument(
+                    "Serial resources have no network address".to_string(),
+                ))
+            }
+        };
+
+        let addresses: Vec<SocketAddr> = (host, port).to_socket_addrs()?.collect();
+        match addresses.is_empty() {
+            true => Err(ScpiError::Connection(Error::new(
+                ErrorKind::NotFound,
+                format!("Host {:?} did not resolve to any address", host),
+            ))),
+            false => Ok(addresses),
+        }
+    }
+}
+
+fn parse_tcpip(resource: &str, rest: &str) -> Result<Resource, ScpiError> {
+    let rest: &str = rest.trim_start_matches(|x: char| x.is_ascii_digit());
+    let rest: &str = match rest.strip_prefix("::") {
+        Some(x) => x,
+        None => return Err(invalid_resource(resource, "expected \"::\" after TCPIP")),
+    };
+
+    let (host, rest): (&str, &str) = split_host(resource, rest)?;
+    let mut fields: Vec<&str> = match rest.is_empty() {
+        true => Vec::new(),
+        false => rest.split("::").collect(),
+    };
+
+    let resource_class: String = match fields.last() {
+        Some(x) if x.eq_ignore_ascii_case("SOCKET") || x.eq_ignore_ascii_case("INSTR") => {
+            fields.pop().unwrap_or_default().to_ascii_uppercase()
+        }
+        _ => "INSTR".to_string(),
+    };
+
+    match (resource_class.as_str(), fields.as_slice()) {
+        ("SOCKET", [port]) => Ok(Resource::Socket {
+            host: host.to_string(),
+            port: parse_port(resource, port)?,
+        }),
+        ("INSTR", []) => Ok(Resource::Vxi11 {
+            host: host.to_string(),
+            device_name: DEFAULT_DEVICE_NAME.to_string(),
+        }),
+        ("INSTR", [device]) if device.to_ascii_lowercase().starts_with("hislip") => {
+            let (sub_address, port): (&str, u16) = match device.split_once(',') {
+                Some((name, port)) => (name, parse_port(resource, port)?),
+                None => (device, HISLIP_PORT),
+            };
+            Ok(Resource::Hislip {
+                host: host.to_string(),
+                sub_address: sub_address.to_string(),
+                port,
+            })
+        }
+        ("INSTR", [device]) if !device.is_empty() => Ok(Resource::Vxi11 {
+            host: host.to_string(),
+            device_name: device.to_string(),
+        }),
+        _ => Err(invalid_resource(resource, "unexpected fields after host")),
+    }
+}
+
+fn parse_serial(resource: &str, rest: &str) -> Result<Resource, ScpiError> {
+    // Slice with `get` so a multi-byte character before the suffix is not split.
+    let suffix_start: usize = rest.len().saturating_sub("::INSTR".len());
+    let target: &str = match rest
+        .get(suffix_start..)
+        .is_some_and(|x| x.eq_ignore_ascii_case("::INSTR"))
+    {
+        true => &rest[..suffix_start],
+        false => rest,
+    };
+
+    if target.is_empty() || target.contains("::") {
+        return Err(invalid_resource(
+            resource,
+            "expected ASRL<board|path>::INSTR",
+        ));
+    }
+
+    let path: String = match target.parse::<u16>() {
+        Ok(board) => serial_board_path(board),
+        Err(_) => target.to_string(),
+    };
+
+    Ok(Resource::Serial { path })
+}
+
+/// VISA numbers serial boards from 1, matching COM1 on Windows and ttyS0 elsewhere.
+fn serial_board_path(board: u16) -> String {
+    match cfg!(windows) {
+        true => format!("COM{}", board),
+        false => format!("/dev/ttyS{}", board.saturating_sub(1)),
+    }
+}
+
+/// Splits off the host, which may be a bracketed IPv6 literal containing "::".
+fn split_host<'a>(resource: &str, rest: &'a str) -> Result<(&'a str, &'a str), ScpiError> {
+    let (host, remainder): (&str, &str) = match rest.strip_prefix('[') {
+        Some(x) => match x.split_once(']') {
+            Some(x) => x,
+            None => return Err(invalid_resource(resource, "unterminated \"[\" in host")),
+        },
+        None => match rest.find("::") {
+            Some(x) => (&rest[..x], &rest[x..]),
+            None => (rest, ""),
+        },
+    };
+
+    if host.is_empty() {
+        return Err(invalid_resource(resource, "missing host"));
+    }
+
+    match remainder.is_empty() {
+        true => Ok((host, remainder)),
+        false => match remainder.strip_prefix("::") {
+            Some(x) => Ok((host, x)),
+            None => Err(invalid_resource(resource, "expected \"::\" after host")),
+        },
+    }
+}
+
+fn parse_port(resource: &str, port: &str) -> Result<u16, ScpiError> {
+    match port.trim().parse::<u16>() {
+        Ok(x) => Ok(x),
+        Err(_) => Err(invalid_resource(
+            resource,
+            &format!("invalid port {:?}", port),
+        )),
+    }
+}
+
+fn invalid_resource(resource: &str, reason: &str) -> ScpiError {
+    ScpiError::InvalidArgument(format!("Invalid resource {:?}: {}", resource, reason))
+}
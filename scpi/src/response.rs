@@ -0,0 +1,326 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Decodes query responses into typed values.
+
+use crate::block::{parse_block_header, BlockHeader};
+use crate::command::ChannelList;
+use crate::error::ScpiError;
+
+/// SCPI reports positive and negative overflow as +/-9.9E37 and "not a number" as 9.91E37.
+const SCPI_INFINITY: f64 = 9.9e37;
+const SCPI_NAN: f64 = 9.91e37;
+
+/// A response element whose type was worked out from its syntax.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ResponseValue {
+    Integer(i64),
+    Float(f64),
+    /// A quoted string, with the quotes removed and doubled quotes collapsed.
+    String(String),
+    /// Unquoted character data such as `VOLT` or `BUS`.
+    Character(String),
+    ChannelList(ChannelList),
+    /// A comma-separated response with more than one element.
+    List(Vec<ResponseValue>),
+}
+
+/// A type that can be decoded from a query response.
+pub trait FromResponse: Sized {
+    fn from_response(response: &str) -> Result<Self, ScpiError>;
+}
+
+impl FromResponse for i64 {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        parse_integer(response)
+    }
+}
+
+impl FromResponse for f64 {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        parse_float(response)
+    }
+}
+
+impl FromResponse for bool {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        parse_boolean(response)
+    }
+}
+
+impl FromResponse for String {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        parse_string(response)
+    }
+}
+
+impl FromResponse for ChannelList {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        parse_channel_list(response)
+    }
+}
+
+impl FromResponse for ResponseValue {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        parse_value(response)
+    }
+}
+
+impl<T: FromResponse> FromResponse for Vec<T> {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        split_list(response)?
+            .into_iter()
+            .map(T::from_response)
+            .collect()
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                             Numbers                                            */
+/* ********************************************************************************************** */
+
+/// Parses an NR1 integer. Integral NR2/NR3 values such as `+1.00000000E+00` and the IEEE 488.2
+/// `#H`, `#Q` and `#B` forms are accepted too.
+pub fn parse_integer(response: &str) -> Result<i64, ScpiError> {
+    let response: &str = response.trim();
+    let radix: Option<u32> = match response.get(..2).map(|x| x.to_ascii_uppercase()) {
+        Some(x) if x == "#H" => Some(16),
+        Some(x) if x == "#Q" => Some(8),
+        Some(x) if x == "#B" => Some(2),
+        _ => None,
+    };
+
+    if let Some(radix) = radix {
+        return i64::from_str_radix(&response[2..], radix)
+            .map_err(|_| ScpiError::Parse(format!("'{}' is not an integer", response)));
+    }
+
+    if let Ok(x) = response.parse::<i64>() {
+        return Ok(x);
+    }
+
+    match parse_float(response) {
+        Ok(x) if x.fract() == 0.0 && x.abs() < i64::MAX as f64 => Ok(x as i64),
+        _ => Err(ScpiError::Parse(format!(
+            "'{}' is not an integer",
+            response
+        ))),
+    }
+}
+
+/// Parses an NR1, NR2 or NR3 number. `9.9E37` maps to infinity and `9.91E37` to NaN, as do the
+/// `INF`, `NINF` and `NAN` mnemonics.
+pub fn parse_float(response: &str) -> Result<f64, ScpiError> {
+    let response: &str = response.trim();
+    match response.to_ascii_uppercase().as_str() {
+        "NAN" => return Ok(f64::NAN),
+        "INF" | "+INF" => return Ok(f64::INFINITY),
+        "NINF" | "-INF" => return Ok(f64::NEG_INFINITY),
+        _ => {}
+    }
+
+    // Rust also accepts words like "inf" and "infinity", which are not SCPI numbers.
+    let numeric: bool = response.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c))
+        && response
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+    let value: f64 = match numeric {
+        true => response
+            .parse()
+            .map_err(|_| ScpiError::Parse(format!("'{}' is not a number", response)))?,
+        false => return Err(ScpiError::Parse(format!("'{}' is not a number", response))),
+    };
+
+    if value == SCPI_NAN {
+        return Ok(f64::NAN);
+    }
+
+    match value.abs() == SCPI_INFINITY {
+        true => Ok(value.signum() * f64::INFINITY),
+        false => Ok(value),
+    }
+}
+
+/// Parses `1`/`0` or `ON`/`OFF`. Other numbers are true when they are not zero.
+pub fn parse_boolean(response: &str) -> Result<bool, ScpiError> {
+    let response: &str = response.trim();
+    match response.to_ascii_uppercase().as_str() {
+        "ON" => Ok(true),
+        "OFF" => Ok(false),
+        _ => match parse_float(response) {
+            Ok(x) if !x.is_nan() => Ok(x != 0.0),
+            _ => Err(ScpiError::Parse(format!("'{}' is not a boolean", response))),
+        },
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                         Text and Lists                                         */
+/* ********************************************************************************************** */
+
+/// Parses a single- or double-quoted string, collapsing doubled quotes.
+pub fn parse_string(response: &str) -> Result<String, ScpiError> {
+    let response: &str = response.trim();
+    let quote: char = match response.chars().next() {
+        Some(x @ ('"' | '\'')) if response.len() >= 2 && response.ends_with(x) => x,
+        _ => {
+            return Err(ScpiError::Parse(format!(
+                "'{}' is not a quoted string",
+                response
+            )))
+        }
+    };
+
+    let inner: &str = &response[1..response.len() - 1];
+    let doubled: String = format!("{}{}", quote, quote);
+    if inner.replace(&doubled, "").contains(quote) {
+        return Err(ScpiError::Parse(format!(
+            "'{}' has an unescaped quote",
+            response
+        )));
+    }
+
+    Ok(inner.replace(&doubled, &quote.to_string()))
+}
+
+/// Splits a comma-separated response, leaving commas inside quotes and parentheses alone.
+pub fn split_list(response: &str) -> Result<Vec<&str>, ScpiError> {
+    let response: &str = response.trim();
+    if response.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut elements: Vec<&str> = Vec::new();
+    let mut quote: Option<char> = None;
+    let mut depth: usize = 0;
+    let mut start: usize = 0;
+
+    for (index, c) in response.char_indices() {
+        match (quote, c) {
+            (Some(x), _) if c == x => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth = depth.saturating_sub(1),
+            (None, ',') if depth == 0 => {
+                elements.push(response[start..index].trim());
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if quote.is_some() || depth > 0 {
+        return Err(ScpiError::Parse(format!(
+            "'{}' has an unterminated string or channel list",
+            response
+        )));
+    }
+
+    elements.push(response[start..].trim());
+    Ok(elements)
+}
+
+/// Parses a channel list such as `(@1,2:5)`.
+pub fn parse_channel_list(response: &str) -> Result<ChannelList, ScpiError> {
+    let response: &str = response.trim();
+    let error = || ScpiError::Parse(format!("'{}' is not a channel list", response));
+
+    let inner: &str = response
+        .strip_prefix("(@")
+        .and_then(|x| x.strip_suffix(')'))
+        .ok_or_else(error)?;
+
+    inner
+        .split(',')
+        .map(str::trim)
+        .try_fold(ChannelList::new(), |list, entry| {
+            match entry.split_once(':') {
+                Some((first, last)) => Ok(list.with_range(
+                    first.trim().parse().map_err(|_| error())?,
+                    last.trim().parse().map_err(|_| error())?,
+                )),
+                None => Ok(list.with_channel(entry.parse().map_err(|_| error())?)),
+            }
+        })
+}
+
+/// Works out the type of a response from its syntax, returning a `ResponseValue::List` when it has
+/// several comma-separated elements.
+pub fn parse_value(response: &str) -> Result<ResponseValue, ScpiError> {
+    let elements: Vec<&str> = split_list(response)?;
+    match elements.as_slice() {
+        [] => Ok(ResponseValue::Character(String::new())),
+        [x] => parse_element(x),
+        _ => Ok(ResponseValue::List(
+            elements
+                .iter()
+                .map(|x| parse_element(x))
+                .collect::<Result<Vec<ResponseValue>, ScpiError>>()?,
+        )),
+    }
+}
+
+fn parse_element(element: &str) -> Result<ResponseValue, ScpiError> {
+    if element.starts_with(['"', '\'']) {
+        return parse_string(element).map(ResponseValue::String);
+    }
+
+    if element.starts_with("(@") {
+        return parse_channel_list(element).map(ResponseValue::ChannelList);
+    }
+
+    if let Ok(x) = element.parse::<i64>() {
+        return Ok(ResponseValue::Integer(x));
+    }
+
+    if element.starts_with('#') {
+        return parse_integer(element).map(ResponseValue::Integer);
+    }
+
+    match parse_float(element) {
+        Ok(x) => Ok(ResponseValue::Float(x)),
+        Err(_) => Ok(ResponseValue::Character(element.to_string())),
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                             Blocks                                             */
+/* ********************************************************************************************** */
+
+/// Returns the data of a complete definite or indefinite block response. Anything after a definite
+/// block, and the newline ending an indefinite one, is ignored.
+pub fn parse_block(response: &[u8]) -> Result<&[u8], ScpiError> {
+    match parse_block_header(response)? {
+        Some(BlockHeader::Definite {
+            header_length,
+            data_length,
+        }) => response
+            .get(header_length..header_length + data_length)
+            .ok_or_else(|| {
+                ScpiError::Parse(format!(
+                    "Block announces {} bytes but only {} arrived",
+                    data_length,
+                    response.len() - header_length
+                ))
+            }),
+        Some(BlockHeader::Indefinite) => {
+            let data: &[u8] = &response[2..];
+            Ok(data.strip_suffix(b"\n").unwrap_or(data))
+        }
+        None => Err(ScpiError::Parse("Block response is truncated".to_string())),
+    }
+}
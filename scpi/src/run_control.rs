@@ -0,0 +1,238 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Stop handles, limits and statistics for the long-running repeat and duty-cycle loops.
+
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use crate::timing::{TimingReport, TimingStrategy};
+
+/// Cloneable flag that asks a running loop to stop after its current message.
+#[derive(Clone, Debug, Default)]
+pub struct StopHandle {
+    stopped: Arc<AtomicBool>,
+}
+
+impl StopHandle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_stopped(&self) -> bool {
+        self.stopped.load(Ordering::SeqCst)
+    }
+}
+
+/// Upper bounds for a loop. The default has none, so the loop only ends through its `StopHandle`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunLimits {
+    max_cycles: Option<usize>,
+    max_duration: Option<Duration>,
+}
+
+impl RunLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_max_cycles(mut self, max_cycles: Option<usize>) -> Self {
+        self.max_cycles = max_cycles;
+        self
+    }
+
+    pub fn with_max_duration(mut self, max_duration: Option<Duration>) -> Self {
+        self.max_duration = max_duration;
+        self
+    }
+
+    pub fn get_max_cycles(&self) -> Option<usize> {
+        self.max_cycles
+    }
+
+    pub fn get_max_duration(&self) -> Option<Duration> {
+        self.max_duration
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StopReason {
+    Stopped,
+    MaxCycles,
+    MaxDuration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunStatistics {
+    cycles: usize,
+    messages_sent: usize,
+    bytes_sent: usize,
+    elapsed: Duration,
+    stop_reason: StopReason,
+    timing: TimingReport,
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl RunStatistics {
+    /// Number of fully completed cycles; a repeat loop counts one message as one cycle.
+    pub fn get_cycles(&self) -> usize {
+        self.cycles
+    }
+
+    pub fn get_messages_sent(&self) -> usize {
+        self.messages_sent
+    }
+
+    pub fn get_bytes_sent(&self) -> usize {
+        self.bytes_sent
+    }
+
+    pub fn get_elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn get_stop_reason(&self) -> StopReason {
+        self.stop_reason
+    }
+
+    pub fn get_timing(&self) -> &TimingReport {
+        &self.timing
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                           Run Tracker                                          */
+/* ********************************************************************************************** */
+
+/// Bookkeeping shared by the loops in `Messenger`.
+pub(crate) struct RunTracker {
+    start: Instant,
+    limits: RunLimits,
+    stop: StopHandle,
+    strategy: TimingStrategy,
+    // Where the schedule currently stands; only advanced by `TimingStrategy::AbsoluteDeadline`.
+    schedule: Instant,
+    last_cycle_start: Option<Instant>,
+    timing: TimingReport,
+    cycles: usize,
+    messages_sent: usize,
+    bytes_sent: usize,
+}
+
+impl RunTracker {
+    pub(crate) fn start(limits: &RunLimits, stop: &StopHandle, strategy: TimingStrategy) -> Self {
+        let start: Instant = Instant::now();
+        Self {
+            start,
+            limits: *limits,
+            stop: stop.clone(),
+            strategy,
+            schedule: start,
+            last_cycle_start: None,
+            timing: TimingReport::default(),
+            cycles: 0,
+            messages_sent: 0,
+            bytes_sent: 0,
+        }
+    }
+
+    /// Marks the start of a cycle, measuring the period since the previous one.
+    pub(crate) fn begin_cycle(&mut self) {
+        let now: Instant = Instant::now();
+        if let Some(x) = self.last_cycle_start {
+            self.timing.record_period(now - x);
+        }
+        self.last_cycle_start = Some(now);
+    }
+
+    pub(crate) fn record_message(&mut self, bytes: usize) {
+        self.messages_sent += 1;
+        self.bytes_sent += bytes;
+    }
+
+    pub(crate) fn finish_cycle(&mut self) {
+        self.cycles += 1;
+    }
+
+    /// Why the loop should end now, if it should.
+    pub(crate) fn stop_reason(&self) -> Option<StopReason> {
+        if self.stop.is_stopped() {
+            return Some(StopReason::Stopped);
+        }
+
+        if self.limits.max_cycles.is_some_and(|x| self.cycles >= x) {
+            return Some(StopReason::MaxCycles);
+        }
+
+        match self.limits.max_duration {
+            Some(x) if self.start.elapsed() >= x => Some(StopReason::MaxDuration),
+            _ => None,
+        }
+    }
+
+    /// Holds after a message sent at `sent_at` according to the timing strategy, returning early if
+    /// the run has to stop.
+    pub(crate) fn hold(&mut self, sent_at: Instant, hold: Duration) -> Option<StopReason> {
+        let deadline: Instant = match self.strategy {
+            TimingStrategy::AbsoluteDeadline { .. } => {
+                self.schedule += hold;
+                self.schedule
+            }
+            _ => sent_at + hold,
+        };
+
+        if !hold.is_zero() && Instant::now() > deadline {
+            self.timing.record_missed_deadline();
+        }
+
+        let stop: &StopHandle = &self.stop;
+        let max_duration: Option<Duration> = self.limits.max_duration;
+        let start: Instant = self.start;
+        self.strategy.wait_until(deadline, || {
+            if stop.is_stopped() {
+                return Some(StopReason::Stopped);
+            }
+
+            match max_duration {
+                Some(x) if start.elapsed() >= x => Some(StopReason::MaxDuration),
+                _ => None,
+            }
+        })
+    }
+
+    pub(crate) fn into_statistics(self, stop_reason: StopReason) -> RunStatistics {
+        RunStatistics {
+            cycles: self.cycles,
+            messages_sent: self.messages_sent,
+            bytes_sent: self.bytes_sent,
+            elapsed: self.start.elapsed(),
+            stop_reason,
+            timing: self.timing,
+        }
+    }
+}
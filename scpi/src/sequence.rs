@@ -0,0 +1,202 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! Multi-step message sequences: a generalization of `DutyCycleMessage` to any number of
+//! messages, each held for its own time, with nested repeat loops.
+//!
+//! Sequences are built with `with_step` / `with_loop`, or parsed from text where each line is
+//! `<hold in microseconds> <message>`, `repeat <count>` opens a loop, `end` closes it, and a line
+//! starting with `#` is a comment:
+//!
+//! ```text
+//! 1000 OUTP ON
+//! repeat 3
+//!     500 VOLT 1
+//!     500 VOLT 2
+//! end
+//! 2000 OUTP OFF
+//! ```
+
+use std::{path::Path, str::FromStr, time::Duration};
+
+use crate::duty_cycle::DutyCycleMessage;
+use crate::error::ScpiError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SequenceStep {
+    /// Sends `message`, then waits until `hold` has passed since the send started.
+    Message { message: String, hold: Duration },
+    /// Runs `sequence` `repetitions` times in a row.
+    Loop {
+        sequence: Sequence,
+        repetitions: usize,
+    },
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sequence {
+    steps: Vec<SequenceStep>,
+}
+
+impl Sequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_step(mut self, message: &str, microseconds: u64) -> Self {
+        self.steps.push(SequenceStep::Message {
+            message: message.to_string(),
+            hold: Duration::from_micros(microseconds),
+        });
+        self
+    }
+
+    /// Shorthand for a loop around a single step.
+    pub fn with_repeated_step(self, message: &str, microseconds: u64, repetitions: usize) -> Self {
+        self.with_loop(
+            Sequence::new().with_step(message, microseconds),
+            repetitions,
+        )
+    }
+
+    pub fn with_loop(mut self, sequence: Sequence, repetitions: usize) -> Self {
+        self.steps.push(SequenceStep::Loop {
+            sequence,
+            repetitions,
+        });
+        self
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ScpiError> {
+        std::fs::read_to_string(path)?.parse()
+    }
+
+    /// Number of messages one pass through the sequence sends.
+    pub fn message_count(&self) -> usize {
+        self.steps
+            .iter()
+            .map(|x| match x {
+                SequenceStep::Message { .. } => 1,
+                SequenceStep::Loop {
+                    sequence,
+                    repetitions,
+                } => sequence.message_count().saturating_mul(*repetitions),
+            })
+            .sum()
+    }
+
+    /// Total hold time of one pass through the sequence.
+    pub fn period(&self) -> Duration {
+        self.steps
+            .iter()
+            .map(|x| match x {
+                SequenceStep::Message { hold, .. } => *hold,
+                SequenceStep::Loop {
+                    sequence,
+                    repetitions,
+                } => sequence
+                    .period()
+                    .saturating_mul(u32::try_from(*repetitions).unwrap_or(u32::MAX)),
+            })
+            .sum()
+    }
+}
+
+impl From<&DutyCycleMessage<'_>> for Sequence {
+    fn from(message: &DutyCycleMessage<'_>) -> Self {
+        let (first_time, second_time): (u64, u64) = message.get_times();
+        let (first_message, second_message): (&str, &str) = message.get_messages();
+
+        Sequence::new()
+            .with_step(first_message, first_time)
+            .with_step(second_message, second_time)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl Sequence {
+    pub fn get_steps(&self) -> &[SequenceStep] {
+        &self.steps
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                          File Parsing                                          */
+/* ********************************************************************************************** */
+
+impl FromStr for Sequence {
+    type Err = ScpiError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        // Each open `repeat` pushes the steps collected so far along with its count.
+        let mut open_loops: Vec<(Sequence, usize)> = Vec::new();
+        let mut current: Sequence = Sequence::new();
+
+        for (index, raw_line) in text.lines().enumerate() {
+            let line_number: usize = index + 1;
+            // `#` only starts a comment at the beginning of a line, since messages use it for
+            // non-decimal numerics (`#H0100`) and block data (`#14abcd`).
+            let line: &str = raw_line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (keyword, rest): (&str, &str) = match line.split_once(char::is_whitespace) {
+                Some((x, y)) => (x, y.trim()),
+                None => (line, ""),
+            };
+
+            match keyword.to_ascii_lowercase().as_str() {
+                "repeat" => {
+                    let repetitions: usize = rest.parse().map_err(|_| {
+                        parse_error(line_number, &format!("invalid repeat count '{}'", rest))
+                    })?;
+                    open_loops.push((current, repetitions));
+                    current = Sequence::new();
+                }
+                "end" => {
+                    let (parent, repetitions): (Sequence, usize) = open_loops
+                        .pop()
+                        .ok_or_else(|| parse_error(line_number, "'end' without 'repeat'"))?;
+                    current = parent.with_loop(current, repetitions);
+                }
+                _ => {
+                    let microseconds: u64 = keyword.parse().map_err(|_| {
+                        parse_error(line_number, &format!("invalid hold time '{}'", keyword))
+                    })?;
+                    if rest.is_empty() {
+                        return Err(parse_error(line_number, "missing message"));
+                    }
+                    current = current.with_step(rest, microseconds);
+                }
+            }
+        }
+
+        match open_loops.is_empty() {
+            true => Ok(current),
+            false => Err(ScpiError::Parse(
+                "sequence ended inside a 'repeat' block".to_string(),
+            )),
+        }
+    }
+}
+
+fn parse_error(line_number: usize, message: &str) -> ScpiError {
+    ScpiError::Parse(format!("sequence line {}: {}", line_number, message))
+}
@@ -0,0 +1,487 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! A programmable instrument for running whole test programs without hardware. Headers are
+//! declared with the `CommandTree` syntax, e.g. `[SOURce]:VOLTage[:LEVel]`, together with the
+//! parameters they take and a handler that reads or changes the simulated device state.
+//!
+//! Messages are then parsed as an instrument would: compound messages and relative headers, short
+//! and long forms, and a `-113,"Undefined header"` error queue entry for anything not declared.
+//! The IEEE 488.2 common commands and `SYSTem:ERRor?` are built in, and `*RST` restores the state
+//! the simulator was created with. Serve it with `MockInstrument` to talk to it over TCP or UDP.
+
+use std::cmp::Ordering;
+
+use crate::command_tree::{CommandTree, ResolvedUnit};
+use crate::common::{Identification, StandardEventStatus};
+use crate::error::ScpiError;
+use crate::error_queue::InstrumentError;
+use crate::mock::Responder;
+use crate::response::{
+    parse_boolean, parse_channel_list, parse_float, parse_integer, parse_string, parse_value,
+    split_list, ResponseValue,
+};
+use crate::status::StatusByte;
+
+const ERROR_QUEUE_SIZE: usize = 20;
+
+/// How the simulator parses one parameter before handing it to a handler.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParameterType {
+    Integer,
+    Float,
+    /// `ON`, `OFF` or a number, passed on as `ResponseValue::Integer` 1 or 0.
+    Boolean,
+    /// Character data such as `BUS` or `IMM`.
+    Character,
+    /// A quoted string.
+    String,
+    ChannelList,
+    /// Whatever the parameter looks like.
+    Any,
+}
+
+/// The parameters and numeric suffixes of one command or query, as passed to a handler.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invocation {
+    parameters: Vec<ResponseValue>,
+    suffixes: Vec<u32>,
+}
+
+impl Invocation {
+    pub fn get_integer(&self, index: usize) -> Result<i64, InstrumentError> {
+        match self.parameters.get(index) {
+            Some(ResponseValue::Integer(x)) => Ok(*x),
+            _ => Err(data_type_error()),
+        }
+    }
+
+    pub fn get_float(&self, index: usize) -> Result<f64, InstrumentError> {
+        match self.parameters.get(index) {
+            Some(ResponseValue::Float(x)) => Ok(*x),
+            Some(ResponseValue::Integer(x)) => Ok(*x as f64),
+            _ => Err(data_type_error()),
+        }
+    }
+
+    pub fn get_bool(&self, index: usize) -> Result<bool, InstrumentError> {
+        Ok(self.get_integer(index)? != 0)
+    }
+
+    /// The text of a string or character data parameter.
+    pub fn get_text(&self, index: usize) -> Result<&str, InstrumentError> {
+        match self.parameters.get(index) {
+            Some(ResponseValue::String(x) | ResponseValue::Character(x)) => Ok(x),
+            _ => Err(data_type_error()),
+        }
+    }
+
+    /// The numeric suffix of the `index`th mnemonic that accepts one, 1 if it was left out.
+    pub fn get_suffix(&self, index: usize) -> u32 {
+        self.suffixes.get(index).copied().unwrap_or(1)
+    }
+}
+
+type CommandHandler<S> = Box<dyn FnMut(&mut S, &Invocation) -> Result<(), InstrumentError> + Send>;
+type QueryHandler<S> =
+    Box<dyn FnMut(&mut S, &Invocation) -> Result<String, InstrumentError> + Send>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Builtin {
+    Identify,
+    Reset,
+    ClearStatus,
+    SetEventStatusEnable,
+    EventStatusEnable,
+    EventStatus,
+    SetServiceRequestEnable,
+    ServiceRequestEnable,
+    StatusByte,
+    OperationComplete,
+    OperationCompleteQuery,
+    Wait,
+    SelfTest,
+    NextError,
+    ErrorCount,
+}
+
+enum Action<S> {
+    Builtin(Builtin),
+    Command(CommandHandler<S>),
+    Query(QueryHandler<S>),
+}
+
+struct Handler<S> {
+    parameters: Vec<ParameterType>,
+    action: Action<S>,
+}
+
+/// The handlers of one declared header, in the order the headers were added to the tree.
+struct Entry<S> {
+    definition: String,
+    command: Option<Handler<S>>,
+    query: Option<Handler<S>>,
+}
+
+/// A simulated instrument holding a device state `S`.
+pub struct Simulator<S> {
+    tree: CommandTree,
+    entries: Vec<Entry<S>>,
+    initial_state: S,
+    state: S,
+    identification: Identification,
+    errors: Vec<InstrumentError>,
+    event_status: u8,
+    event_status_enable: u8,
+    service_request_enable: u8,
+}
+
+impl<S: Clone + Send> Simulator<S> {
+    pub fn new(identification: Identification, state: S) -> Self {
+        let mut simulator: Self = Self {
+            tree: CommandTree::new(),
+            entries: Vec::new(),
+            initial_state: state.clone(),
+            state,
+            identification,
+            errors: Vec::new(),
+            event_status: 0,
+            event_status_enable: 0,
+            service_request_enable: 0,
+        };
+
+        let builtins: [(&str, bool, &[ParameterType], Builtin); 15] = [
+            ("*IDN", true, &[], Builtin::Identify),
+            ("*RST", false, &[], Builtin::Reset),
+            ("*CLS", false, &[], Builtin::ClearStatus),
+            (
+                "*ESE",
+                false,
+                &[ParameterType::Integer],
+                Builtin::SetEventStatusEnable,
+            ),
+            ("*ESE", true, &[], Builtin::EventStatusEnable),
+            ("*ESR", true, &[], Builtin::EventStatus),
+            (
+                "*SRE",
+                false,
+                &[ParameterType::Integer],
+                Builtin::SetServiceRequestEnable,
+            ),
+            ("*SRE", true, &[], Builtin::ServiceRequestEnable),
+            ("*STB", true, &[], Builtin::StatusByte),
+            ("*OPC", false, &[], Builtin::OperationComplete),
+            ("*OPC", true, &[], Builtin::OperationCompleteQuery),
+            ("*WAI", false, &[], Builtin::Wait),
+            ("*TST", true, &[], Builtin::SelfTest),
+            ("SYSTem:ERRor[:NEXT]", true, &[], Builtin::NextError),
+            ("SYSTem:ERRor:COUNt", true, &[], Builtin::ErrorCount),
+        ];
+        for (definition, query, parameters, builtin) in builtins {
+            let handler: Handler<S> = Handler {
+                parameters: parameters.to_vec(),
+                action: Action::Builtin(builtin),
+            };
+            // The definitions above are fixed and known to be valid.
+            if let Ok(entry) = simulator.entry(definition) {
+                match query {
+                    true => entry.query = Some(handler),
+                    false => entry.command = Some(handler),
+                }
+            }
+        }
+
+        simulator
+    }
+
+    /// Declares the command form of `definition`, e.g. `[SOURce]:VOLTage[:LEVel]` with one
+    /// `ParameterType::Float`. Declaring a header again, built-in ones included, replaces its
+    /// handler.
+    pub fn with_command<F>(
+        mut self,
+        definition: &str,
+        parameters: &[ParameterType],
+        handler: F,
+    ) -> Result<Self, ScpiError>
+    where
+        F: FnMut(&mut S, &Invocation) -> Result<(), InstrumentError> + Send + 'static,
+    {
+        self.entry(definition)?.command = Some(Handler {
+            parameters: parameters.to_vec(),
+            action: Action::Command(Box::new(handler)),
+        });
+        Ok(self)
+    }
+
+    /// Declares the query form of `definition`. The handler returns the response as sent.
+    pub fn with_query<F>(
+        mut self,
+        definition: &str,
+        parameters: &[ParameterType],
+        handler: F,
+    ) -> Result<Self, ScpiError>
+    where
+        F: FnMut(&mut S, &Invocation) -> Result<String, InstrumentError> + Send + 'static,
+    {
+        self.entry(definition)?.query = Some(Handler {
+            parameters: parameters.to_vec(),
+            action: Action::Query(Box::new(handler)),
+        });
+        Ok(self)
+    }
+
+    /// Executes one message and returns the responses of its queries joined with `;`, or `None`
+    /// if it had none. Execution stops at the first unit that fails, which queues an error.
+    pub fn process(&mut self, message: &str) -> Option<String> {
+        let mut responses: Vec<String> = Vec::new();
+
+        for unit in self.tree.resolve(message) {
+            let result: Result<Option<String>, InstrumentError> = match unit {
+                Ok(x) => self.execute(&x),
+                Err(_) => Err(undefined_header()),
+            };
+
+            match result {
+                Ok(Some(x)) => responses.push(x),
+                Ok(None) => (),
+                Err(x) => {
+                    self.push_error(x);
+                    break;
+                }
+            }
+        }
+
+        match responses.is_empty() {
+            true => None,
+            false => Some(responses.join(";")),
+        }
+    }
+
+    /// Queues `error` as the instrument would, setting the matching `*ESR?` bit. A full queue
+    /// replaces its last entry with `-350,"Queue overflow"`.
+    pub fn push_error(&mut self, error: InstrumentError) {
+        self.event_status |= match error.get_code() {
+            -199..=-100 => StandardEventStatus::COMMAND_ERROR,
+            -299..=-200 => StandardEventStatus::EXECUTION_ERROR,
+            -499..=-400 => StandardEventStatus::QUERY_ERROR,
+            _ => StandardEventStatus::DEVICE_DEPENDENT_ERROR,
+        };
+
+        match self.errors.len() >= ERROR_QUEUE_SIZE {
+            true => {
+                self.errors.pop();
+                self.errors
+                    .push(InstrumentError::new(-350, "Queue overflow"));
+            }
+            false => self.errors.push(error),
+        }
+    }
+
+    /// The status byte `*STB?` would report right now.
+    pub fn get_status_byte(&self) -> StatusByte {
+        let mut bits: u8 = 0;
+        if !self.errors.is_empty() {
+            bits |= StatusByte::ERROR_QUEUE;
+        }
+        if self.event_status & self.event_status_enable != 0 {
+            bits |= StatusByte::EVENT_SUMMARY;
+        }
+        if bits & self.service_request_enable & !StatusByte::REQUEST_SERVICE != 0 {
+            bits |= StatusByte::REQUEST_SERVICE;
+        }
+
+        StatusByte::new(bits)
+    }
+
+    fn entry(&mut self, definition: &str) -> Result<&mut Entry<S>, ScpiError> {
+        let definition: &str = definition.trim().trim_end_matches('?');
+        let index: usize = match self.entries.iter().position(|x| x.definition == definition) {
+            Some(x) => x,
+            None => {
+                self.tree = std::mem::take(&mut self.tree).with_header(definition)?;
+                self.entries.push(Entry {
+                    definition: definition.to_string(),
+                    command: None,
+                    query: None,
+                });
+                self.entries.len() - 1
+            }
+        };
+
+        Ok(&mut self.entries[index])
+    }
+
+    fn execute(&mut self, unit: &ResolvedUnit) -> Result<Option<String>, InstrumentError> {
+        let suffixes: Vec<u32> = self.tree.get_suffixes(unit);
+        let entry: &mut Entry<S> = &mut self.entries[unit.header];
+        let handler: &mut Handler<S> = match unit.query {
+            true => entry.query.as_mut(),
+            false => entry.command.as_mut(),
+        }
+        .ok_or_else(undefined_header)?;
+
+        let invocation: Invocation = Invocation {
+            parameters: parse_parameters(unit.parameters, &handler.parameters)?,
+            suffixes,
+        };
+        let builtin: Builtin = match &mut handler.action {
+            Action::Command(x) => return x(&mut self.state, &invocation).map(|_| None),
+            Action::Query(x) => return x(&mut self.state, &invocation).map(Some),
+            Action::Builtin(x) => *x,
+        };
+
+        self.run_builtin(builtin, &invocation)
+    }
+
+    fn run_builtin(
+        &mut self,
+        builtin: Builtin,
+        invocation: &Invocation,
+    ) -> Result<Option<String>, InstrumentError> {
+        let response: String = match builtin {
+            Builtin::Identify => self.identification.to_string(),
+            Builtin::Reset => {
+                self.state = self.initial_state.clone();
+                return Ok(None);
+            }
+            Builtin::ClearStatus => {
+                self.errors.clear();
+                self.event_status = 0;
+                return Ok(None);
+            }
+            Builtin::SetEventStatusEnable => {
+                self.event_status_enable = register_parameter(invocation)?;
+                return Ok(None);
+            }
+            Builtin::EventStatusEnable => self.event_status_enable.to_string(),
+            Builtin::EventStatus => std::mem::take(&mut self.event_status).to_string(),
+            Builtin::SetServiceRequestEnable => {
+                self.service_request_enable = register_parameter(invocation)?;
+                return Ok(None);
+            }
+            Builtin::ServiceRequestEnable => self.service_request_enable.to_string(),
+            Builtin::StatusByte => self.get_status_byte().get_bits().to_string(),
+            Builtin::OperationComplete => {
+                self.event_status |= StandardEventStatus::OPERATION_COMPLETE;
+                return Ok(None);
+            }
+            Builtin::OperationCompleteQuery => "1".to_string(),
+            Builtin::Wait => return Ok(None),
+            Builtin::SelfTest => "0".to_string(),
+            Builtin::NextError => match self.errors.is_empty() {
+                true => InstrumentError::new(0, "No error").to_string(),
+                false => self.errors.remove(0).to_string(),
+            },
+            Builtin::ErrorCount => self.errors.len().to_string(),
+        };
+
+        Ok(Some(response))
+    }
+}
+
+impl<S: Clone + Send> Responder for Simulator<S> {
+    fn respond(&mut self, message: &str) -> Option<String> {
+        self.process(message)
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl Invocation {
+    pub fn get_parameters(&self) -> &[ResponseValue] {
+        &self.parameters
+    }
+
+    pub fn get_suffixes(&self) -> &[u32] {
+        &self.suffixes
+    }
+}
+
+impl<S> Simulator<S> {
+    pub fn get_state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn get_state_mut(&mut self) -> &mut S {
+        &mut self.state
+    }
+
+    pub fn get_identification(&self) -> &Identification {
+        &self.identification
+    }
+
+    /// Errors not yet read with `SYSTem:ERRor?`, oldest first.
+    pub fn get_errors(&self) -> &[InstrumentError] {
+        &self.errors
+    }
+}
+
+fn parse_parameters(
+    text: &str,
+    types: &[ParameterType],
+) -> Result<Vec<ResponseValue>, InstrumentError> {
+    let text: &str = text.trim();
+    let values: Vec<&str> = match text.is_empty() {
+        true => Vec::new(),
+        false => split_list(text).map_err(|_| data_type_error())?,
+    };
+
+    match values.len().cmp(&types.len()) {
+        Ordering::Less => return Err(InstrumentError::new(-109, "Missing parameter")),
+        Ordering::Greater => return Err(InstrumentError::new(-108, "Parameter not allowed")),
+        Ordering::Equal => (),
+    }
+
+    values
+        .iter()
+        .zip(types)
+        .map(|(value, kind)| parse_parameter(value, *kind).map_err(|_| data_type_error()))
+        .collect()
+}
+
+fn parse_parameter(value: &str, kind: ParameterType) -> Result<ResponseValue, ScpiError> {
+    let value: &str = value.trim();
+    match kind {
+        ParameterType::Integer => parse_integer(value).map(ResponseValue::Integer),
+        ParameterType::Float => parse_float(value).map(ResponseValue::Float),
+        ParameterType::Boolean => parse_boolean(value).map(|x| ResponseValue::Integer(x as i64)),
+        ParameterType::Character => match parse_value(value)? {
+            ResponseValue::Character(x) => Ok(ResponseValue::Character(x)),
+            _ => Err(ScpiError::Parse(format!(
+                "'{}' is not character data",
+                value
+            ))),
+        },
+        ParameterType::String => parse_string(value).map(ResponseValue::String),
+        ParameterType::ChannelList => parse_channel_list(value).map(ResponseValue::ChannelList),
+        ParameterType::Any => parse_value(value),
+    }
+}
+
+fn register_parameter(invocation: &Invocation) -> Result<u8, InstrumentError> {
+    u8::try_from(invocation.get_integer(0)?)
+        .map_err(|_| InstrumentError::new(-222, "Data out of range"))
+}
+
+fn undefined_header() -> InstrumentError {
+    InstrumentError::new(-113, "Undefined header")
+}
+
+fn data_type_error() -> InstrumentError {
+    InstrumentError::new(-104, "Data type error")
+}
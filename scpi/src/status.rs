@@ -0,0 +1,224 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! The SCPI status system: the status byte and the OPERation and QUEStionable register groups.
+
+use crate::common::parse_register;
+use crate::error::ScpiError;
+use crate::response::{parse_integer, FromResponse};
+
+pub const OPERATION_CALIBRATING: u16 = 1 << 0;
+pub const OPERATION_SETTLING: u16 = 1 << 1;
+pub const OPERATION_RANGING: u16 = 1 << 2;
+pub const OPERATION_SWEEPING: u16 = 1 << 3;
+pub const OPERATION_MEASURING: u16 = 1 << 4;
+pub const OPERATION_WAITING_FOR_TRIGGER: u16 = 1 << 5;
+pub const OPERATION_WAITING_FOR_ARM: u16 = 1 << 6;
+pub const OPERATION_CORRECTING: u16 = 1 << 7;
+pub const OPERATION_INSTRUMENT_SUMMARY: u16 = 1 << 13;
+pub const OPERATION_PROGRAM_RUNNING: u16 = 1 << 14;
+
+pub const QUESTIONABLE_VOLTAGE: u16 = 1 << 0;
+pub const QUESTIONABLE_CURRENT: u16 = 1 << 1;
+pub const QUESTIONABLE_TIME: u16 = 1 << 2;
+pub const QUESTIONABLE_POWER: u16 = 1 << 3;
+pub const QUESTIONABLE_TEMPERATURE: u16 = 1 << 4;
+pub const QUESTIONABLE_FREQUENCY: u16 = 1 << 5;
+pub const QUESTIONABLE_PHASE: u16 = 1 << 6;
+pub const QUESTIONABLE_MODULATION: u16 = 1 << 7;
+pub const QUESTIONABLE_CALIBRATION: u16 = 1 << 8;
+pub const QUESTIONABLE_INSTRUMENT_SUMMARY: u16 = 1 << 13;
+pub const QUESTIONABLE_COMMAND_WARNING: u16 = 1 << 14;
+
+/// The status byte, as read with `*STB?` or a serial poll.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StatusByte {
+    bits: u8,
+}
+
+impl StatusByte {
+    /// Set while the error queue is not empty.
+    pub const ERROR_QUEUE: u8 = 1 << 2;
+    pub const QUESTIONABLE_SUMMARY: u8 = 1 << 3;
+    pub const MESSAGE_AVAILABLE: u8 = 1 << 4;
+    /// Set while an enabled `*ESR?` bit is set.
+    pub const EVENT_SUMMARY: u8 = 1 << 5;
+    /// Master Summary Status for `*STB?`, Request Service for a serial poll.
+    pub const REQUEST_SERVICE: u8 = 1 << 6;
+    pub const OPERATION_SUMMARY: u8 = 1 << 7;
+
+    pub fn new(bits: u8) -> Self {
+        Self { bits }
+    }
+
+    pub fn is_set(&self, mask: u8) -> bool {
+        self.bits & mask != 0
+    }
+
+    pub fn error_queue(&self) -> bool {
+        self.is_set(Self::ERROR_QUEUE)
+    }
+
+    pub fn questionable_summary(&self) -> bool {
+        self.is_set(Self::QUESTIONABLE_SUMMARY)
+    }
+
+    pub fn message_available(&self) -> bool {
+        self.is_set(Self::MESSAGE_AVAILABLE)
+    }
+
+    pub fn event_summary(&self) -> bool {
+        self.is_set(Self::EVENT_SUMMARY)
+    }
+
+    pub fn request_service(&self) -> bool {
+        self.is_set(Self::REQUEST_SERVICE)
+    }
+
+    pub fn operation_summary(&self) -> bool {
+        self.is_set(Self::OPERATION_SUMMARY)
+    }
+}
+
+impl FromResponse for StatusByte {
+    fn from_response(response: &str) -> Result<Self, ScpiError> {
+        Ok(Self::new(parse_register(response)?))
+    }
+}
+
+/// The SCPI register groups that summarize into the status byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StatusRegisterGroup {
+    Operation,
+    Questionable,
+}
+
+impl StatusRegisterGroup {
+    /// The command node of the group, e.g. `STAT:OPER`.
+    pub fn get_node(&self) -> &'static str {
+        match self {
+            Self::Operation => "STAT:OPER",
+            Self::Questionable => "STAT:QUES",
+        }
+    }
+
+    /// The status byte bit this group sets while an enabled event is pending.
+    pub fn get_summary_bit(&self) -> u8 {
+        match self {
+            Self::Operation => StatusByte::OPERATION_SUMMARY,
+            Self::Questionable => StatusByte::QUESTIONABLE_SUMMARY,
+        }
+    }
+}
+
+/// A snapshot of every register in one group. The condition register follows the instrument
+/// state; a condition change sets the matching event bit if the transition filter lets it through,
+/// and enabled event bits raise the group's summary bit in the status byte.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct StatusRegister {
+    group: StatusRegisterGroup,
+    condition: u16,
+    event: u16,
+    enable: u16,
+    positive_transition: u16,
+    negative_transition: u16,
+}
+
+impl StatusRegister {
+    pub fn new(group: StatusRegisterGroup) -> Self {
+        Self {
+            group,
+            condition: 0,
+            event: 0,
+            enable: 0,
+            positive_transition: 0,
+            negative_transition: 0,
+        }
+    }
+
+    pub fn with_condition(mut self, condition: u16) -> Self {
+        self.condition = condition;
+        self
+    }
+
+    pub fn with_event(mut self, event: u16) -> Self {
+        self.event = event;
+        self
+    }
+
+    pub fn with_enable(mut self, enable: u16) -> Self {
+        self.enable = enable;
+        self
+    }
+
+    pub fn with_transitions(mut self, positive: u16, negative: u16) -> Self {
+        self.positive_transition = positive;
+        self.negative_transition = negative;
+        self
+    }
+
+    /// Event bits that currently contribute to the summary bit.
+    pub fn pending_events(&self) -> u16 {
+        self.event & self.enable
+    }
+
+    pub fn summary(&self) -> bool {
+        self.pending_events() != 0
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl StatusByte {
+    pub fn get_bits(&self) -> u8 {
+        self.bits
+    }
+}
+
+impl StatusRegister {
+    pub fn get_group(&self) -> StatusRegisterGroup {
+        self.group
+    }
+
+    pub fn get_condition(&self) -> u16 {
+        self.condition
+    }
+
+    pub fn get_event(&self) -> u16 {
+        self.event
+    }
+
+    pub fn get_enable(&self) -> u16 {
+        self.enable
+    }
+
+    pub fn get_positive_transition(&self) -> u16 {
+        self.positive_transition
+    }
+
+    pub fn get_negative_transition(&self) -> u16 {
+        self.negative_transition
+    }
+}
+
+/// Parses a 16-bit register value such as a `STAT:OPER:COND?` response.
+pub(crate) fn parse_register16(response: &str) -> Result<u16, ScpiError> {
+    let value: i64 = parse_integer(response)?;
+    u16::try_from(value)
+        .map_err(|_| ScpiError::Parse(format!("Register value {} does not fit in 16 bits", value)))
+}
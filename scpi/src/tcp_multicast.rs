@@ -0,0 +1,271 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! `NetworkMode::TcpMulticast`: one message fanned out over TCP to a group of instruments.
+
+use std::{io::Error, net::SocketAddr, time::Duration};
+
+use crate::connection_options::ConnectionOptions;
+use crate::error::ScpiError;
+use crate::messenger::Messenger;
+use crate::networking::TcpTransport;
+use crate::transport::Transport;
+
+/// The outcome of one group operation on one instrument.
+#[derive(Debug)]
+pub struct InstrumentResult<T> {
+    address: SocketAddr,
+    result: Result<T, ScpiError>,
+}
+
+impl<T> InstrumentResult<T> {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    pub fn into_result(self) -> Result<T, ScpiError> {
+        self.result
+    }
+
+    pub fn get_address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn get_result(&self) -> &Result<T, ScpiError> {
+        &self.result
+    }
+}
+
+/// Holds a TCP connection to every instrument in a rack. Operations run on every member, one
+/// after another or on one thread per member, and report a result per instrument.
+pub struct TcpMulticastGroup {
+    members: Vec<(SocketAddr, Messenger)>,
+    parallel: bool,
+}
+
+impl TcpMulticastGroup {
+    /// Connects to every address. If any fail, the error is `ScpiError::Group` with an entry for
+    /// each unreachable instrument.
+    pub fn connect(
+        addresses: &[SocketAddr],
+        options: &ConnectionOptions,
+        parallel: bool,
+    ) -> Result<Self, ScpiError> {
+        let (group, failures) = Self::connect_reachable(addresses, options, parallel)?;
+        match failures.is_empty() {
+            true => Ok(group),
+            false => Err(ScpiError::Group(failures)),
+        }
+    }
+
+    /// Like `connect`, but keeps the instruments that could be reached and returns the error of
+    /// each one that could not.
+    pub fn connect_reachable(
+        addresses: &[SocketAddr],
+        options: &ConnectionOptions,
+        parallel: bool,
+    ) -> Result<(Self, Vec<(SocketAddr, ScpiError)>), ScpiError> {
+        if addresses.is_empty() {
+            return Err(ScpiError::InvalidArgument(
+                "A TCP multicast group needs at least one instrument".to_string(),
+            ));
+        }
+
+        let connect = |address: &SocketAddr| -> Result<Messenger, ScpiError> {
+            let transport: TcpTransport =
+                TcpTransport::connect(*address, options.get_connect_timeout())?;
+            Messenger::from_transport(Box::new(transport), options)
+        };
+
+        let results: Vec<Result<Messenger, ScpiError>> = match parallel {
+            true => std::thread::scope(|scope| {
+                let handles: Vec<_> = addresses
+                    .iter()
+                    .map(|address| scope.spawn(move || connect(address)))
+                    .collect();
+                handles.into_iter().map(join_member).collect()
+            }),
+            false => addresses.iter().map(connect).collect(),
+        };
+
+        let mut members: Vec<(SocketAddr, Messenger)> = Vec::with_capacity(addresses.len());
+        let mut failures: Vec<(SocketAddr, ScpiError)> = Vec::new();
+        for (address, result) in addresses.iter().zip(results) {
+            match result {
+                Ok(x) => members.push((*address, x)),
+                Err(e) => failures.push((*address, e)),
+            }
+        }
+
+        Ok((Self::from_messengers(members, parallel), failures))
+    }
+
+    /// Groups already open messengers, e.g. ones using a transport other than plain TCP.
+    pub fn from_messengers(members: Vec<(SocketAddr, Messenger)>, parallel: bool) -> Self {
+        Self { members, parallel }
+    }
+
+    pub fn set_parallel(&mut self, parallel: bool) {
+        self.parallel = parallel;
+    }
+
+    pub fn send_message(&mut self, message: &str) -> Vec<InstrumentResult<usize>> {
+        self.for_each_member(|messenger| messenger.send_message(message))
+    }
+
+    pub fn query(&mut self, message: &str) -> Vec<InstrumentResult<String>> {
+        self.for_each_member(|messenger| messenger.query(message))
+    }
+
+    /// Sends the whole list to each instrument; an instrument stops at its first failure.
+    pub fn send_list_of_messages(&mut self, messages: &[&str]) -> Vec<InstrumentResult<()>> {
+        self.for_each_member(|messenger| messenger.send_list_of_messages(messages))
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+
+    fn for_each_member<T, F>(&mut self, operation: F) -> Vec<InstrumentResult<T>>
+    where
+        T: Send,
+        F: Fn(&mut Messenger) -> Result<T, ScpiError> + Sync,
+    {
+        let operation: &F = &operation;
+        match self.parallel {
+            true => std::thread::scope(|scope| {
+                let handles: Vec<_> = self
+                    .members
+                    .iter_mut()
+                    .map(|(address, messenger)| {
+                        (*address, scope.spawn(move || operation(messenger)))
+                    })
+                    .collect();
+                handles
+                    .into_iter()
+                    .map(|(address, handle)| InstrumentResult {
+                        address,
+                        result: join_member(handle),
+                    })
+                    .collect()
+            }),
+            false => self
+                .members
+                .iter_mut()
+                .map(|(address, messenger)| InstrumentResult {
+                    address: *address,
+                    result: operation(messenger),
+                })
+                .collect(),
+        }
+    }
+
+    /* ****************************************************************************************** */
+    /*                                   Boilerplate Getters                                      */
+    /* ****************************************************************************************** */
+
+    pub fn get_parallel(&self) -> bool {
+        self.parallel
+    }
+
+    pub fn get_addresses(&self) -> Vec<SocketAddr> {
+        self.members.iter().map(|(address, _)| *address).collect()
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                      Fan-Out Transport                                         */
+/* ********************************************************************************************** */
+
+/// Lets a `Messenger` drive the whole group, which is how `NetworkMode::TcpMulticast` works: each
+/// write goes to every member. Reads are unsupported, since every instrument would answer.
+impl Transport for TcpMulticastGroup {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        into_group_result(
+            Vec::new(),
+            self.for_each_member(|messenger| messenger.get_transport_mut().write_bytes(bytes)),
+        )?;
+        Ok(bytes.len())
+    }
+
+    fn read_bytes(&mut self, _buffer: &mut [u8]) -> Result<usize, ScpiError> {
+        Err(ScpiError::Unsupported(
+            "Every instrument in a TCP multicast group answers; use TcpMulticastGroup::query",
+        ))
+    }
+
+    fn set_timeout(
+        &mut self,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError> {
+        into_group_result(
+            Vec::new(),
+            self.for_each_member(|messenger| {
+                messenger
+                    .get_transport_mut()
+                    .set_timeout(read_timeout, write_timeout)
+            }),
+        )
+    }
+
+    fn flush(&mut self) -> Result<(), ScpiError> {
+        into_group_result(
+            Vec::new(),
+            self.for_each_member(|messenger| messenger.get_transport_mut().flush()),
+        )
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        into_group_result(Vec::new(), self.for_each_member(Messenger::clear))
+    }
+
+    fn can_read(&self) -> bool {
+        false
+    }
+}
+
+/// Fails with `ScpiError::Group` listing `failures` and then every member whose operation failed.
+pub(crate) fn into_group_result<T>(
+    mut failures: Vec<(SocketAddr, ScpiError)>,
+    results: Vec<InstrumentResult<T>>,
+) -> Result<(), ScpiError> {
+    failures.extend(
+        results
+            .into_iter()
+            .filter_map(|x| x.result.err().map(|e| (x.address, e))),
+    );
+
+    match failures.is_empty() {
+        true => Ok(()),
+        false => Err(ScpiError::Group(failures)),
+    }
+}
+
+fn join_member<T>(
+    handle: std::thread::ScopedJoinHandle<'_, Result<T, ScpiError>>,
+) -> Result<T, ScpiError> {
+    match handle.join() {
+        Ok(x) => x,
+        Err(_) => Err(ScpiError::Connection(Error::other(
+            "Instrument worker thread panicked",
+        ))),
+    }
+}
@@ -0,0 +1,154 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! How the timed loops wait between messages, and how accurately they managed to.
+
+use std::time::{Duration, Instant};
+
+/// Default time before a deadline at which the sleeping strategies switch to spinning.
+pub const DEFAULT_SPIN_WINDOW: Duration = Duration::from_micros(200);
+
+/// Longest single sleep, so a stop request is still noticed promptly during long holds.
+const MAX_SLEEP: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TimingStrategy {
+    /// Busy-waits for the whole hold. Most precise, but keeps one core at 100%.
+    #[default]
+    Spin,
+    /// Sleeps until `spin_window` before the deadline, then busy-waits the rest.
+    Hybrid { spin_window: Duration },
+    /// Like `Hybrid`, but every deadline is computed from the start of the run instead of from the
+    /// previous send, so late sends do not push back the ones that follow.
+    AbsoluteDeadline { spin_window: Duration },
+}
+
+impl TimingStrategy {
+    pub fn hybrid() -> Self {
+        Self::Hybrid {
+            spin_window: DEFAULT_SPIN_WINDOW,
+        }
+    }
+
+    pub fn absolute_deadline() -> Self {
+        Self::AbsoluteDeadline {
+            spin_window: DEFAULT_SPIN_WINDOW,
+        }
+    }
+
+    /// Waits until `deadline`, returning early with whatever `should_stop` reports once it reports
+    /// something.
+    pub(crate) fn wait_until<R, F: FnMut() -> Option<R>>(
+        &self,
+        deadline: Instant,
+        mut should_stop: F,
+    ) -> Option<R> {
+        let spin_window: Duration = match self {
+            Self::Spin => Duration::ZERO,
+            Self::Hybrid { spin_window } | Self::AbsoluteDeadline { spin_window } => *spin_window,
+        };
+
+        loop {
+            if let Some(x) = should_stop() {
+                return Some(x);
+            }
+
+            let remaining: Duration = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return None;
+            }
+
+            match *self != Self::Spin && remaining > spin_window {
+                true => std::thread::sleep((remaining - spin_window).min(MAX_SLEEP)),
+                false => std::hint::spin_loop(),
+            }
+        }
+    }
+}
+
+/// Achieved cycle periods and deadline misses of a run.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimingReport {
+    periods: usize,
+    mean_seconds: f64,
+    // Sum of squared deviations from the mean (Welford), so long runs need no sample buffer.
+    squared_deviations: f64,
+    min: Duration,
+    max: Duration,
+    missed_deadlines: usize,
+}
+
+impl TimingReport {
+    pub(crate) fn record_period(&mut self, period: Duration) {
+        match self.periods {
+            0 => {
+                self.min = period;
+                self.max = period;
+            }
+            _ => {
+                self.min = self.min.min(period);
+                self.max = self.max.max(period);
+            }
+        }
+
+        self.periods += 1;
+        let seconds: f64 = period.as_secs_f64();
+        let delta: f64 = seconds - self.mean_seconds;
+        self.mean_seconds += delta / self.periods as f64;
+        self.squared_deviations += delta * (seconds - self.mean_seconds);
+    }
+
+    pub(crate) fn record_missed_deadline(&mut self) {
+        self.missed_deadlines += 1;
+    }
+}
+
+/* ********************************************************************************************** */
+/*                                       Boilerplate Getters                                      */
+/* ********************************************************************************************** */
+
+impl TimingReport {
+    /// Number of measured periods, one fewer than the number of cycles started.
+    pub fn get_period_count(&self) -> usize {
+        self.periods
+    }
+
+    pub fn get_mean_period(&self) -> Duration {
+        Duration::from_secs_f64(self.mean_seconds)
+    }
+
+    pub fn get_min_period(&self) -> Duration {
+        self.min
+    }
+
+    pub fn get_max_period(&self) -> Duration {
+        self.max
+    }
+
+    /// Population standard deviation of the periods.
+    pub fn get_period_std_dev(&self) -> Duration {
+        match self.periods {
+            0 => Duration::ZERO,
+            x => Duration::from_secs_f64((self.squared_deviations / x as f64).sqrt()),
+        }
+    }
+
+    /// Number of holds whose deadline had already passed by the time the send before them
+    /// returned, i.e. sends that overran the time they were given. Zero-length holds never count.
+    pub fn get_missed_deadlines(&self) -> usize {
+        self.missed_deadlines
+    }
+}
@@ -0,0 +1,57 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+use std::time::Duration;
+
+use crate::error::ScpiError;
+
+/// A byte pipe to an instrument. `Messenger` handles terminators, framing and error checking on
+/// top of it, so an implementation only has to move raw bytes.
+pub trait Transport: Send {
+    /// Writes all of `bytes`, returning how many were sent.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError>;
+
+    /// Reads whatever is available into `buffer`, blocking up to the read timeout.
+    /// Returning `Ok(0)` means nothing arrived yet; a closed connection must be an error.
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<usize, ScpiError>;
+
+    /// Timeouts of `None` block indefinitely.
+    fn set_timeout(
+        &mut self,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError>;
+
+    fn flush(&mut self) -> Result<(), ScpiError>;
+
+    /// Discards pending input, or issues a device clear on protocols that have one.
+    fn clear(&mut self) -> Result<(), ScpiError>;
+
+    /// Whether responses can be read back. `Messenger` refuses queries and error checking on
+    /// write-only transports before sending anything.
+    fn can_read(&self) -> bool {
+        true
+    }
+
+    /// Blocks until the instrument requests service or `timeout` passes, returning whether it did.
+    /// Transports without a service request channel are `Unsupported`, which makes `Messenger`
+    /// poll the status byte instead.
+    fn wait_for_service_request(&mut self, _timeout: Duration) -> Result<bool, ScpiError> {
+        Err(ScpiError::Unsupported(
+            "Service requests are not signalled over this transport",
+        ))
+    }
+}
@@ -17,42 +17,3052 @@
 #[cfg(test)]
 mod tests {
     use std::{
-        net::{AddrParseError, IpAddr},
-        str::FromStr,
+        collections::VecDeque,
+        io::{BufRead, BufReader, Error, Read, Write},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket},
+        sync::{Arc, Mutex},
+        thread::JoinHandle,
+        time::{Duration, Instant},
     };
 
-    use crate::{networking::NetworkMode, send_repeated_scpi_message, send_scpi_message};
+    use rs232::SerialConnection;
+
+    use crate::{
+        block::{encode_definite_block, parse_block_header, BlockHeader, ByteOrder},
+        command::{ChannelList, Command, CompoundCommand, Parameter},
+        command_tree::{CommandTree, MnemonicForm},
+        common::{CompletionMethod, CompletionOptions, Identification, StandardEventStatus},
+        connection_options::{ConnectionOptions, MulticastOptions},
+        duty_cycle::{DutyCycleHandle, DutyCycleMessage},
+        error::ScpiError,
+        error_queue::{parse_error_queue_entry, ErrorCheckMode, InstrumentError},
+        framing::ReadBuffer,
+        hislip::{self, HislipClient, HislipMessage, RemoteLocalControl},
+        messenger::Messenger,
+        mock::{MockInstrument, MockScript},
+        networking::{NetworkMode, SerialConfig, UdpTransport},
+        onc_rpc::{self, RpcCall, XdrReader, XdrWriter},
+        query_scpi_message, query_scpi_resource_message,
+        resource::Resource,
+        response::{
+            parse_block, parse_boolean, parse_channel_list, parse_float, parse_integer,
+            parse_string, parse_value, split_list, ResponseValue,
+        },
+        run_control::{RunLimits, RunStatistics, StopHandle, StopReason},
+        send_list_of_scpi_messages, send_list_of_scpi_messages_to_group,
+        send_repeated_scpi_message, send_scpi_message,
+        sequence::{Sequence, SequenceStep},
+        simulator::{ParameterType, Simulator},
+        status::{
+            StatusByte, StatusRegister, StatusRegisterGroup, OPERATION_MEASURING,
+            OPERATION_SWEEPING,
+        },
+        tcp_multicast::{InstrumentResult, TcpMulticastGroup},
+        timing::{TimingReport, TimingStrategy},
+        transport::Transport,
+        vxi11::{self, Vxi11Client},
+    };
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const LOCALHOST_V6: IpAddr = IpAddr::V6(Ipv6Addr::LOCALHOST);
+    const IDN_RESPONSE: &str = "PySCPI,Loopback,0,1.0";
+    const RAW_IDN_RESPONSE: &[u8] = b"PySCPI,Loopback,0,1.0\n";
+    const VXI11_MAX_RECEIVE_SIZE: u32 = 16;
+    const VXI11_STATUS_BYTE: u8 = 0x50;
+    const HISLIP_MAX_MESSAGE_SIZE: u64 = 64;
+    const HISLIP_SESSION_ID: u16 = 42;
+
+    fn spawn_tcp_responder(response: &'static [u8]) -> Result<(u16, JoinHandle<String>), Error> {
+        spawn_tcp_responder_on(LOCALHOST, response)
+    }
+
+    fn spawn_tcp_responder_on(
+        address: IpAddr,
+        response: &'static [u8],
+    ) -> Result<(u16, JoinHandle<String>), Error> {
+        let listener: TcpListener = TcpListener::bind((address, 0))?;
+        let port: u16 = listener.local_addr()?.port();
+
+        let handle: JoinHandle<String> = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Accepting test client failed");
+            let mut request: String = String::new();
+            BufReader::new(stream.try_clone().expect("Cloning test stream failed"))
+                .read_line(&mut request)
+                .expect("Reading test request failed");
+            stream
+                .write_all(response)
+                .expect("Writing test response failed");
+            request
+        });
+
+        Ok((port, handle))
+    }
+
+    fn spawn_tcp_instrument<F>(handler: F) -> Result<(u16, JoinHandle<Vec<String>>), Error>
+    where
+        F: FnMut(&str) -> Option<String> + Send + 'static,
+    {
+        spawn_tcp_instrument_on(TcpListener::bind((LOCALHOST, 0))?, handler)
+    }
+
+    fn spawn_tcp_instrument_on<F>(
+        listener: TcpListener,
+        mut handler: F,
+    ) -> Result<(u16, JoinHandle<Vec<String>>), Error>
+    where
+        F: FnMut(&str) -> Option<String> + Send + 'static,
+    {
+        let port: u16 = listener.local_addr()?.port();
+
+        let handle: JoinHandle<Vec<String>> = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Accepting test client failed");
+            let reader = BufReader::new(stream.try_clone().expect("Cloning test stream failed"));
+            let mut received: Vec<String> = Vec::new();
+            for line in reader.lines() {
+                let command: String = line.expect("Reading test request failed");
+                let command: &str = command.trim();
+                if let Some(response) = handler(command) {
+                    stream
+                        .write_all(format!("{}\n", response).as_bytes())
+                        .expect("Writing test response failed");
+                }
+                received.push(command.to_string());
+            }
+            received
+        });
+
+        Ok((port, handle))
+    }
+
+    /// Answers one portmapper lookup, then serves a single VXI-11 core channel connection. The
+    /// returned log records every core procedure the client invoked. Once service requests are
+    /// enabled, `*TRG` raises one on the interrupt channel.
+    fn spawn_vxi11_instrument<F>(handler: F) -> Result<(u16, JoinHandle<Vec<String>>), Error>
+    where
+        F: FnMut(&str) -> Option<String> + Send + 'static,
+    {
+        spawn_vxi11_instrument_with(0, handler)
+    }
+
+    /// Like `spawn_vxi11_instrument`, but answers `create_intr_chan` with `interrupt_error`.
+    fn spawn_vxi11_instrument_with<F>(
+        interrupt_error: i32,
+        mut handler: F,
+    ) -> Result<(u16, JoinHandle<Vec<String>>), Error>
+    where
+        F: FnMut(&str) -> Option<String> + Send + 'static,
+    {
+        let portmapper: TcpListener = TcpListener::bind((LOCALHOST, 0))?;
+        let core: TcpListener = TcpListener::bind((LOCALHOST, 0))?;
+        let portmapper_port: u16 = portmapper.local_addr()?.port();
+        let core_port: u32 = core.local_addr()?.port() as u32;
+
+        let handle: JoinHandle<Vec<String>> = std::thread::spawn(move || {
+            let (mut stream, _) = portmapper.accept().expect("Accepting portmapper failed");
+            let call: RpcCall = onc_rpc::read_call(&mut stream).expect("Reading GETPORT failed");
+            assert_eq!(call.program, onc_rpc::PORTMAPPER_PROGRAM);
+            assert_eq!(call.procedure, onc_rpc::PORTMAPPER_GETPORT);
+            let results: Vec<u8> = XdrWriter::new().put_u32(core_port).into_bytes();
+            onc_rpc::write_reply(&mut stream, call.xid, &results).expect("Writing port failed");
+
+            let (mut stream, _) = core.accept().expect("Accepting core channel failed");
+            let mut log: Vec<String> = Vec::new();
+            let mut message: Vec<u8> = Vec::new();
+            let mut chunks: usize = 0;
+            let mut responses: VecDeque<String> = VecDeque::new();
+            let mut interrupt: Option<TcpStream> = None;
+            let mut srq_handle: Option<Vec<u8>> = None;
+
+            while let Ok(call) = onc_rpc::read_call::<TcpStream>(&mut stream) {
+                let mut params: XdrReader = XdrReader::new(&call.params);
+                let results: XdrWriter = match call.procedure {
+                    vxi11::CREATE_LINK => {
+                        params.get_i32().expect("Bad client id");
+                        params.get_bool().expect("Bad lock flag");
+                        params.get_u32().expect("Bad lock timeout");
+                        let device: String = params.get_string().expect("Bad device name");
+                        log.push(format!("create_link:{}", device));
+                        XdrWriter::new()
+                            .put_i32(0)
+                            .put_i32(7)
+                            .put_u32(0)
+                            .put_u32(VXI11_MAX_RECEIVE_SIZE)
+                    }
+                    vxi11::DEVICE_WRITE => {
+                        assert_eq!(params.get_i32().expect("Bad link id"), 7);
+                        params.get_u32().expect("Bad io timeout");
+                        params.get_u32().expect("Bad lock timeout");
+                        let flags: i32 = params.get_i32().expect("Bad flags");
+                        let data: Vec<u8> = params.get_opaque().expect("Bad data");
+                        assert!(data.len() <= VXI11_MAX_RECEIVE_SIZE as usize);
+                        message.extend_from_slice(&data);
+                        chunks += 1;
+
+                        if flags & vxi11::FLAG_END != 0 {
+                            let command: String =
+                                String::from_utf8_lossy(&message).trim().to_string();
+                            log.push(format!("write[{}]:{}", chunks, command));
+                            if let Some(response) = handler(&command) {
+                                responses.push_back(format!("{}\n", response));
+                            }
+                            if let (Some(channel), Some(handle), "*TRG") =
+                                (interrupt.as_mut(), srq_handle.as_ref(), command.as_str())
+                            {
+                                let srq: Vec<u8> = XdrWriter::new()
+                                    .put_u32(1)
+                                    .put_u32(0)
+                                    .put_u32(2)
+                                    .put_u32(vxi11::DEVICE_INTR_PROGRAM)
+                                    .put_u32(vxi11::DEVICE_INTR_VERSION)
+                                    .put_u32(vxi11::DEVICE_INTR_SRQ)
+                                    .put_u32(0)
+                                    .put_opaque(&[])
+                                    .put_u32(0)
+                                    .put_opaque(&[])
+                                    .put_opaque(handle)
+                                    .into_bytes();
+                                onc_rpc::write_record(channel, &srq)
+                                    .expect("Writing device_intr_srq failed");
+                            }
+                            message.clear();
+                            chunks = 0;
+                        }
+
+                        XdrWriter::new().put_i32(0).put_u32(data.len() as u32)
+                    }
+                    vxi11::DEVICE_READ => {
+                        log.push("read".to_string());
+                        match responses.pop_front() {
+                            Some(response) => XdrWriter::new()
+                                .put_i32(0)
+                                .put_i32(vxi11::REASON_END)
+                                .put_string(&response),
+                            None => XdrWriter::new().put_i32(15).put_i32(0).put_opaque(&[]),
+                        }
+                    }
+                    vxi11::DEVICE_READ_STB => {
+                        log.push("read_stb".to_string());
+                        XdrWriter::new()
+                            .put_i32(0)
+                            .put_u32(VXI11_STATUS_BYTE as u32)
+                    }
+                    vxi11::DEVICE_TRIGGER => {
+                        log.push("trigger".to_string());
+                        XdrWriter::new().put_i32(0)
+                    }
+                    vxi11::DEVICE_CLEAR => {
+                        log.push("clear".to_string());
+                        responses.clear();
+                        XdrWriter::new().put_i32(0)
+                    }
+                    vxi11::DESTROY_LINK => {
+                        log.push("destroy_link".to_string());
+                        XdrWriter::new().put_i32(0)
+                    }
+                    vxi11::CREATE_INTR_CHAN if interrupt_error != 0 => {
+                        log.push(format!("create_intr_chan:{}", interrupt_error));
+                        XdrWriter::new().put_i32(interrupt_error)
+                    }
+                    vxi11::CREATE_INTR_CHAN => {
+                        let host: Ipv4Addr = Ipv4Addr::from(params.get_u32().expect("Bad host"));
+                        let port: u16 = params.get_u32().expect("Bad port") as u16;
+                        assert_eq!(
+                            params.get_u32().expect("Bad program"),
+                            vxi11::DEVICE_INTR_PROGRAM
+                        );
+                        log.push("create_intr_chan".to_string());
+                        interrupt = Some(
+                            TcpStream::connect((host, port))
+                                .expect("Connecting interrupt channel failed"),
+                        );
+                        XdrWriter::new().put_i32(0)
+                    }
+                    vxi11::DEVICE_ENABLE_SRQ => {
+                        assert_eq!(params.get_i32().expect("Bad link id"), 7);
+                        let enable: bool = params.get_bool().expect("Bad enable flag");
+                        let handle: Vec<u8> = params.get_opaque().expect("Bad handle");
+                        log.push(format!("enable_srq:{}", enable));
+                        srq_handle = match enable {
+                            true => Some(handle),
+                            false => None,
+                        };
+                        XdrWriter::new().put_i32(0)
+                    }
+                    vxi11::DESTROY_INTR_CHAN => {
+                        log.push("destroy_intr_chan".to_string());
+                        interrupt = None;
+                        XdrWriter::new().put_i32(0)
+                    }
+                    _ => XdrWriter::new().put_i32(8),
+                };
+                onc_rpc::write_reply(&mut stream, call.xid, &results.into_bytes())
+                    .expect("Writing VXI-11 reply failed");
+            }
+
+            log
+        });
+
+        Ok((portmapper_port, handle))
+    }
+
+    /// Serves one HiSLIP session on a synchronous/asynchronous channel pair. Device clear honours
+    /// whatever overlap mode the client requests, and `*TRG` raises a service request.
+    fn spawn_hislip_instrument<F>(mut handler: F) -> Result<(u16, JoinHandle<Vec<String>>), Error>
+    where
+        F: FnMut(&str) -> Option<String> + Send + 'static,
+    {
+        let listener: TcpListener = TcpListener::bind((LOCALHOST, 0))?;
+        let port: u16 = listener.local_addr()?.port();
+
+        let handle: JoinHandle<Vec<String>> = std::thread::spawn(move || {
+            let log: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+
+            let (mut sync_channel, _) = listener.accept().expect("Accepting sync channel failed");
+            let initialize: HislipMessage =
+                HislipMessage::read_from(&mut sync_channel).expect("Reading Initialize failed");
+            assert_eq!(initialize.message_type, hislip::INITIALIZE);
+            log.lock().expect("Log poisoned").push(format!(
+                "initialize:{}",
+                String::from_utf8_lossy(&initialize.payload)
+            ));
+            HislipMessage::new(
+                hislip::INITIALIZE_RESPONSE,
+                0,
+                (0x0100 << 16) | HISLIP_SESSION_ID as u32,
+                &[],
+            )
+            .write_to(&mut sync_channel)
+            .expect("Writing InitializeResponse failed");
+
+            let (mut async_channel, _) = listener.accept().expect("Accepting async channel failed");
+            let initialize: HislipMessage = HislipMessage::read_from(&mut async_channel)
+                .expect("Reading AsyncInitialize failed");
+            assert_eq!(initialize.message_type, hislip::ASYNC_INITIALIZE);
+            assert_eq!(initialize.parameter, HISLIP_SESSION_ID as u32);
+            HislipMessage::new(hislip::ASYNC_INITIALIZE_RESPONSE, 0, 0, &[])
+                .write_to(&mut async_channel)
+                .expect("Writing AsyncInitializeResponse failed");
+
+            let mut srq_channel: TcpStream = async_channel
+                .try_clone()
+                .expect("Cloning async channel failed");
+            let async_log: Arc<Mutex<Vec<String>>> = Arc::clone(&log);
+            let async_handle: JoinHandle<()> = std::thread::spawn(move || {
+                while let Ok(message) = HislipMessage::read_from(&mut async_channel) {
+                    let (response_type, payload): (u8, Vec<u8>) = match message.message_type {
+                        hislip::ASYNC_MAXIMUM_MESSAGE_SIZE => (
+                            hislip::ASYNC_MAXIMUM_MESSAGE_SIZE_RESPONSE,
+                            HISLIP_MAX_MESSAGE_SIZE.to_be_bytes().to_vec(),
+                        ),
+                        hislip::ASYNC_DEVICE_CLEAR => {
+                            let mut log = async_log.lock().expect("Log poisoned");
+                            log.push("async_device_clear".to_string());
+                            (hislip::ASYNC_DEVICE_CLEAR_ACKNOWLEDGE, Vec::new())
+                        }
+                        hislip::ASYNC_REMOTE_LOCAL_CONTROL => {
+                            let mut log = async_log.lock().expect("Log poisoned");
+                            log.push(format!("remote_local:{}", message.control_code));
+                            (hislip::ASYNC_REMOTE_LOCAL_RESPONSE, Vec::new())
+                        }
+                        x => panic!("Unexpected async message type {}", x),
+                    };
+                    HislipMessage::new(response_type, 0, 0, &payload)
+                        .write_to(&mut async_channel)
+                        .expect("Writing async response failed");
+                }
+            });
+
+            let mut message: Vec<u8> = Vec::new();
+            let mut frames: usize = 0;
+            let mut rmt_delivered: bool = false;
+            while let Ok(frame) = HislipMessage::read_from(&mut sync_channel) {
+                match frame.message_type {
+                    hislip::DATA | hislip::DATA_END => {
+                        assert!(frame.payload.len() as u64 + 16 <= HISLIP_MAX_MESSAGE_SIZE);
+                        message.extend_from_slice(&frame.payload);
+                        rmt_delivered |= frame.control_code & 0x01 != 0;
+                        frames += 1;
+                    }
+                    hislip::DEVICE_CLEAR_COMPLETE => {
+                        let mut log = log.lock().expect("Log poisoned");
+                        log.push(format!("device_clear_complete:{}", frame.control_code));
+                        HislipMessage::new(
+                            hislip::DEVICE_CLEAR_ACKNOWLEDGE,
+                            frame.control_code,
+                            0,
+                            &[],
+                        )
+                        .write_to(&mut sync_channel)
+                        .expect("Writing DeviceClearAcknowledge failed");
+                        continue;
+                    }
+                    x => panic!("Unexpected sync message type {}", x),
+                }
+
+                if frame.message_type == hislip::DATA_END {
+                    let command: String = String::from_utf8_lossy(&message).trim().to_string();
+                    log.lock().expect("Log poisoned").push(format!(
+                        "data[{},{:#x},rmt={}]:{}",
+                        frames, frame.parameter, rmt_delivered, command
+                    ));
+                    if command == "*TRG" {
+                        HislipMessage::new(hislip::ASYNC_SERVICE_REQUEST, 0x40, 0, &[])
+                            .write_to(&mut srq_channel)
+                            .expect("Writing AsyncServiceRequest failed");
+                    }
+                    if let Some(response) = handler(&command) {
+                        HislipMessage::new(
+                            hislip::DATA_END,
+                            0,
+                            frame.parameter,
+                            format!("{}\n", response).as_bytes(),
+                        )
+                        .write_to(&mut sync_channel)
+                        .expect("Writing DataEnd failed");
+                    }
+                    message.clear();
+                    frames = 0;
+                    rmt_delivered = false;
+                }
+            }
+
+            async_handle.join().expect("Async channel thread panicked");
+            let log: Vec<String> = log.lock().expect("Log poisoned").clone();
+            log
+        });
+
+        Ok((port, handle))
+    }
+
+    struct ScriptedTransport {
+        written: Arc<Mutex<Vec<u8>>>,
+        responses: VecDeque<Vec<u8>>,
+        timeouts: Arc<Mutex<Vec<Option<Duration>>>>,
+    }
+
+    impl Transport for ScriptedTransport {
+        fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+            self.written
+                .lock()
+                .expect("Written buffer poisoned")
+                .extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<usize, ScpiError> {
+            let response: Vec<u8> = self.responses.pop_front().ok_or(ScpiError::Timeout)?;
+            buffer[..response.len()].copy_from_slice(&response);
+            Ok(response.len())
+        }
+
+        fn set_timeout(
+            &mut self,
+            read_timeout: Option<Duration>,
+            _write_timeout: Option<Duration>,
+        ) -> Result<(), ScpiError> {
+            self.timeouts
+                .lock()
+                .expect("Timeout log poisoned")
+                .push(read_timeout);
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), ScpiError> {
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<(), ScpiError> {
+            self.responses.clear();
+            Ok(())
+        }
+    }
+
+    /// Takes `SLOW_WRITE` to write any message starting with "SLOW".
+    struct SlowTransport;
+
+    const SLOW_WRITE: Duration = Duration::from_millis(20);
+
+    impl Transport for SlowTransport {
+        fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+            if bytes.starts_with(b"SLOW") {
+                std::thread::sleep(SLOW_WRITE);
+            }
+            Ok(bytes.len())
+        }
+
+        fn read_bytes(&mut self, _buffer: &mut [u8]) -> Result<usize, ScpiError> {
+            Err(ScpiError::Timeout)
+        }
+
+        fn set_timeout(
+            &mut self,
+            _read_timeout: Option<Duration>,
+            _write_timeout: Option<Duration>,
+        ) -> Result<(), ScpiError> {
+            Ok(())
+        }
+
+        fn flush(&mut self) -> Result<(), ScpiError> {
+            Ok(())
+        }
+
+        fn clear(&mut self) -> Result<(), ScpiError> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_send_udp_message() -> Result<(), ScpiError> {
+        let instrument: MockInstrument =
+            MockInstrument::start(&NetworkMode::Udp, MockScript::new())?;
+
+        let sent: usize = send_scpi_message(
+            "*IDN",
+            &NetworkMode::Udp,
+            &LOCALHOST,
+            instrument.get_port(),
+            0,
+        )?;
+
+        assert_eq!(sent, "*IDN\r\n".len());
+        assert_eq!(
+            instrument.wait_for_received(1, Duration::from_secs(5))?,
+            ["*IDN"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_repeated_udp_messages() -> Result<(), ScpiError> {
+        let instrument: MockInstrument =
+            MockInstrument::start(&NetworkMode::Udp, MockScript::new())?;
+
+        send_repeated_scpi_message(
+            "*IDN",
+            &NetworkMode::Udp,
+            &LOCALHOST,
+            instrument.get_port(),
+            0,
+            Some(10),
+        )?;
+
+        assert_eq!(
+            instrument.wait_for_received(10, Duration::from_secs(5))?,
+            vec!["*IDN"; 10]
+        );
+        Ok(())
+    }
 
     #[test]
-    fn test_send_udp_message() -> Result<(), AddrParseError> {
-        const MESSAGE: &str = "*IDN";
-        const MODE: NetworkMode = NetworkMode::Udp;
-        let remote_client: IpAddr = IpAddr::from_str("192.168.1.70")?;
-        const REMOTE_PORT: u16 = 5025;
-        const LOCAL_PORT: u16 = 42;
+    fn test_mock_instrument() -> Result<(), ScpiError> {
+        let script: MockScript = MockScript::new()
+            .with_response("*IDN?", IDN_RESPONSE)
+            .with_responses("MEAS:VOLT?", &["1.5", "2.5"]);
+        let instrument: MockInstrument = MockInstrument::start(&NetworkMode::Tcp, script)?;
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
 
-        assert!(send_scpi_message(MESSAGE, &MODE, &remote_client, REMOTE_PORT, LOCAL_PORT).is_ok());
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            instrument.get_port(),
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &options,
+        )?;
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        messenger.send_list_of_messages(&["*RST", "VOLT 1.5"])?;
+        let readings: Vec<f64> = (0..3)
+            .map(|_| messenger.query_as::<f64>("MEAS:VOLT?"))
+            .collect::<Result<Vec<f64>, ScpiError>>()?;
+        assert_eq!(readings, [1.5, 2.5, 2.5]);
 
+        instrument.add_response("SYST:ERR?", "0,\"No error\"");
+        assert_eq!(messenger.query("SYST:ERR?")?, "0,\"No error\"");
+        assert_eq!(
+            instrument.get_received(),
+            [
+                "*IDN?",
+                "*RST",
+                "VOLT 1.5",
+                "MEAS:VOLT?",
+                "MEAS:VOLT?",
+                "MEAS:VOLT?",
+                "SYST:ERR?"
+            ]
+        );
+
+        // Unscripted queries go unanswered, and a second client is served as well.
+        instrument.clear_received();
+        let mut second: Messenger = Messenger::new(
+            0,
+            instrument.get_port(),
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        assert!(matches!(
+            second.query_with_timeout("MEAS:CURR?", Duration::from_millis(50)),
+            Err(ScpiError::Timeout)
+        ));
+        assert_eq!(instrument.get_received(), ["MEAS:CURR?"]);
+        assert!(matches!(
+            MockInstrument::start(&NetworkMode::Hislip, MockScript::new()),
+            Err(ScpiError::Unsupported(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_query() -> Result<(), ScpiError> {
+        let (port, responder) = spawn_tcp_responder(RAW_IDN_RESPONSE)?;
+
+        let response: String = query_scpi_message("*IDN?", &NetworkMode::Tcp, &LOCALHOST, port, 0)?;
+
+        assert_eq!(response, IDN_RESPONSE);
+        assert_eq!(responder.join().expect("Responder panicked"), "*IDN?\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_udp_query() -> Result<(), ScpiError> {
+        let responder_socket: UdpSocket = UdpSocket::bind((LOCALHOST, 0))?;
+        let port: u16 = responder_socket.local_addr()?.port();
+        let stranger_socket: UdpSocket = UdpSocket::bind((LOCALHOST, 0))?;
+
+        let responder: JoinHandle<Vec<u8>> = std::thread::spawn(move || {
+            let mut request: [u8; 64] = [0; 64];
+            let (size, client) = responder_socket
+                .recv_from(&mut request)
+                .expect("Receiving test request failed");
+            // Only datagrams from the instrument's address may answer the query.
+            stranger_socket
+                .send_to(b"+9.99000E+00\n", client)
+                .expect("Sending stranger datagram failed");
+            responder_socket
+                .send_to(b"+3.30000E+00\n", client)
+                .expect("Sending test response failed");
+            request[..size].to_vec()
+        });
+
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Udp,
+            &ConnectionOptions::default(),
+        )?;
+        let response: String =
+            messenger.query_with_timeout("MEAS:VOLT?", Duration::from_secs(5))?;
+
+        assert_eq!(response, "+3.30000E+00");
+        assert_eq!(
+            responder.join().expect("Responder panicked"),
+            b"MEAS:VOLT?\r\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_timeout() -> Result<(), ScpiError> {
+        let silent_socket: UdpSocket = UdpSocket::bind((LOCALHOST, 0))?;
+        let port: u16 = silent_socket.local_addr()?.port();
+
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Udp,
+            &ConnectionOptions::default(),
+        )?;
+        let error: ScpiError = messenger
+            .query_with_timeout("*IDN?", Duration::from_millis(50))
+            .expect_err("Query to a silent instrument should time out");
+
+        assert!(matches!(error, ScpiError::Timeout));
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_terminators() -> Result<(), ScpiError> {
+        let (port, responder) = spawn_tcp_responder(b"  3.3  \r")?;
+        let options: ConnectionOptions = ConnectionOptions::new()
+            .with_write_terminator("\n")
+            .with_read_terminator("\r")
+            .with_trim(false);
+
+        let mut messenger: Messenger =
+            Messenger::new(0, port, &LOCALHOST, &NetworkMode::Tcp, &options)?;
+        let response: String = messenger.query(" MEAS:VOLT?")?;
+
+        assert_eq!(response, "  3.3  ");
+        assert_eq!(
+            responder.join().expect("Responder panicked"),
+            " MEAS:VOLT?\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_timeout_option() -> Result<(), ScpiError> {
+        let silent_socket: UdpSocket = UdpSocket::bind((LOCALHOST, 0))?;
+        let port: u16 = silent_socket.local_addr()?.port();
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_millis(50)));
+
+        let mut messenger: Messenger =
+            Messenger::new(0, port, &LOCALHOST, &NetworkMode::Udp, &options)?;
+        let error: ScpiError = messenger
+            .query("*IDN?")
+            .expect_err("Query to a silent instrument should time out");
+
+        assert!(matches!(error, ScpiError::Timeout));
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_multicast_needs_group() {
+        let mode: NetworkMode = NetworkMode::TcpMulticast {
+            members: Vec::new(),
+            parallel: false,
+        };
+        assert!(matches!(
+            Messenger::new(0, 5025, &LOCALHOST, &mode, &ConnectionOptions::default()),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_block_header_parsing() -> Result<(), ScpiError> {
+        assert_eq!(parse_block_header(b"#")?, None);
+        assert_eq!(parse_block_header(b"#21")?, None);
+        assert_eq!(parse_block_header(b"#0abc")?, Some(BlockHeader::Indefinite));
+        assert_eq!(
+            parse_block_header(b"#212abc")?,
+            Some(BlockHeader::Definite {
+                header_length: 4,
+                data_length: 12
+            })
+        );
+        assert!(matches!(
+            parse_block_header(b"12"),
+            Err(ScpiError::Parse(_))
+        ));
+        assert!(matches!(
+            parse_block_header(b"#2x1"),
+            Err(ScpiError::Parse(_))
+        ));
+        assert_eq!(encode_definite_block(b"hello")?, b"#15hello");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_buffer_framing() -> Result<(), ScpiError> {
+        let mut buffer: ReadBuffer = ReadBuffer::default();
+
+        // Nothing is consumed until the whole block and its terminator have arrived.
+        buffer.extend(b"#15he");
+        assert_eq!(buffer.take_block("\n")?, None);
+        buffer.extend(b"llo");
+        assert_eq!(buffer.take_block("\n")?, None);
+        buffer.extend(b"\n#0a\nb\r\n+1\r\n");
+        assert_eq!(buffer.take_block("\n")?, Some(b"hello".to_vec()));
+        assert_eq!(buffer.take_block("\r\n")?, Some(b"a\nb".to_vec()));
+        assert_eq!(buffer.take_response("\r\n")?, Some(b"+1\r\n".to_vec()));
+        assert!(matches!(
+            buffer.take_response(""),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+
+        // An unfinished read marks the buffer stale until it completes or is discarded.
+        buffer.extend(b"+2");
+        assert_eq!(buffer.take_response("\n")?, None);
+        buffer.extend(b"\n");
+        assert_eq!(buffer.take_response("\n")?, Some(b"+2\n".to_vec()));
+        assert!(!buffer.discard_if_stale());
+        buffer.extend(b"+3");
+        assert_eq!(buffer.take_response("\n")?, None);
+        assert!(buffer.discard_if_stale());
+        buffer.extend(b"+4\n");
+        assert_eq!(buffer.take_response("\n")?, Some(b"+4\n".to_vec()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_block() -> Result<(), ScpiError> {
+        let (port, responder) = spawn_tcp_responder(b"#212hello\nworld!\n")?;
+
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        let data: Vec<u8> = messenger.query_block("CURV?")?;
+
+        assert_eq!(data, b"hello\nworld!");
+        assert_eq!(responder.join().expect("Responder panicked"), "CURV?\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_typed_block() -> Result<(), ScpiError> {
+        const RESPONSE: &[u8] = &[
+            b'#', b'1', b'8', 0x3f, 0x80, 0x00, 0x00, 0xc0, 0x20, 0x00, 0x00, b'\n',
+        ];
+        let (port, _) = spawn_tcp_responder(RESPONSE)?;
+
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        let values: Vec<f32> = messenger.query_binary_values("CURV?", ByteOrder::BigEndian)?;
+
+        assert_eq!(values, vec![1.0, -2.5]);
         Ok(())
     }
 
     #[test]
-    fn test_repeated_udp_messages() -> Result<(), AddrParseError> {
-        const MESSAGE: &str = "*IDN";
-        const MODE: NetworkMode = NetworkMode::Udp;
-        let remote_client: IpAddr = IpAddr::from_str("192.168.1.70")?;
-        const REMOTE_PORT: u16 = 5025;
-        const LOCAL_PORT: u16 = 42;
+    fn test_send_block() -> Result<(), ScpiError> {
+        let listener: TcpListener = TcpListener::bind((LOCALHOST, 0))?;
+        let port: u16 = listener.local_addr()?.port();
+        let receiver: JoinHandle<Vec<u8>> = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().expect("Accepting test client failed");
+            let mut request: Vec<u8> = Vec::new();
+            stream
+                .read_to_end(&mut request)
+                .expect("Reading test request failed");
+            request
+        });
+
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        messenger.send_block("DATA:ARB pulse,", &[1, 2, 3])?;
+        messenger.send_binary_values("TRAC:DATA", &[1i16, -1], ByteOrder::LittleEndian)?;
+        drop(messenger);
 
-        assert!(send_repeated_scpi_message(
-            MESSAGE,
-            &MODE,
-            &remote_client,
-            REMOTE_PORT,
-            LOCAL_PORT,
-            Some(10)
+        assert_eq!(
+            receiver.join().expect("Receiver panicked"),
+            b"DATA:ARB pulse,#13\x01\x02\x03\r\nTRAC:DATA #14\x01\x00\xff\xff\r\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_queue_entry_parsing() -> Result<(), ScpiError> {
+        assert_eq!(
+            parse_error_queue_entry("-113,\"Undefined header\"")?,
+            InstrumentError::new(-113, "Undefined header")
+        );
+        assert_eq!(
+            parse_error_queue_entry("+0,\"No error\"\n")?,
+            InstrumentError::new(0, "No error")
+        );
+        assert_eq!(
+            parse_error_queue_entry("-222,\"Data out of range; \"\"VOLT\"\"\"")?,
+            InstrumentError::new(-222, "Data out of range; \"VOLT\"")
+        );
+        assert!(matches!(
+            parse_error_queue_entry("No error"),
+            Err(ScpiError::Parse(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_check_after_list() -> Result<(), ScpiError> {
+        let mut queue: Vec<&str> = vec!["+0,\"No error\"", "-222,\"Data out of range\""];
+        let (port, instrument) = spawn_tcp_instrument(move |command| match command {
+            "SYST:ERR?" => queue.pop().map(str::to_string),
+            _ => None,
+        })?;
+
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        messenger.set_error_check_mode(ErrorCheckMode::AfterList);
+        let error: ScpiError = messenger
+            .send_list_of_messages(&["*RST", "VOLT 99"])
+            .expect_err("Instrument errors should fail the list");
+        drop(messenger);
+
+        match error {
+            ScpiError::Instrument(errors) => {
+                assert_eq!(
+                    errors,
+                    vec![InstrumentError::new(-222, "Data out of range")]
+                )
+            }
+            other => panic!("Unexpected error {:?}", other),
+        }
+        assert_eq!(
+            instrument.join().expect("Instrument panicked"),
+            vec!["*RST", "VOLT 99", "SYST:ERR?", "SYST:ERR?"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_check_after_each_command() -> Result<(), ScpiError> {
+        let (port, instrument) = spawn_tcp_instrument(|command| match command {
+            "*IDN?" => Some(IDN_RESPONSE.to_string()),
+            "SYST:ERR?" => Some("0,\"No error\"".to_string()),
+            _ => None,
+        })?;
+
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        messenger.set_error_check_mode(ErrorCheckMode::AfterEachCommand);
+        messenger.send_list_of_messages(&["*CLS"])?;
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        drop(messenger);
+
+        assert_eq!(
+            instrument.join().expect("Instrument panicked"),
+            vec!["*CLS", "SYST:ERR?", "*IDN?", "SYST:ERR?"]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serial_messenger() -> Result<(), ScpiError> {
+        let (host, instrument) = SerialConnection::pair(&SerialConfig::default())?;
+
+        let responder: JoinHandle<Vec<String>> = std::thread::spawn(move || {
+            let mut reader: BufReader<SerialConnection> = BufReader::new(instrument);
+            let mut received: Vec<String> = Vec::new();
+            while received.len() < 4 {
+                let mut line: String = String::new();
+                reader
+                    .read_line(&mut line)
+                    .expect("Reading serial request failed");
+                if line.trim() == "*IDN?" {
+                    reader
+                        .get_mut()
+                        .write_all(RAW_IDN_RESPONSE)
+                        .expect("Writing serial response failed");
+                }
+                received.push(line);
+            }
+            received
+        });
+
+        let options: ConnectionOptions = ConnectionOptions::new()
+            .with_write_terminator("\n")
+            .with_read_timeout(Some(Duration::from_secs(5)));
+        let mut messenger: Messenger = Messenger::from_transport(Box::new(host), &options)?;
+
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        assert_eq!(messenger.send_repeated_message("OUTP ON", Some(3))?, 8);
+        assert_eq!(
+            responder.join().expect("Responder panicked"),
+            vec!["*IDN?\n", "OUTP ON\n", "OUTP ON\n", "OUTP ON\n"]
+        );
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_serial_network_mode() -> Result<(), ScpiError> {
+        let (instrument, host) = SerialConnection::pair(&SerialConfig::default())?;
+        let path: String = host
+            .get_name()
+            .ok_or_else(|| ScpiError::InvalidArgument("Serial pair has no path".to_string()))?;
+
+        // Both ends stay open until the end of the test, since a pty hangs up once either closes.
+        let responder: JoinHandle<(Vec<String>, BufReader<SerialConnection>)> =
+            std::thread::spawn(move || {
+                let mut reader: BufReader<SerialConnection> = BufReader::new(instrument);
+                let mut received: Vec<String> = Vec::new();
+                while received.len() < 3 {
+                    let mut line: String = String::new();
+                    reader
+                        .read_line(&mut line)
+                        .expect("Reading serial request failed");
+                    if line.trim() == "*IDN?" {
+                        reader
+                            .get_mut()
+                            .write_all(RAW_IDN_RESPONSE)
+                            .expect("Writing serial response failed");
+                    }
+                    received.push(line.trim().to_string());
+                }
+                (received, reader)
+            });
+
+        let mode: NetworkMode = NetworkMode::Serial {
+            path,
+            config: SerialConfig::default(),
+        };
+        let unused: IpAddr = IpAddr::V4(Ipv4Addr::UNSPECIFIED);
+        send_list_of_scpi_messages(&["*RST", "OUTP ON"], &mode, &unused, 0, 0)?;
+        assert_eq!(
+            query_scpi_message("*IDN?", &mode, &unused, 0, 0)?,
+            IDN_RESPONSE
+        );
+        let (received, _instrument) = responder.join().expect("Responder panicked");
+        assert_eq!(received, vec!["*RST", "OUTP ON", "*IDN?"]);
+        drop(host);
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_transport() -> Result<(), ScpiError> {
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let timeouts: Arc<Mutex<Vec<Option<Duration>>>> = Arc::new(Mutex::new(Vec::new()));
+        let transport: ScriptedTransport = ScriptedTransport {
+            written: written.clone(),
+            responses: VecDeque::from([b"PySCPI,".to_vec(), b"Loopback,0,1.0\n".to_vec()]),
+            timeouts: timeouts.clone(),
+        };
+
+        let mut messenger: Messenger =
+            Messenger::from_transport(Box::new(transport), &ConnectionOptions::default())?;
+        let response: String = messenger.query_with_timeout("*IDN?", Duration::from_secs(1))?;
+
+        assert_eq!(response, IDN_RESPONSE);
+        assert!(matches!(messenger.query("*IDN?"), Err(ScpiError::Timeout)));
+        assert_eq!(
+            *written.lock().expect("Written buffer poisoned"),
+            b"*IDN?\r\n*IDN?\r\n"
+        );
+        assert_eq!(
+            *timeouts.lock().expect("Timeout log poisoned"),
+            vec![None, Some(Duration::from_secs(1)), None]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_vxi11_query() -> Result<(), ScpiError> {
+        let (portmapper_port, handle) = spawn_vxi11_instrument(|command| match command {
+            "*IDN?" => Some(IDN_RESPONSE.to_string()),
+            _ => None,
+        })?;
+
+        let client: Vxi11Client =
+            Vxi11Client::connect_with_portmapper(&LOCALHOST, portmapper_port, "inst0", None)?;
+        assert_eq!(client.get_max_receive_size(), VXI11_MAX_RECEIVE_SIZE);
+
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
+        let mut messenger: Messenger = Messenger::from_transport(Box::new(client), &options)?;
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        messenger.send_message("SOUR:VOLT:LEV:IMM:AMPL 1.25")?;
+        assert!(matches!(messenger.read_response(), Err(ScpiError::Timeout)));
+        drop(messenger);
+
+        let log: Vec<String> = handle.join().expect("VXI-11 server panicked");
+        assert_eq!(
+            log,
+            vec![
+                "create_link:inst0",
+                "write[1]:*IDN?",
+                "read",
+                "write[2]:SOUR:VOLT:LEV:IMM:AMPL 1.25",
+                "read",
+                "destroy_link",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vxi11_device_operations() -> Result<(), ScpiError> {
+        let (portmapper_port, handle) = spawn_vxi11_instrument(|_| Some("1".to_string()))?;
+
+        let mut client: Vxi11Client =
+            Vxi11Client::connect_with_portmapper(&LOCALHOST, portmapper_port, "gpib0,5", None)?;
+        client.device_trigger()?;
+        assert_eq!(client.device_read_stb()?, VXI11_STATUS_BYTE);
+        assert_eq!(client.device_write(b"*OPC?\n")?, 6);
+        client.device_clear()?;
+        client.destroy_link()?;
+        client.destroy_link()?;
+        drop(client);
+
+        let log: Vec<String> = handle.join().expect("VXI-11 server panicked");
+        assert_eq!(
+            log,
+            vec![
+                "create_link:gpib0,5",
+                "trigger",
+                "read_stb",
+                "write[1]:*OPC?",
+                "clear",
+                "destroy_link",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hislip_query() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_hislip_instrument(|command| match command {
+            "*IDN?" => Some(IDN_RESPONSE.to_string()),
+            _ => None,
+        })?;
+        let long_command: String = format!("DISP:TEXT \"{}\"", "X".repeat(80));
+
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
+        let mut messenger: Messenger =
+            Messenger::new(0, port, &LOCALHOST, &NetworkMode::Hislip, &options)?;
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        messenger.send_message(&long_command)?;
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        drop(messenger);
+
+        let log: Vec<String> = handle.join().expect("HiSLIP server panicked");
+        assert_eq!(
+            log,
+            vec![
+                "initialize:hislip0".to_string(),
+                "data[1,0xffffff00,rmt=false]:*IDN?".to_string(),
+                format!("data[2,0xffffff02,rmt=true]:{}", long_command),
+                "data[1,0xffffff04,rmt=false]:*IDN?".to_string(),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hislip_device_clear_and_overlap() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_hislip_instrument(|_| Some(IDN_RESPONSE.to_string()))?;
+
+        let mut client: HislipClient =
+            HislipClient::connect((LOCALHOST, port).into(), "hislip1", None)?;
+        assert_eq!(client.get_session_id(), HISLIP_SESSION_ID);
+        assert_eq!(client.get_max_message_size(), HISLIP_MAX_MESSAGE_SIZE);
+        assert!(!client.get_overlapped());
+
+        client.remote_local(RemoteLocalControl::EnableRemoteGoToRemote)?;
+        assert!(client.set_overlapped(true)?);
+        client.write_message(b"*IDN?\n")?;
+        assert_eq!(client.read_message()?, (RAW_IDN_RESPONSE.to_vec(), true));
+        client.write_message(b"*IDN?\n")?;
+        client.clear()?;
+        assert!(client.get_overlapped());
+        drop(client);
+
+        let log: Vec<String> = handle.join().expect("HiSLIP server panicked");
+        assert_eq!(
+            log[..5],
+            [
+                "initialize:hislip1",
+                "remote_local:3",
+                "async_device_clear",
+                "device_clear_complete:1",
+                "data[1,0xffffff00,rmt=false]:*IDN?",
+            ]
+        );
+
+        // The unanswered message and the second AsyncDeviceClear travel on different channels, so
+        // the server may log them in either order.
+        let mut in_flight: Vec<String> = log[5..7].to_vec();
+        in_flight.sort();
+        assert_eq!(
+            in_flight,
+            ["async_device_clear", "data[1,0xffffff02,rmt=true]:*IDN?"]
+        );
+        assert_eq!(log[7..], ["device_clear_complete:1"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resource_parsing() -> Result<(), ScpiError> {
+        assert_eq!(
+            "TCPIP0::192.168.1.70::5025::SOCKET".parse::<Resource>()?,
+            Resource::Socket {
+                host: "192.168.1.70".to_string(),
+                port: 5025
+            }
+        );
+        assert_eq!(
+            "tcpip::scope.lab::inst1::instr".parse::<Resource>()?,
+            Resource::Vxi11 {
+                host: "scope.lab".to_string(),
+                device_name: "inst1".to_string()
+            }
+        );
+        assert_eq!(
+            "TCPIP::10.0.0.2".parse::<Resource>()?,
+            Resource::Vxi11 {
+                host: "10.0.0.2".to_string(),
+                device_name: "inst0".to_string()
+            }
+        );
+        assert_eq!(
+            "TCPIP::host::hislip0::INSTR".parse::<Resource>()?,
+            Resource::Hislip {
+                host: "host".to_string(),
+                sub_address: "hislip0".to_string(),
+                port: 4880
+            }
+        );
+        assert_eq!(
+            "TCPIP::[fe80::1]::hislip1,4881::INSTR".parse::<Resource>()?,
+            Resource::Hislip {
+                host: "fe80::1".to_string(),
+                sub_address: "hislip1".to_string(),
+                port: 4881
+            }
+        );
+        assert_eq!(
+            "ASRL/dev/ttyUSB0::INSTR".parse::<Resource>()?,
+            Resource::Serial {
+                path: "/dev/ttyUSB0".to_string()
+            }
+        );
+        assert_eq!(
+            "ASRLé123456".parse::<Resource>()?,
+            Resource::Serial {
+                path: "é123456".to_string()
+            }
+        );
+        assert_eq!(
+            "ASRL/dev/ttyé::INSTR".parse::<Resource>()?,
+            Resource::Serial {
+                path: "/dev/ttyé".to_string()
+            }
+        );
+
+        for invalid in [
+            "GPIB0::5::INSTR",
+            "TCPIP::host::5025::SOCKET::extra",
+            "TCPIP::host::port::SOCKET",
+            "TCPIP::::5025::SOCKET",
+            "TCPIP::[::1::5025::SOCKET",
+            "ASRL::INSTR",
+            "ASRLé::INSTR::x",
+        ] {
+            assert!(
+                matches!(
+                    invalid.parse::<Resource>(),
+                    Err(ScpiError::InvalidArgument(_))
+                ),
+                "{} should not parse",
+                invalid
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_socket_resource() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_tcp_responder(RAW_IDN_RESPONSE)?;
+
+        let resource: String = format!("TCPIP0::localhost::{}::SOCKET", port);
+        assert_eq!(
+            query_scpi_resource_message("*IDN?", &resource)?,
+            IDN_RESPONSE
+        );
+        assert_eq!(handle.join().expect("Responder panicked"), "*IDN?\r\n");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_open_hislip_resource() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_hislip_instrument(|_| Some(IDN_RESPONSE.to_string()))?;
+
+        let resource: String = format!("TCPIP::127.0.0.1::hislip2,{}::INSTR", port);
+        let mut messenger: Messenger = Messenger::open(&resource, &ConnectionOptions::default())?;
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        drop(messenger);
+
+        let log: Vec<String> = handle.join().expect("HiSLIP server panicked");
+        assert_eq!(log[0], "initialize:hislip2");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_tcp_query() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_tcp_responder_on(LOCALHOST_V6, RAW_IDN_RESPONSE)?;
+
+        let response: String =
+            query_scpi_message("*IDN?", &NetworkMode::Tcp, &LOCALHOST_V6, port, 0)?;
+
+        assert_eq!(response, IDN_RESPONSE);
+        assert_eq!(handle.join().expect("Responder panicked"), "*IDN?\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_udp_query() -> Result<(), ScpiError> {
+        let responder_socket: UdpSocket = UdpSocket::bind((LOCALHOST_V6, 0))?;
+        let port: u16 = responder_socket.local_addr()?.port();
+
+        let responder: JoinHandle<()> = std::thread::spawn(move || {
+            let mut request: [u8; 64] = [0; 64];
+            let (_, client) = responder_socket
+                .recv_from(&mut request)
+                .expect("Receiving test request failed");
+            responder_socket
+                .send_to(RAW_IDN_RESPONSE, client)
+                .expect("Sending test response failed");
+        });
+
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
+        let mut messenger: Messenger =
+            Messenger::new(0, port, &LOCALHOST_V6, &NetworkMode::Udp, &options)?;
+
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        responder.join().expect("Responder panicked");
+        Ok(())
+    }
+
+    #[test]
+    fn test_ipv6_multicast_loopback() -> Result<(), ScpiError> {
+        const GROUP: Ipv6Addr = Ipv6Addr::new(0xff01, 0, 0, 0, 0, 0, 0x5c, 0x1);
+        let multicast: MulticastOptions = MulticastOptions::new()
+            .with_hops(Some(1))
+            .with_loopback(true);
+
+        let mut listener: UdpTransport =
+            UdpTransport::join_multicast(0, SocketAddr::new(IpAddr::V6(GROUP), 0), &multicast)?;
+        listener.set_timeout(Some(Duration::from_secs(5)), None)?;
+        let port: u16 = listener.local_address()?.port();
+
+        let options: ConnectionOptions = ConnectionOptions::new().with_multicast(multicast);
+        let mut sender: Messenger = Messenger::new(
+            0,
+            port,
+            &IpAddr::V6(GROUP),
+            &NetworkMode::UdpMulticast,
+            &options,
+        )?;
+        sender.send_message("OUTP ON")?;
+
+        let mut received: [u8; 64] = [0; 64];
+        let size: usize = listener.read_bytes(&mut received)?;
+        assert_eq!(&received[..size], b"OUTP ON\r\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_multicast_group() -> Result<(), ScpiError> {
+        let mut addresses: Vec<SocketAddr> = Vec::new();
+        let mut handles: Vec<JoinHandle<Vec<String>>> = Vec::new();
+        for index in 0..3 {
+            let (port, handle) = spawn_tcp_instrument(move |command| match command {
+                "*IDN?" => Some(format!("PySCPI,Supply,{},1.0", index)),
+                _ => None,
+            })?;
+            addresses.push(SocketAddr::new(LOCALHOST, port));
+            handles.push(handle);
+        }
+
+        let mut group: TcpMulticastGroup =
+            TcpMulticastGroup::connect(&addresses, &ConnectionOptions::default(), true)?;
+        assert_eq!(group.len(), 3);
+        assert_eq!(group.get_addresses(), addresses);
+
+        let results: Vec<InstrumentResult<()>> =
+            group.send_list_of_messages(&["VOLT 5", "OUTP ON"]);
+        assert!(results.iter().all(|x| x.is_ok()));
+
+        group.set_parallel(false);
+        let responses: Vec<InstrumentResult<String>> = group.query("*IDN?");
+        for (index, response) in responses.into_iter().enumerate() {
+            assert_eq!(response.get_address(), addresses[index]);
+            assert_eq!(
+                response.into_result()?,
+                format!("PySCPI,Supply,{},1.0", index)
+            );
+        }
+        drop(group);
+
+        for handle in handles {
+            assert_eq!(
+                handle.join().expect("Instrument panicked"),
+                vec!["VOLT 5", "OUTP ON", "*IDN?"]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_multicast_reports_failures() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_tcp_instrument(|_| Some(IDN_RESPONSE.to_string()))?;
+        let closed_port: u16 = TcpListener::bind((LOCALHOST, 0))?.local_addr()?.port();
+
+        let unreachable: SocketAddr = SocketAddr::new(LOCALHOST, closed_port);
+        let (reachable_port, _) = spawn_tcp_instrument(|_| None)?;
+        let addresses: [SocketAddr; 2] = [SocketAddr::new(LOCALHOST, reachable_port), unreachable];
+        match TcpMulticastGroup::connect(&addresses, &ConnectionOptions::default(), true) {
+            Err(ScpiError::Group(failures)) => {
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].0, unreachable);
+                assert!(matches!(failures[0].1, ScpiError::Connection(_)));
+            }
+            x => panic!("Expected a group error, got {:?}", x.map(|_| ())),
+        }
+        assert!(matches!(
+            send_list_of_scpi_messages_to_group(&["*RST"], &[LOCALHOST], closed_port, true),
+            Err(ScpiError::Group(_))
+        ));
+
+        let scripted: ScriptedTransport = ScriptedTransport {
+            written: Arc::new(Mutex::new(Vec::new())),
+            responses: VecDeque::new(),
+            timeouts: Arc::new(Mutex::new(Vec::new())),
+        };
+        let tcp: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        let silent: Messenger =
+            Messenger::from_transport(Box::new(scripted), &ConnectionOptions::default())?;
+        let mut group: TcpMulticastGroup = TcpMulticastGroup::from_messengers(
+            vec![
+                (SocketAddr::new(LOCALHOST, port), tcp),
+                (unreachable, silent),
+            ],
+            true,
+        );
+
+        let responses: Vec<InstrumentResult<String>> = group.query("*IDN?");
+        assert_eq!(
+            responses[0].get_result().as_deref().ok(),
+            Some(IDN_RESPONSE)
+        );
+        assert!(matches!(responses[1].get_result(), Err(ScpiError::Timeout)));
+        drop(group);
+        handle.join().expect("Instrument panicked");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tcp_multicast_mode() -> Result<(), ScpiError> {
+        // Every member listens on the same port, so each needs its own loopback address.
+        let first: TcpListener = TcpListener::bind((LOCALHOST, 0))?;
+        let port: u16 = first.local_addr()?.port();
+        let second_address: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2));
+        let second: TcpListener = TcpListener::bind((second_address, port))?;
+        let (_, first_handle) = spawn_tcp_instrument_on(first, |_| None)?;
+        let (_, second_handle) = spawn_tcp_instrument_on(second, |_| None)?;
+
+        let mode: NetworkMode = NetworkMode::TcpMulticast {
+            members: vec![LOCALHOST, second_address],
+            parallel: true,
+        };
+        send_list_of_scpi_messages(&["VOLT 5", "OUTP ON"], &mode, &LOCALHOST, port, 0)?;
+        for handle in [first_handle, second_handle] {
+            assert_eq!(
+                handle.join().expect("Instrument panicked"),
+                vec!["VOLT 5", "OUTP ON"]
+            );
+        }
+
+        // Nothing listens on the second address, but the first still gets the whole list.
+        let first: TcpListener = TcpListener::bind((LOCALHOST, 0))?;
+        let port: u16 = first.local_addr()?.port();
+        let (_, handle) = spawn_tcp_instrument_on(first, |_| None)?;
+        let mode: NetworkMode = NetworkMode::TcpMulticast {
+            members: vec![second_address, LOCALHOST],
+            parallel: false,
+        };
+        match send_list_of_scpi_messages(&["VOLT 5", "OUTP ON"], &mode, &LOCALHOST, port, 0) {
+            Err(ScpiError::Group(failures)) => {
+                assert_eq!(failures.len(), 1);
+                assert_eq!(failures[0].0, SocketAddr::new(second_address, port));
+                assert!(matches!(failures[0].1, ScpiError::Connection(_)));
+            }
+            x => panic!("Expected a group error, got {:?}", x),
+        }
+        assert_eq!(
+            handle.join().expect("Instrument panicked"),
+            vec!["VOLT 5", "OUTP ON"]
+        );
+
+        // Queries and error checks need one answer per instrument, which only TcpMulticastGroup
+        // can return, so they are refused before anything is sent.
+        let (port, handle) = spawn_tcp_instrument(|_| Some(IDN_RESPONSE.to_string()))?;
+        let mode: NetworkMode = NetworkMode::TcpMulticast {
+            members: vec![LOCALHOST],
+            parallel: false,
+        };
+        let mut messenger: Messenger =
+            Messenger::new(0, port, &LOCALHOST, &mode, &ConnectionOptions::default())?;
+        assert!(matches!(
+            messenger.query("*IDN?"),
+            Err(ScpiError::Unsupported(_))
+        ));
+        messenger.set_error_check_mode(ErrorCheckMode::AfterList);
+        assert!(matches!(
+            messenger.send_list_of_messages(&["*RST"]),
+            Err(ScpiError::Unsupported(_))
+        ));
+        messenger.set_error_check_mode(ErrorCheckMode::AfterEachCommand);
+        assert!(matches!(
+            messenger.send_message("*RST"),
+            Err(ScpiError::Unsupported(_))
+        ));
+        messenger.set_error_check_mode(ErrorCheckMode::Disabled);
+        messenger.send_message("OUTP OFF")?;
+        drop(messenger);
+        assert_eq!(
+            handle.join().expect("Instrument panicked"),
+            vec!["OUTP OFF"]
+        );
+
+        Ok(())
+    }
+
+    fn silent_transport(written: &Arc<Mutex<Vec<u8>>>) -> ScriptedTransport {
+        ScriptedTransport {
+            written: Arc::clone(written),
+            responses: VecDeque::new(),
+            timeouts: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    #[test]
+    fn test_repeated_message_limits() -> Result<(), ScpiError> {
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut messenger: Messenger = Messenger::from_transport(
+            Box::new(silent_transport(&written)),
+            &ConnectionOptions::default(),
+        )?;
+
+        let limits: RunLimits = RunLimits::new().with_max_cycles(Some(4));
+        let statistics: RunStatistics =
+            messenger.run_repeated_message("*TRG", &limits, &StopHandle::new())?;
+        assert_eq!(statistics.get_stop_reason(), StopReason::MaxCycles);
+        assert_eq!(statistics.get_cycles(), 4);
+        assert_eq!(statistics.get_messages_sent(), 4);
+        assert_eq!(statistics.get_bytes_sent(), 24);
+        assert_eq!(messenger.send_repeated_message("*TRG", Some(2))?, 6);
+        assert_eq!(
+            written.lock().expect("Written buffer poisoned").as_slice(),
+            "*TRG\r\n".repeat(6).as_bytes()
+        );
+
+        let stop: StopHandle = StopHandle::new();
+        stop.stop();
+        let statistics: RunStatistics =
+            messenger.run_repeated_message("*TRG", &RunLimits::new(), &stop)?;
+        assert_eq!(statistics.get_stop_reason(), StopReason::Stopped);
+        assert_eq!(statistics.get_messages_sent(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_duty_cycle_limits_and_stop() -> Result<(), ScpiError> {
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut messenger: Messenger = Messenger::from_transport(
+            Box::new(silent_transport(&written)),
+            &ConnectionOptions::default(),
+        )?;
+        let message: DutyCycleMessage = DutyCycleMessage::new(2_000, 3_000, "OUTP ON", "OUTP OFF");
+
+        let limits: RunLimits = RunLimits::new().with_max_cycles(Some(3));
+        let statistics: RunStatistics =
+            messenger.run_duty_cycled_message(&message, &limits, &StopHandle::new())?;
+        assert_eq!(statistics.get_stop_reason(), StopReason::MaxCycles);
+        assert_eq!(statistics.get_cycles(), 3);
+        assert_eq!(statistics.get_messages_sent(), 6);
+        assert!(statistics.get_elapsed() >= Duration::from_millis(15));
+
+        let limits: RunLimits = RunLimits::new().with_max_duration(Some(Duration::from_millis(12)));
+        let statistics: RunStatistics =
+            messenger.run_duty_cycled_message(&message, &limits, &StopHandle::new())?;
+        assert_eq!(statistics.get_stop_reason(), StopReason::MaxDuration);
+        assert!(statistics.get_elapsed() >= Duration::from_millis(12));
+        // A loaded machine may not finish any 5 ms cycle in time, but can never finish more than 3.
+        assert!(statistics.get_cycles() <= 3);
+
+        let stop: StopHandle = StopHandle::new();
+        let remote_stop: StopHandle = stop.clone();
+        let runner: JoinHandle<Result<RunStatistics, ScpiError>> = std::thread::spawn(move || {
+            messenger.run_duty_cycled_message(&message, &RunLimits::new(), &remote_stop)
+        });
+        std::thread::sleep(Duration::from_millis(20));
+        stop.stop();
+        let statistics: RunStatistics = runner.join().expect("Duty cycle runner panicked")?;
+        assert_eq!(statistics.get_stop_reason(), StopReason::Stopped);
+        assert!(statistics.get_cycles() >= 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequence_parsing() -> Result<(), ScpiError> {
+        let text: &str = "# warm up\n\
+                          1000 OUTP ON\n\
+                          repeat 2\n\
+                          \t# low\n\
+                          \t100 VOLT 1\n\
+                          \trepeat 3\n\
+                          \t\t50 *TRG\n\
+                          \tend\n\
+                          end\n\
+                          2000 OUTP OFF\n";
+        let sequence: Sequence = text.parse()?;
+
+        let expected: Sequence = Sequence::new()
+            .with_step("OUTP ON", 1000)
+            .with_loop(
+                Sequence::new()
+                    .with_step("VOLT 1", 100)
+                    .with_repeated_step("*TRG", 50, 3),
+                2,
+            )
+            .with_step("OUTP OFF", 2000);
+        assert_eq!(sequence, expected);
+        assert_eq!(sequence.message_count(), 10);
+        assert_eq!(sequence.period(), Duration::from_micros(3_500));
+        assert!(matches!(
+            sequence.get_steps()[1],
+            SequenceStep::Loop { repetitions: 2, .. }
+        ));
+
+        // `#` inside a message is data, not a comment.
+        let hashes: Sequence = "100 STAT:OPER:ENAB #H0100\n\
+                                100 STAT:QUES:ENAB #B101\n\
+                                100 DATA #14abcd\n\
+                                \t# comment after indentation\n"
+            .parse()?;
+        assert_eq!(
+            hashes,
+            Sequence::new()
+                .with_step("STAT:OPER:ENAB #H0100", 100)
+                .with_step("STAT:QUES:ENAB #B101", 100)
+                .with_step("DATA #14abcd", 100)
+        );
+
+        let huge: Sequence =
+            Sequence::new().with_loop(Sequence::new().with_repeated_step("*TRG", 0, 2), usize::MAX);
+        assert_eq!(huge.message_count(), usize::MAX);
+
+        for bad in [
+            "OUTP ON",
+            "100",
+            "repeat two\nend",
+            "end",
+            "repeat 2\n100 *TRG",
+        ] {
+            assert!(
+                matches!(bad.parse::<Sequence>(), Err(ScpiError::Parse(_))),
+                "{:?} should not parse",
+                bad
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_sequence() -> Result<(), ScpiError> {
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut messenger: Messenger = Messenger::from_transport(
+            Box::new(silent_transport(&written)),
+            &ConnectionOptions::default(),
+        )?;
+
+        let sequence: Sequence = Sequence::new()
+            .with_step("A", 0)
+            .with_loop(
+                Sequence::new()
+                    .with_step("B", 0)
+                    .with_repeated_step("C", 0, 2),
+                2,
+            )
+            .with_loop(Sequence::new().with_step("never", 0), 0);
+        let limits: RunLimits = RunLimits::new().with_max_cycles(Some(2));
+        let statistics: RunStatistics =
+            messenger.run_sequence(&sequence, &limits, &StopHandle::new())?;
+        assert_eq!(statistics.get_stop_reason(), StopReason::MaxCycles);
+        assert_eq!(statistics.get_cycles(), 2);
+        assert_eq!(statistics.get_messages_sent(), 14);
+        assert_eq!(
+            written.lock().expect("Written buffer poisoned").as_slice(),
+            "A\r\nB\r\nC\r\nC\r\nB\r\nC\r\nC\r\n".repeat(2).as_bytes()
+        );
+
+        let empty: Sequence = Sequence::new().with_repeated_step("never", 0, 0);
+        assert!(matches!(
+            messenger.run_sequence(&empty, &limits, &StopHandle::new()),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_timing_report() {
+        let mut report: TimingReport = TimingReport::default();
+        assert_eq!(report.get_period_std_dev(), Duration::ZERO);
+
+        for milliseconds in [1, 2, 3] {
+            report.record_period(Duration::from_millis(milliseconds));
+        }
+        report.record_missed_deadline();
+
+        assert_eq!(report.get_period_count(), 3);
+        assert_eq!(report.get_min_period(), Duration::from_millis(1));
+        assert_eq!(report.get_max_period(), Duration::from_millis(3));
+        assert!(
+            report.get_mean_period().abs_diff(Duration::from_millis(2)) < Duration::from_nanos(10)
+        );
+        assert!(
+            report
+                .get_period_std_dev()
+                .abs_diff(Duration::from_secs_f64((2.0f64 / 3.0).sqrt() / 1000.0))
+                < Duration::from_nanos(10)
+        );
+        assert_eq!(report.get_missed_deadlines(), 1);
+    }
+
+    #[test]
+    fn test_timing_strategies() -> Result<(), ScpiError> {
+        let mut messenger: Messenger =
+            Messenger::from_transport(Box::new(SlowTransport), &ConnectionOptions::default())?;
+        let limits: RunLimits = RunLimits::new().with_max_cycles(Some(3));
+
+        // Sleeping most of the hold must still never cut a period short.
+        messenger.set_timing_strategy(TimingStrategy::hybrid());
+        let pulses: Sequence = Sequence::new().with_step("FAST", 3_000);
+        let statistics: RunStatistics =
+            messenger.run_sequence(&pulses, &limits, &StopHandle::new())?;
+        assert_eq!(statistics.get_timing().get_period_count(), 2);
+        assert!(statistics.get_timing().get_min_period() >= Duration::from_millis(3));
+        assert_eq!(statistics.get_timing().get_missed_deadlines(), 0);
+
+        // The slow write overruns its 1 ms hold every cycle. Relative holds then add up to at least
+        // 20 + 40 ms per cycle, while absolute deadlines catch up during the 40 ms hold and keep
+        // each cycle at 41 ms. The margins are wide so a loaded machine cannot blur the difference.
+        let overrun: Sequence = Sequence::new()
+            .with_step("SLOW", 1_000)
+            .with_step("FAST", 40_000);
+
+        messenger.set_timing_strategy(TimingStrategy::Spin);
+        let relative: RunStatistics =
+            messenger.run_sequence(&overrun, &limits, &StopHandle::new())?;
+        assert_eq!(relative.get_timing().get_missed_deadlines(), 3);
+        assert!(relative.get_timing().get_min_period() >= SLOW_WRITE + Duration::from_millis(40));
+
+        messenger.set_timing_strategy(TimingStrategy::absolute_deadline());
+        assert_eq!(
+            messenger.get_timing_strategy(),
+            TimingStrategy::AbsoluteDeadline {
+                spin_window: Duration::from_micros(200)
+            }
+        );
+        let absolute: RunStatistics =
+            messenger.run_sequence(&overrun, &limits, &StopHandle::new())?;
+        assert_eq!(absolute.get_timing().get_missed_deadlines(), 3);
+        assert!(absolute.get_elapsed() >= Duration::from_millis(123));
+        assert!(absolute.get_elapsed() < relative.get_elapsed());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_background_duty_cycle() -> Result<(), ScpiError> {
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut messenger: Messenger = Messenger::from_transport(
+            Box::new(silent_transport(&written)),
+            &ConnectionOptions::default(),
+        )?;
+        let message: DutyCycleMessage = DutyCycleMessage::new(500, 500, "OUTP ON", "OUTP OFF");
+
+        let handle: DutyCycleHandle = messenger.start_duty_cycle(&message, &RunLimits::new())?;
+        assert!(handle.is_running());
+        assert!(messenger.get_duty_cycle_handle().is_some());
+        assert!(matches!(
+            messenger.send_message("*CLS"),
+            Err(ScpiError::Unsupported(_))
+        ));
+        assert!(matches!(
+            messenger.start_duty_cycle(&message, &RunLimits::new()),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+
+        handle.set_messages("VOLT 1", "VOLT 0");
+        handle.set_times(200, 300);
+        let changed: Instant = Instant::now();
+        while !String::from_utf8_lossy(&written.lock().expect("Written buffer poisoned"))
+            .contains("VOLT 0")
+        {
+            assert!(
+                changed.elapsed() < Duration::from_secs(5),
+                "Runner ignored the new messages"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+
+        let statistics: RunStatistics = messenger.stop_duty_cycle()?;
+        assert!(!handle.is_running());
+        assert_eq!(statistics.get_stop_reason(), StopReason::Stopped);
+        assert!(messenger.get_duty_cycle_handle().is_none());
+        assert_eq!(messenger.send_message("*CLS")?, 6);
+        assert!(written
+            .lock()
+            .expect("Written buffer poisoned")
+            .ends_with(b"*CLS\r\n"));
+
+        let limits: RunLimits = RunLimits::new().with_max_cycles(Some(2));
+        let handle: DutyCycleHandle = messenger.start_duty_cycle(&message, &limits)?;
+        let started: Instant = Instant::now();
+        while handle.is_running() {
+            assert!(
+                started.elapsed() < Duration::from_secs(5),
+                "Runner ignored its limits"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let statistics: RunStatistics = messenger.stop_duty_cycle()?;
+        assert_eq!(statistics.get_stop_reason(), StopReason::MaxCycles);
+        assert_eq!(statistics.get_messages_sent(), 4);
+        assert!(matches!(
+            messenger.stop_duty_cycle(),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+
+        // No thread can get a stack this large, and the connection must survive the failed spawn.
+        let builder: std::thread::Builder = std::thread::Builder::new().stack_size(1 << 62);
+        assert!(matches!(
+            messenger.start_duty_cycle_on(builder, &message, &limits),
+            Err(ScpiError::Connection(_))
+        ));
+        assert!(messenger.get_duty_cycle_handle().is_none());
+        assert_eq!(messenger.send_message("*RST")?, 6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_parameters() -> Result<(), ScpiError> {
+        let cases: [(Parameter, &str); 14] = [
+            (Parameter::Nr1(-42), "-42"),
+            (Parameter::Nr2(3.3), "3.3"),
+            (Parameter::Nr2(1e20), "100000000000000000000"),
+            (Parameter::Nr3(0.0033), "3.3E-3"),
+            (Parameter::Nr3(f64::NAN), "NAN"),
+            (Parameter::Nr2(f64::INFINITY), "INF"),
+            (Parameter::Nr3(f64::NEG_INFINITY), "NINF"),
+            (Parameter::Boolean(true), "ON"),
+            (Parameter::Boolean(false), "OFF"),
+            (Parameter::Character("MAX".to_string()), "MAX"),
+            (
+                Parameter::String("say \"hi\"".to_string()),
+                "\"say \"\"hi\"\"\"",
+            ),
+            (
+                Parameter::ChannelList(ChannelList::new().with_channel(1).with_range(2, 5)),
+                "(@1,2:5)",
+            ),
+            (Parameter::Nr2(100.0).with_unit("mV"), "100mV"),
+            (Parameter::Nr1(10).with_unit("KHZ"), "10KHZ"),
+        ];
+        for (parameter, expected) in cases {
+            assert_eq!(parameter.format()?, expected);
+        }
+
+        let invalid: [Parameter; 5] = [
+            Parameter::Character("1MAX".to_string()),
+            Parameter::Character("MUCH_TOO_LONG_".to_string()),
+            Parameter::ChannelList(ChannelList::new()),
+            Parameter::Boolean(true).with_unit("V"),
+            Parameter::Nr2(1.0).with_unit("m V"),
+        ];
+        for parameter in invalid {
+            assert!(
+                matches!(parameter.format(), Err(ScpiError::InvalidArgument(_))),
+                "{:?} should be rejected",
+                parameter
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_command_builder() -> Result<(), ScpiError> {
+        let command: Command = Command::new("SOUR:VOLT")
+            .with_parameter(Parameter::Nr2(3.3).with_unit("V"))
+            .with_character("DEF");
+        assert_eq!(command.build()?, "SOUR:VOLT 3.3V,DEF");
+        assert_eq!(
+            Command::query("MEAS:VOLT").with_character("AC").build()?,
+            "MEAS:VOLT? AC"
+        );
+        assert_eq!(Command::query("*IDN").build()?, "*IDN?");
+        assert!(matches!(
+            Command::new("SOUR:VOLT 3").build(),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+
+        let compound: CompoundCommand = CompoundCommand::new()
+            .with_command(Command::new("SOUR:VOLT").with_nr2(1.5))
+            .with_command(Command::new("CURR").with_nr2(0.1))
+            .with_root_command(Command::new("OUTP").with_boolean(true))
+            .with_root_command(Command::new("*OPC"))
+            .with_root_command(Command::new(":SYST:BEEP"));
+        assert_eq!(
+            compound.build()?,
+            "SOUR:VOLT 1.5;CURR 0.1;:OUTP ON;*OPC;:SYST:BEEP"
+        );
+        assert!(!compound.is_query());
+        assert!(matches!(
+            CompoundCommand::new().build(),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut messenger: Messenger = Messenger::from_transport(
+            Box::new(silent_transport(&written)),
+            &ConnectionOptions::default(),
+        )?;
+        messenger.send_command(&command)?;
+        assert!(messenger
+            .send_command(&Command::new("OUTP").with_character("2FAST"))
+            .is_err());
+        assert_eq!(
+            written.lock().expect("Written buffer poisoned").as_slice(),
+            b"SOUR:VOLT 3.3V,DEF\r\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_numbers() -> Result<(), ScpiError> {
+        assert_eq!(parse_integer(" +42\n")?, 42);
+        assert_eq!(parse_integer("+1.00000000E+01")?, 10);
+        assert_eq!(parse_integer("#HFF")?, 255);
+        assert_eq!(parse_integer("#Q17")?, 15);
+        assert_eq!(parse_integer("#b101")?, 5);
+        assert!(matches!(parse_integer("1.5"), Err(ScpiError::Parse(_))));
+
+        assert_eq!(parse_float("3.3")?, 3.3);
+        assert_eq!(parse_float("-1.234E-03")?, -0.001234);
+        assert_eq!(parse_float("+9.90000000E+37")?, f64::INFINITY);
+        assert_eq!(parse_float("-9.9E37")?, f64::NEG_INFINITY);
+        assert!(parse_float("9.91E37")?.is_nan());
+        assert!(parse_float("NAN")?.is_nan());
+        assert_eq!(parse_float("ninf")?, f64::NEG_INFINITY);
+        for bad in ["", "inf inity", "infinity", "1,2", "VOLT"] {
+            assert!(
+                matches!(parse_float(bad), Err(ScpiError::Parse(_))),
+                "{:?} should not parse",
+                bad
+            );
+        }
+
+        assert!(parse_boolean("1")?);
+        assert!(!parse_boolean("0")?);
+        assert!(parse_boolean("on")?);
+        assert!(!parse_boolean("OFF")?);
+        assert!(parse_boolean("+1.0E+00")?);
+        assert!(matches!(parse_boolean("MAYBE"), Err(ScpiError::Parse(_))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_text_and_lists() -> Result<(), ScpiError> {
+        assert_eq!(parse_string("\"say \"\"hi\"\"\"")?, "say \"hi\"");
+        assert_eq!(parse_string("'it''s'")?, "it's");
+        assert!(matches!(parse_string("VOLT"), Err(ScpiError::Parse(_))));
+        assert!(matches!(parse_string("\"a\"b\""), Err(ScpiError::Parse(_))));
+
+        assert_eq!(
+            split_list("1,\"a,b\",(@1,2:3), 4")?,
+            vec!["1", "\"a,b\"", "(@1,2:3)", "4"]
+        );
+        assert!(split_list("")?.is_empty());
+        assert!(matches!(split_list("\"open,1"), Err(ScpiError::Parse(_))));
+
+        let channels: ChannelList = parse_channel_list("(@1,2:5,9:7)")?;
+        assert_eq!(
+            channels,
+            ChannelList::new()
+                .with_channel(1)
+                .with_range(2, 5)
+                .with_range(9, 7)
+        );
+        assert_eq!(channels.channels(), vec![1, 2, 3, 4, 5, 9, 8, 7]);
+        assert!(matches!(
+            parse_channel_list("(1,2)"),
+            Err(ScpiError::Parse(_))
+        ));
+
+        assert_eq!(parse_value("42")?, ResponseValue::Integer(42));
+        assert_eq!(
+            parse_value("VOLT")?,
+            ResponseValue::Character("VOLT".to_string())
+        );
+        assert_eq!(
+            parse_value("+1.5E+00,-3,\"x\",BUS,(@2)")?,
+            ResponseValue::List(vec![
+                ResponseValue::Float(1.5),
+                ResponseValue::Integer(-3),
+                ResponseValue::String("x".to_string()),
+                ResponseValue::Character("BUS".to_string()),
+                ResponseValue::ChannelList(ChannelList::new().with_channel(2)),
+            ])
+        );
+
+        assert_eq!(parse_block(b"#15hello\n")?, b"hello");
+        assert_eq!(parse_block(b"#0raw\n")?, b"raw");
+        assert!(matches!(parse_block(b"#19short"), Err(ScpiError::Parse(_))));
+
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut transport: ScriptedTransport = silent_transport(&written);
+        transport.responses =
+            VecDeque::from(vec![b"+2.50000000E+00\n".to_vec(), b"1,2,3\n".to_vec()]);
+        let mut messenger: Messenger =
+            Messenger::from_transport(Box::new(transport), &ConnectionOptions::default())?;
+        assert_eq!(messenger.query_as::<f64>("MEAS:VOLT?")?, 2.5);
+        assert_eq!(messenger.query_as::<Vec<i64>>("DATA?")?, vec![1, 2, 3]);
+
+        Ok(())
+    }
+
+    const POWER_SUPPLY_TREE: &str = "# A small power supply\n\
+                                     *RST\n\
+                                     *IDN?\n\
+                                     [SOURce]:VOLTage[:LEVel]\n\
+                                     [SOURce]:CURRent[:LEVel]\n\
+                                     OUTPut<n>[:STATe]\n\
+                                     MEASure<n>:VOLTage?  # query only\n";
+
+    #[test]
+    fn test_command_tree_normalization() -> Result<(), ScpiError> {
+        let tree: CommandTree = POWER_SUPPLY_TREE.parse()?;
+
+        assert_eq!(
+            tree.normalize("volt 3.3", MnemonicForm::Long)?,
+            "VOLTAGE 3.3"
+        );
+        assert_eq!(
+            tree.normalize("SOURCE:VOLTAGE:LEVEL 1", MnemonicForm::Short)?,
+            "SOUR:VOLT:LEV 1"
+        );
+        assert_eq!(
+            tree.normalize("meas2:volt?", MnemonicForm::Long)?,
+            "MEASURE2:VOLTAGE?"
+        );
+        assert_eq!(
+            tree.normalize("OUTP2 ON;:*RST", MnemonicForm::AsWritten)?,
+            "OUTP2 ON;:*RST"
+        );
+        assert_eq!(
+            tree.normalize(
+                "SOUR:VOLT 1.5;CURR 0.1;*IDN?;CURRENT:LEVEL 2;:OUTPUT ON",
+                MnemonicForm::Short
+            )?,
+            "SOUR:VOLT 1.5;CURR 0.1;*IDN?;CURR:LEV 2;:OUTP ON"
+        );
+        assert_eq!(
+            tree.normalize("VOLT 1;SYST:TEXT \"a;b\"", MnemonicForm::Short)
+                .map_err(|x| x.to_string()),
+            Err("Invalid argument: Unknown command header 'SYST:TEXT \"a;b\"'".to_string())
+        );
+
+        for bad in [
+            "VOLTT 3",
+            "VOLTA 3",
+            "MEAS:VOLT",
+            "OUTP:STAT:MODE ON",
+            "SOUR 1",
+            "*TST?",
+        ] {
+            assert!(
+                matches!(tree.validate(bad), Err(ScpiError::InvalidArgument(_))),
+                "{:?} should be rejected",
+                bad
+            );
+        }
+
+        for bad in ["volt", "[SOUR:VOLT", "SOUR]:VOLT", "[SOURce]"] {
+            assert!(
+                matches!(
+                    CommandTree::new().with_header(bad),
+                    Err(ScpiError::Parse(_))
+                ),
+                "{:?} should not parse",
+                bad
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_messenger_command_tree() -> Result<(), ScpiError> {
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut transport: ScriptedTransport = silent_transport(&written);
+        transport.responses = VecDeque::from(vec![b"1.25\n".to_vec()]);
+        let mut messenger: Messenger =
+            Messenger::from_transport(Box::new(transport), &ConnectionOptions::default())?;
+
+        messenger.send_message("VOLTT 3")?;
+        messenger.set_command_tree(Some(POWER_SUPPLY_TREE.parse()?));
+        messenger.set_mnemonic_form(MnemonicForm::Long);
+        assert!(matches!(
+            messenger.send_message("VOLTT 3"),
+            Err(ScpiError::InvalidArgument(_))
+        ));
+        messenger.send_message("volt 3")?;
+        assert_eq!(messenger.query("MEAS:VOLT?")?, "1.25");
+
+        assert_eq!(
+            written.lock().expect("Written buffer poisoned").as_slice(),
+            b"VOLTT 3\r\nVOLTAGE 3\r\nMEASURE:VOLTAGE?\r\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_common_command_results() -> Result<(), ScpiError> {
+        let identification: Identification =
+            Identification::parse(" Keysight Technologies,34461A,MY5300,A.02.14-02.40-02.14\n")?;
+        assert_eq!(identification.get_manufacturer(), "Keysight Technologies");
+        assert_eq!(identification.get_model(), "34461A");
+        assert_eq!(identification.get_serial_number(), "MY5300");
+        assert_eq!(identification.get_firmware(), "A.02.14-02.40-02.14");
+        assert_eq!(
+            Identification::parse(IDN_RESPONSE)?.to_string(),
+            IDN_RESPONSE
+        );
+        assert!(matches!(
+            Identification::parse("PySCPI,Loopback"),
+            Err(ScpiError::Parse(_))
+        ));
+
+        let status: StandardEventStatus = StandardEventStatus::new(0b1010_0001);
+        assert!(status.operation_complete());
+        assert!(status.command_error());
+        assert!(status.power_on());
+        assert!(!status.execution_error());
+        assert!(status.has_errors());
+        assert!(!StandardEventStatus::new(StandardEventStatus::USER_REQUEST).has_errors());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_common_commands() -> Result<(), ScpiError> {
+        let written: Arc<Mutex<Vec<u8>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut transport: ScriptedTransport = silent_transport(&written);
+        transport.responses = [
+            RAW_IDN_RESPONSE,
+            b"1\n",
+            b"0\n",
+            b"+32\n",
+            b"96\n",
+            b"256\n",
+        ]
+        .iter()
+        .map(|x| x.to_vec())
+        .collect();
+        let mut messenger: Messenger =
+            Messenger::from_transport(Box::new(transport), &ConnectionOptions::default())?;
+        // An empty tree rejects everything, but common commands never go through it.
+        messenger.set_command_tree(Some(CommandTree::new()));
+
+        assert_eq!(messenger.identify()?, Identification::parse(IDN_RESPONSE)?);
+        messenger.reset()?;
+        messenger.clear_status()?;
+        messenger.wait_to_continue()?;
+        assert!(messenger.query_operation_complete()?);
+        assert_eq!(messenger.self_test()?, 0);
+        assert_eq!(
+            messenger.query_event_status()?,
+            StandardEventStatus::new(StandardEventStatus::COMMAND_ERROR)
+        );
+        let status_byte: StatusByte = messenger.query_status_byte()?;
+        assert_eq!(status_byte, StatusByte::new(96));
+        assert!(status_byte.event_summary() && status_byte.request_service());
+        assert!(!status_byte.message_available());
+        assert!(matches!(
+            messenger.query_status_byte(),
+            Err(ScpiError::Parse(_))
+        ));
+
+        assert_eq!(
+            written.lock().expect("Written buffer poisoned").as_slice(),
+            b"*IDN?\r\n*RST\r\n*CLS\r\n*WAI\r\n*OPC?\r\n*TST?\r\n*ESR?\r\n*STB?\r\n*STB?\r\n"
+        );
+
+        Ok(())
+    }
+
+    /// An instrument whose `INIT` takes `SWEEP_TIME` and whose `HANG` never finishes, with just
+    /// enough of the status system for the completion methods.
+    fn spawn_sweeping_instrument() -> Result<(u16, JoinHandle<Vec<String>>), Error> {
+        const SWEEP_TIME: Duration = Duration::from_millis(30);
+
+        let mut busy_until: Instant = Instant::now();
+        let mut opc_armed: bool = false;
+        let mut esr: u8 = 0;
+        let mut ese: u8 = 4;
+
+        spawn_tcp_instrument(move |command| {
+            if opc_armed && Instant::now() >= busy_until {
+                esr |= StandardEventStatus::OPERATION_COMPLETE;
+                opc_armed = false;
+            }
+
+            match command {
+                "INIT" => busy_until = Instant::now() + SWEEP_TIME,
+                "HANG" => busy_until = Instant::now() + Duration::from_secs(3600),
+                "*OPC" => opc_armed = true,
+                "*OPC?" => {
+                    // A hung operation never completes, so the query goes unanswered.
+                    let remaining: Duration = busy_until.saturating_duration_since(Instant::now());
+                    if remaining > SWEEP_TIME {
+                        return None;
+                    }
+                    std::thread::sleep(remaining);
+                    return Some("1".to_string());
+                }
+                "*ESR?" => return Some(std::mem::take(&mut esr).to_string()),
+                "*ESE?" => return Some(ese.to_string()),
+                "*STB?" => {
+                    let summary: u8 = match esr & ese {
+                        0 => 0,
+                        _ => 32,
+                    };
+                    return Some(summary.to_string());
+                }
+                x => ese = x.strip_prefix("*ESE ")?.parse().ok()?,
+            }
+            None
+        })
+    }
+
+    #[test]
+    fn test_send_and_wait() -> Result<(), ScpiError> {
+        let (port, handle): (u16, JoinHandle<Vec<String>>) = spawn_sweeping_instrument()?;
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+
+        for method in [
+            CompletionMethod::OperationCompleteQuery,
+            CompletionMethod::EventStatusPolling,
+            CompletionMethod::StatusBytePolling,
+        ] {
+            let options: CompletionOptions = CompletionOptions::new()
+                .with_method(method)
+                .with_poll_interval(Duration::from_millis(5));
+            let start: Instant = Instant::now();
+            messenger.send_and_wait("INIT", &options)?;
+            assert!(
+                start.elapsed() >= Duration::from_millis(30),
+                "{:?} returned early",
+                method
+            );
+        }
+        assert_eq!(messenger.query("*ESE?")?, "4");
+
+        let options: CompletionOptions = CompletionOptions::new()
+            .with_method(CompletionMethod::EventStatusPolling)
+            .with_timeout(Duration::from_millis(50))
+            .with_poll_interval(Duration::from_millis(5));
+        assert!(matches!(
+            messenger.send_and_wait("HANG", &options),
+            Err(ScpiError::Timeout)
+        ));
+
+        let options: CompletionOptions =
+            options.with_method(CompletionMethod::OperationCompleteQuery);
+        assert!(matches!(
+            messenger.send_list_of_messages_and_wait(&["INIT", "HANG"], &options),
+            Err(ScpiError::Timeout)
+        ));
+
+        drop(messenger);
+        let received: Vec<String> = handle.join().expect("Instrument thread panicked");
+        assert_eq!(received[..5], ["INIT", "*OPC?", "*ESR?", "INIT", "*OPC"]);
+        let ese_writes: Vec<&String> = received.iter().filter(|x| x.starts_with("*ESE ")).collect();
+        assert_eq!(ese_writes, ["*ESE 5", "*ESE 4"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_late_response_is_discarded() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_tcp_instrument(|command| match command {
+            "MEAS:VOLT?" => {
+                std::thread::sleep(Duration::from_millis(150));
+                Some("1.5".to_string())
+            }
+            "MEAS:CURR?" => Some("0.25".to_string()),
+            "*OPC?" => {
+                std::thread::sleep(Duration::from_millis(150));
+                Some("1".to_string())
+            }
+            _ => None,
+        })?;
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+
+        assert!(matches!(
+            messenger.query_with_timeout("MEAS:VOLT?", Duration::from_millis(50)),
+            Err(ScpiError::Timeout)
+        ));
+        std::thread::sleep(Duration::from_millis(250));
+        assert_eq!(messenger.query("MEAS:CURR?")?, "0.25");
+
+        let options: CompletionOptions = CompletionOptions::new()
+            .with_method(CompletionMethod::OperationCompleteQuery)
+            .with_timeout(Duration::from_millis(50));
+        assert!(matches!(
+            messenger.send_and_wait("INIT", &options),
+            Err(ScpiError::Timeout)
+        ));
+        std::thread::sleep(Duration::from_millis(250));
+        assert_eq!(messenger.query("MEAS:CURR?")?, "0.25");
+
+        drop(messenger);
+        let received: Vec<String> = handle.join().expect("Instrument thread panicked");
+        assert_eq!(
+            received,
+            ["MEAS:VOLT?", "MEAS:CURR?", "INIT", "*OPC?", "MEAS:CURR?"]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_status_registers() -> Result<(), ScpiError> {
+        let (port, handle): (u16, JoinHandle<Vec<String>>) =
+            spawn_tcp_instrument(|command| match command {
+                "*ESE?" => Some("60".to_string()),
+                "*SRE?" => Some("32".to_string()),
+                "STAT:OPER:COND?" => Some("8".to_string()),
+                "STAT:OPER:EVEN?" => Some("24".to_string()),
+                "STAT:OPER:ENAB?" => Some("16".to_string()),
+                "STAT:OPER:PTR?" => Some("32767".to_string()),
+                "STAT:OPER:NTR?" => Some("0".to_string()),
+                "STAT:QUES:COND?" => Some("70000".to_string()),
+                _ => None,
+            })?;
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+        messenger.set_command_tree(Some(CommandTree::new()));
+
+        messenger.set_event_status_enable(StandardEventStatus::ERRORS)?;
+        assert_eq!(messenger.query_event_status_enable()?, 60);
+        messenger.set_service_request_enable(StatusByte::EVENT_SUMMARY)?;
+        assert_eq!(messenger.query_service_request_enable()?, 32);
+        messenger.preset_status()?;
+        messenger.set_status_enable(StatusRegisterGroup::Operation, OPERATION_MEASURING)?;
+        messenger.set_status_transitions(StatusRegisterGroup::Questionable, 3, 1)?;
+        assert_eq!(
+            messenger.query_status_condition(StatusRegisterGroup::Operation)?,
+            OPERATION_SWEEPING
+        );
+        assert!(matches!(
+            messenger.query_status_condition(StatusRegisterGroup::Questionable),
+            Err(ScpiError::Parse(_))
+        ));
+
+        let register: StatusRegister =
+            messenger.query_status_register(StatusRegisterGroup::Operation)?;
+        assert_eq!(
+            register,
+            StatusRegister::new(StatusRegisterGroup::Operation)
+                .with_condition(OPERATION_SWEEPING)
+                .with_event(OPERATION_SWEEPING | OPERATION_MEASURING)
+                .with_enable(OPERATION_MEASURING)
+                .with_transitions(0x7FFF, 0)
+        );
+        assert_eq!(register.pending_events(), OPERATION_MEASURING);
+        assert!(register.summary());
+        assert_eq!(
+            register.get_group().get_summary_bit(),
+            StatusByte::OPERATION_SUMMARY
+        );
+
+        drop(messenger);
+        let received: Vec<String> = handle.join().expect("Instrument thread panicked");
+        assert_eq!(
+            received[..8],
+            [
+                "*ESE 60",
+                "*ESE?",
+                "*SRE 32",
+                "*SRE?",
+                "STAT:PRES",
+                "STAT:OPER:ENAB 16",
+                "STAT:QUES:PTR 3",
+                "STAT:QUES:NTR 1",
+            ]
+        );
+        assert_eq!(
+            received[10..],
+            [
+                "STAT:OPER:COND?",
+                "STAT:OPER:EVEN?",
+                "STAT:OPER:ENAB?",
+                "STAT:OPER:PTR?",
+                "STAT:OPER:NTR?",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_service_request_polling() -> Result<(), ScpiError> {
+        let mut polls: usize = 0;
+        let (port, handle): (u16, JoinHandle<Vec<String>>) =
+            spawn_tcp_instrument(move |command| match command {
+                "*STB?" => {
+                    polls += 1;
+                    match polls {
+                        1..=2 => Some("16".to_string()),
+                        3 => Some("96".to_string()),
+                        _ => Some("0".to_string()),
+                    }
+                }
+                _ => None,
+            })?;
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )?;
+
+        let status_byte: StatusByte =
+            messenger.wait_for_service_request(Duration::from_secs(5), Duration::from_millis(1))?;
+        assert!(status_byte.request_service() && status_byte.event_summary());
+        assert!(matches!(
+            messenger.wait_for_service_request(Duration::from_millis(20), Duration::from_millis(5)),
+            Err(ScpiError::Timeout)
+        ));
+
+        drop(messenger);
+        let received: Vec<String> = handle.join().expect("Instrument thread panicked");
+        assert!(received.len() > 4);
+        assert!(received.iter().all(|x| x == "*STB?"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vxi11_service_request() -> Result<(), ScpiError> {
+        let (portmapper_port, handle) = spawn_vxi11_instrument(|command| match command {
+            "*STB?" => Some("64".to_string()),
+            _ => None,
+        })?;
+
+        let mut client: Vxi11Client =
+            Vxi11Client::connect_with_portmapper(&LOCALHOST, portmapper_port, "inst0", None)?;
+        assert!(!client.wait_for_service_request(Duration::from_millis(20))?);
+        client.device_write(b"*TRG\n")?;
+        assert!(client.wait_for_service_request(Duration::from_secs(5))?);
+
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
+        let mut messenger: Messenger = Messenger::from_transport(Box::new(client), &options)?;
+        messenger.send_message("*TRG")?;
+        let status_byte: StatusByte =
+            messenger.wait_for_service_request(Duration::from_secs(5), Duration::from_secs(5))?;
+        assert!(status_byte.request_service());
+        drop(messenger);
+
+        let log: Vec<String> = handle.join().expect("VXI-11 server panicked");
+        assert_eq!(
+            log,
+            vec![
+                "create_link:inst0",
+                "create_intr_chan",
+                "enable_srq:true",
+                "write[1]:*TRG",
+                "write[1]:*TRG",
+                "write[1]:*STB?",
+                "read",
+                "destroy_intr_chan",
+                "destroy_link",
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vxi11_service_request_without_interrupt_channel() -> Result<(), ScpiError> {
+        // Servers refuse as "operation not supported" or "channel not established".
+        for error in [8, 6] {
+            let (portmapper_port, handle) =
+                spawn_vxi11_instrument_with(error, |command| match command {
+                    "*STB?" => Some("64".to_string()),
+                    _ => None,
+                })?;
+
+            let mut client: Vxi11Client =
+                Vxi11Client::connect_with_portmapper(&LOCALHOST, portmapper_port, "inst0", None)?;
+            assert!(matches!(
+                client.enable_srq(true),
+                Err(ScpiError::Unsupported(_))
+            ));
+
+            let options: ConnectionOptions =
+                ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
+            let mut messenger: Messenger = Messenger::from_transport(Box::new(client), &options)?;
+            let status_byte: StatusByte = messenger
+                .wait_for_service_request(Duration::from_secs(5), Duration::from_millis(1))?;
+            assert!(status_byte.request_service());
+            drop(messenger);
+
+            let log: Vec<String> = handle.join().expect("VXI-11 server panicked");
+            let refused: String = format!("create_intr_chan:{}", error);
+            assert_eq!(
+                log,
+                vec![
+                    "create_link:inst0",
+                    &refused,
+                    &refused,
+                    "write[1]:*STB?",
+                    "read",
+                    "destroy_link",
+                ]
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_hislip_service_request() -> Result<(), ScpiError> {
+        let (port, handle) = spawn_hislip_instrument(|command| match command {
+            "*STB?" => Some("64".to_string()),
+            "*IDN?" => Some(IDN_RESPONSE.to_string()),
+            _ => None,
+        })?;
+
+        let mut client: HislipClient =
+            HislipClient::connect((LOCALHOST, port).into(), "hislip0", None)?;
+        assert!(!client.wait_for_service_request(Duration::from_millis(20))?);
+
+        // A request that arrives during another asynchronous exchange is kept for later.
+        client.write_message(b"*TRG\n")?;
+        std::thread::sleep(Duration::from_millis(20));
+        client.remote_local(RemoteLocalControl::EnableRemote)?;
+        assert!(client.wait_for_service_request(Duration::from_millis(20))?);
+        assert!(!client.wait_for_service_request(Duration::from_millis(20))?);
+
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
+        let mut messenger: Messenger = Messenger::from_transport(Box::new(client), &options)?;
+        messenger.send_message("*TRG")?;
+        let status_byte: StatusByte =
+            messenger.wait_for_service_request(Duration::from_secs(5), Duration::from_secs(5))?;
+        assert!(status_byte.request_service());
+        assert_eq!(messenger.query("*IDN?")?, IDN_RESPONSE);
+        drop(messenger);
+
+        let log: Vec<String> = handle.join().expect("HiSLIP server panicked");
+        assert_eq!(
+            log[1..3],
+            ["data[1,0xffffff00,rmt=false]:*TRG", "remote_local:1"]
+        );
+
+        Ok(())
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq)]
+    struct SimulatedSupply {
+        voltage: f64,
+        outputs: [bool; 2],
+        display: String,
+    }
+
+    fn simulated_supply() -> Result<Simulator<SimulatedSupply>, ScpiError> {
+        Simulator::new(
+            Identification::parse(IDN_RESPONSE)?,
+            SimulatedSupply::default(),
         )
-        .is_ok());
+        .with_command(
+            "[SOURce]:VOLTage[:LEVel]",
+            &[ParameterType::Float],
+            |supply, call| match call.get_float(0)? {
+                x if (0.0..=30.0).contains(&x) => {
+                    supply.voltage = x;
+                    Ok(())
+                }
+                _ => Err(InstrumentError::new(-222, "Data out of range")),
+            },
+        )?
+        .with_query("[SOURce]:VOLTage[:LEVel]", &[], |supply, _| {
+            Ok(supply.voltage.to_string())
+        })?
+        .with_command(
+            "OUTPut<n>[:STATe]",
+            &[ParameterType::Boolean],
+            |supply, call| {
+                let output: &mut bool = supply
+                    .outputs
+                    .get_mut(call.get_suffix(0) as usize - 1)
+                    .ok_or_else(|| InstrumentError::new(-114, "Header suffix out of range"))?;
+                *output = call.get_bool(0)?;
+                Ok(())
+            },
+        )?
+        .with_query("OUTPut<n>[:STATe]", &[], |supply, call| {
+            let output: bool = supply.outputs[call.get_suffix(0) as usize - 1];
+            Ok((output as u8).to_string())
+        })?
+        .with_command("DISPlay:TEXT", &[ParameterType::String], |supply, call| {
+            supply.display = call.get_text(0)?.to_string();
+            Ok(())
+        })
+    }
+
+    #[test]
+    fn test_simulator_parsing() -> Result<(), ScpiError> {
+        let mut simulator: Simulator<SimulatedSupply> = simulated_supply()?;
+
+        assert_eq!(simulator.process("*IDN?").as_deref(), Some(IDN_RESPONSE));
+        assert_eq!(
+            simulator.process("SOURCE:VOLTAGE:LEVEL 2.5;:outp2 on"),
+            None
+        );
+        assert_eq!(simulator.process("volt:lev 3;lev?").as_deref(), Some("3"));
+        assert_eq!(
+            simulator
+                .process("VOLT?;:OUTP2?;:OUTPUT1:STATE?")
+                .as_deref(),
+            Some("3;1;0")
+        );
+        assert_eq!(simulator.process("DISP:TEXT 'Hello, ''world'''"), None);
+        assert_eq!(
+            *simulator.get_state(),
+            SimulatedSupply {
+                voltage: 3.0,
+                outputs: [false, true],
+                display: "Hello, 'world'".to_string(),
+            }
+        );
+
+        // Execution stops at the first bad unit of a compound message.
+        assert_eq!(simulator.process("VOLT 1;VOLT:BOGUS 2;VOLT 4"), None);
+        assert_eq!(simulator.get_state().voltage, 1.0);
+        for message in [
+            "VOLT ABC",
+            "VOLT",
+            "VOLT 1,2",
+            "VOLT 100",
+            "OUTP3 ON",
+            "DISP:TEXT?",
+        ] {
+            assert_eq!(simulator.process(message), None);
+        }
+        assert_eq!(
+            simulator
+                .get_errors()
+                .iter()
+                .map(InstrumentError::to_string)
+                .collect::<Vec<String>>(),
+            [
+                "-113,\"Undefined header\"",
+                "-104,\"Data type error\"",
+                "-109,\"Missing parameter\"",
+                "-108,\"Parameter not allowed\"",
+                "-222,\"Data out of range\"",
+                "-114,\"Header suffix out of range\"",
+                "-113,\"Undefined header\"",
+            ]
+        );
+        assert_eq!(simulator.process("SYST:ERR:COUN?").as_deref(), Some("7"));
+        assert_eq!(
+            simulator.process("SYSTEM:ERROR:NEXT?").as_deref(),
+            Some("-113,\"Undefined header\"")
+        );
+
+        for _ in 0..30 {
+            simulator.process("BOGUS");
+        }
+        assert_eq!(simulator.get_errors().len(), 20);
+        assert_eq!(
+            simulator.get_errors().last(),
+            Some(&InstrumentError::new(-350, "Queue overflow"))
+        );
+
+        simulator.process("*RST;*CLS");
+        assert_eq!(*simulator.get_state(), SimulatedSupply::default());
+        assert!(simulator.get_errors().is_empty());
+        assert_eq!(
+            simulator.process("SYST:ERR?").as_deref(),
+            Some("0,\"No error\"")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_simulator_over_tcp() -> Result<(), ScpiError> {
+        let instrument: MockInstrument<Simulator<SimulatedSupply>> =
+            MockInstrument::start(&NetworkMode::Tcp, simulated_supply()?)?;
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_secs(5)));
+        let mut messenger: Messenger = Messenger::new(
+            0,
+            instrument.get_port(),
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &options,
+        )?;
+
+        assert_eq!(messenger.identify()?, Identification::parse(IDN_RESPONSE)?);
+        messenger.send_list_of_messages(&["VOLT 12.5", "OUTP1 ON"])?;
+        assert_eq!(messenger.query_as::<f64>("SOUR:VOLT?")?, 12.5);
+        assert!(messenger.query_as::<bool>("OUTP?")?);
+
+        messenger.set_event_status_enable(StandardEventStatus::ERRORS)?;
+        messenger.set_service_request_enable(StatusByte::EVENT_SUMMARY)?;
+        messenger.send_message("FOO:BAR 1")?;
+        let status_byte: StatusByte = messenger.query_status_byte()?;
+        assert!(status_byte.error_queue() && status_byte.event_summary());
+        assert!(status_byte.request_service());
+        assert!(messenger.query_event_status()?.command_error());
+        assert_eq!(
+            messenger.read_error_queue()?,
+            [InstrumentError::new(-113, "Undefined header")]
+        );
+
+        // An unknown query is never answered, just like on a real instrument.
+        assert!(matches!(
+            messenger.query_with_timeout("MEAS:VOLT?", Duration::from_millis(50)),
+            Err(ScpiError::Timeout)
+        ));
+        messenger.reset()?;
+        assert_eq!(messenger.query_as::<f64>("VOLT?")?, 0.0);
+        assert_eq!(
+            instrument.with_responder(|x| x.get_errors().to_vec()),
+            [InstrumentError::new(-113, "Undefined header")]
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod async_tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::Duration,
+    };
+
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+        net::{TcpListener, TcpStream, UdpSocket},
+        sync::mpsc,
+        task::JoinHandle,
+        time::Instant,
+    };
+
+    use crate::{
+        async_messenger::{AsyncMessenger, AsyncTransport, BoxFuture},
+        block::ByteOrder,
+        connection_options::ConnectionOptions,
+        duty_cycle::DutyCycleMessage,
+        error::ScpiError,
+        networking::NetworkMode,
+    };
+
+    const LOCALHOST: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    const IDN_RESPONSE: &str = "PySCPI,Loopback,0,1.0";
+
+    /// Answers "*IDN?", a block query and a slow "SLOW?", and forwards every received line with its
+    /// arrival time.
+    async fn spawn_async_instrument() -> Result<
+        (
+            u16,
+            mpsc::UnboundedReceiver<(Instant, String)>,
+            JoinHandle<()>,
+        ),
+        ScpiError,
+    > {
+        let listener: TcpListener = TcpListener::bind((LOCALHOST, 0)).await?;
+        let port: u16 = listener.local_addr()?.port();
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let handle: JoinHandle<()> = tokio::spawn(async move {
+            let (stream, _) = listener
+                .accept()
+                .await
+                .expect("Accepting test client failed");
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command: String = line.trim().to_string();
+                let response: Option<&[u8]> = match command.as_str() {
+                    "*IDN?" => Some(b"PySCPI,Loopback,0,1.0\n"),
+                    "CURV?" => Some(b"#16\x01\x00\x02\x00\x03\x00\n"),
+                    "SLOW?" => {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        Some(b"slow\n")
+                    }
+                    _ => None,
+                };
+                if let Some(response) = response {
+                    writer
+                        .write_all(response)
+                        .await
+                        .expect("Writing test response failed");
+                }
+                let _ = sender.send((Instant::now(), command));
+            }
+        });
+
+        Ok((port, receiver, handle))
+    }
+
+    #[tokio::test]
+    async fn test_async_tcp_query_and_block() -> Result<(), ScpiError> {
+        let (port, mut received, handle) = spawn_async_instrument().await?;
+
+        let mut messenger: AsyncMessenger = AsyncMessenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )
+        .await?;
+        assert_eq!(messenger.query("*IDN?").await?, IDN_RESPONSE);
+        assert_eq!(
+            messenger
+                .query_binary_values::<u16>("CURV?", ByteOrder::LittleEndian)
+                .await?,
+            vec![1, 2, 3]
+        );
+        messenger.send_block("DATA", b"abc").await?;
+        drop(messenger);
+
+        handle.await.expect("Instrument panicked");
+        let mut commands: Vec<String> = Vec::new();
+        while let Ok((_, command)) = received.try_recv() {
+            commands.push(command);
+        }
+        assert_eq!(commands, vec!["*IDN?", "CURV?", "DATA #13abc"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_udp_query_timeout() -> Result<(), ScpiError> {
+        let responder: UdpSocket = UdpSocket::bind((LOCALHOST, 0)).await?;
+        let port: u16 = responder.local_addr()?.port();
+
+        let options: ConnectionOptions =
+            ConnectionOptions::new().with_read_timeout(Some(Duration::from_millis(50)));
+        let mut messenger: AsyncMessenger =
+            AsyncMessenger::new(0, port, &LOCALHOST, &NetworkMode::Udp, &options).await?;
+        assert!(matches!(
+            messenger.query("MEAS:VOLT?").await,
+            Err(ScpiError::Timeout)
+        ));
+
+        let mut request: [u8; 64] = [0; 64];
+        let (size, client) = responder.recv_from(&mut request).await?;
+        assert_eq!(&request[..size], b"MEAS:VOLT?\r\n");
+        responder.send_to(b"+3.30000E+00\n", client).await?;
+        assert_eq!(messenger.read_response().await?, "+3.30000E+00");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_cancelled_query() -> Result<(), ScpiError> {
+        let (port, mut received, handle) = spawn_async_instrument().await?;
+        let mut messenger: AsyncMessenger = AsyncMessenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )
+        .await?;
+
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(20), messenger.query("SLOW?")).await;
+        assert!(cancelled.is_err());
+        assert!(matches!(
+            messenger
+                .query_with_timeout("SLOW?", Duration::from_millis(20))
+                .await,
+            Err(ScpiError::Timeout)
+        ));
+
+        // Both late answers have arrived by now and must not answer the next query.
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(messenger.query("*IDN?").await?, IDN_RESPONSE);
+        drop(messenger);
+        handle.await.expect("Instrument panicked");
+
+        let mut commands: Vec<String> = Vec::new();
+        while let Ok((_, command)) = received.try_recv() {
+            commands.push(command);
+        }
+        assert_eq!(commands, vec!["SLOW?", "SLOW?", "*IDN?"]);
+
+        Ok(())
+    }
+
+    /// Writes one byte at a time after a pause, so a query can be dropped halfway through its write.
+    struct TricklingTransport {
+        stream: TcpStream,
+    }
+
+    impl AsyncTransport for TricklingTransport {
+        fn write_bytes<'a>(
+            &'a mut self,
+            bytes: &'a [u8],
+        ) -> BoxFuture<'a, Result<usize, ScpiError>> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                Ok(self.stream.write(&bytes[..1]).await?)
+            })
+        }
+
+        fn read_bytes<'a>(
+            &'a mut self,
+            buffer: &'a mut [u8],
+        ) -> BoxFuture<'a, Result<usize, ScpiError>> {
+            Box::pin(async move { Ok(self.stream.read(buffer).await?) })
+        }
+
+        fn clear(&mut self) -> Result<(), ScpiError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_async_query_cancelled_while_writing() -> Result<(), ScpiError> {
+        let (port, mut received, handle) = spawn_async_instrument().await?;
+        let stream: TcpStream = TcpStream::connect((LOCALHOST, port)).await?;
+        let mut messenger: AsyncMessenger = AsyncMessenger::from_transport(
+            Box::new(TricklingTransport { stream }),
+            &ConnectionOptions::default(),
+        );
+
+        // Each message takes about 70ms to write, so these are dropped with most of it unsent.
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(25), messenger.query("*IDN?")).await;
+        assert!(cancelled.is_err());
+        assert_eq!(messenger.query("SLOW?").await?, "slow");
+
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(25),
+            messenger.query_binary_values::<u16>("CURV?", ByteOrder::LittleEndian),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        assert_eq!(messenger.query("*IDN?").await?, IDN_RESPONSE);
+        drop(messenger);
+        handle.await.expect("Instrument panicked");
+
+        let mut commands: Vec<String> = Vec::new();
+        while let Ok((_, command)) = received.try_recv() {
+            commands.push(command);
+        }
+        assert_eq!(commands, vec!["*IDN?", "SLOW?", "CURV?", "*IDN?"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_async_duty_cycle() -> Result<(), ScpiError> {
+        let (port, mut received, handle) = spawn_async_instrument().await?;
+        let mut messenger: AsyncMessenger = AsyncMessenger::new(
+            0,
+            port,
+            &LOCALHOST,
+            &NetworkMode::Tcp,
+            &ConnectionOptions::default(),
+        )
+        .await?;
+        let message: DutyCycleMessage =
+            DutyCycleMessage::new(20_000, 10_000, "OUTP ON", "OUTP OFF");
+
+        let start: Instant = Instant::now();
+        assert_eq!(
+            messenger
+                .send_duty_cycled_message(&message, Some(3))
+                .await?,
+            3
+        );
+        assert!(start.elapsed() >= Duration::from_millis(90));
+
+        let cancelled = tokio::time::timeout(
+            Duration::from_millis(45),
+            messenger.send_duty_cycled_message(&message, None),
+        )
+        .await;
+        assert!(cancelled.is_err());
+        assert_eq!(messenger.query("*IDN?").await?, IDN_RESPONSE);
+        drop(messenger);
+        handle.await.expect("Instrument panicked");
+
+        let mut commands: Vec<(Instant, String)> = Vec::new();
+        while let Ok(x) = received.try_recv() {
+            commands.push(x);
+        }
+        let expected: Vec<&str> = ["OUTP ON", "OUTP OFF"].repeat(5);
+        let names: Vec<&str> = commands.iter().map(|(_, x)| x.as_str()).collect();
+        assert_eq!(names[..6], expected[..6]);
+        assert_eq!(names.last(), Some(&"*IDN?"));
+        assert!(commands[2].0 - commands[0].0 >= Duration::from_millis(29));
 
         Ok(())
     }
@@ -0,0 +1,505 @@
+/*
+    Copyright 2024 Sebastian Pineda (spineda@wpi.edu)
+
+   Licensed under the Apache License, Version 2.0 (the "License");
+   you may not use this file except in compliance with the License.
+   You may obtain a copy of the License at
+
+       http://www.apache.org/licenses/LICENSE-2.0
+
+   Unless required by applicable law or agreed to in writing, software
+   distributed under the License is distributed on an "AS IS" BASIS,
+   WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+   See the License for the specific language governing permissions and
+   limitations under the License.
+*/
+
+//! VXI-11 (TCP/IP Instrument Protocol) client built on ONC RPC.
+
+use std::{
+    io::{Error, ErrorKind},
+    net::{IpAddr, SocketAddr, TcpListener, TcpStream},
+    time::{Duration, Instant},
+};
+
+use crate::error::ScpiError;
+use crate::onc_rpc::{self, RpcCall, RpcClient, XdrReader, XdrWriter};
+use crate::transport::Transport;
+
+pub const PORTMAPPER_PORT: u16 = 111;
+pub const DEFAULT_DEVICE_NAME: &str = "inst0";
+
+pub(crate) const DEVICE_CORE_PROGRAM: u32 = 0x0006_07AF;
+pub(crate) const DEVICE_CORE_VERSION: u32 = 1;
+
+pub(crate) const CREATE_LINK: u32 = 10;
+pub(crate) const DEVICE_WRITE: u32 = 11;
+pub(crate) const DEVICE_READ: u32 = 12;
+pub(crate) const DEVICE_READ_STB: u32 = 13;
+pub(crate) const DEVICE_TRIGGER: u32 = 14;
+pub(crate) const DEVICE_CLEAR: u32 = 15;
+pub(crate) const DEVICE_REMOTE: u32 = 16;
+pub(crate) const DEVICE_LOCAL: u32 = 17;
+pub(crate) const DEVICE_ENABLE_SRQ: u32 = 20;
+pub(crate) const DESTROY_LINK: u32 = 23;
+pub(crate) const CREATE_INTR_CHAN: u32 = 25;
+pub(crate) const DESTROY_INTR_CHAN: u32 = 26;
+
+pub(crate) const DEVICE_INTR_PROGRAM: u32 = 0x0006_07B1;
+pub(crate) const DEVICE_INTR_VERSION: u32 = 1;
+pub(crate) const DEVICE_INTR_SRQ: u32 = 30;
+const DEVICE_TCP: u32 = 0;
+
+pub(crate) const FLAG_END: i32 = 0x08;
+pub(crate) const FLAG_TERMCHAR_SET: i32 = 0x80;
+
+pub const REASON_REQUEST_COUNT: i32 = 0x01;
+pub const REASON_TERMCHAR: i32 = 0x02;
+pub const REASON_END: i32 = 0x04;
+
+const IO_TIMEOUT_ERROR: i32 = 15;
+const DEFAULT_IO_TIMEOUT_MS: u32 = 10_000;
+const RPC_TIMEOUT_MARGIN: Duration = Duration::from_secs(1);
+const ACCEPT_POLL_INTERVAL: Duration = Duration::from_millis(1);
+// Sockets reject a zero read timeout.
+const MIN_WAIT: Duration = Duration::from_millis(1);
+
+/// A link to one VXI-11 device on an instrument.
+pub struct Vxi11Client {
+    core: RpcClient,
+    link_id: i32,
+    max_receive_size: u32,
+    abort_port: u16,
+    read_io_timeout_ms: u32,
+    write_io_timeout_ms: u32,
+    lock_timeout_ms: u32,
+    term_char: Option<u8>,
+    linked: bool,
+    interrupt: Option<InterruptChannel>,
+}
+
+/// Our end of the interrupt channel: the instrument connects back to `listener` and calls
+/// `device_intr_srq` whenever it requests service.
+struct InterruptChannel {
+    listener: TcpListener,
+    connection: Option<TcpStream>,
+}
+
+impl Vxi11Client {
+    /// Finds the core channel through the portmapper on port 111 and creates a link.
+    pub fn connect(
+        remote_client: &IpAddr,
+        device_name: &str,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Self, ScpiError> {
+        Self::connect_with_portmapper(remote_client, PORTMAPPER_PORT, device_name, connect_timeout)
+    }
+
+    pub fn connect_with_portmapper(
+        remote_client: &IpAddr,
+        portmapper_port: u16,
+        device_name: &str,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Self, ScpiError> {
+        let core_port: u16 = onc_rpc::get_port(
+            SocketAddr::new(*remote_client, portmapper_port),
+            DEVICE_CORE_PROGRAM,
+            DEVICE_CORE_VERSION,
+            connect_timeout,
+        )?;
+
+        Self::connect_to_core(
+            SocketAddr::new(*remote_client, core_port),
+            device_name,
+            connect_timeout,
+        )
+    }
+
+    /// Skips the portmapper and creates a link on an already known core channel.
+    pub fn connect_to_core(
+        core_address: SocketAddr,
+        device_name: &str,
+        connect_timeout: Option<Duration>,
+    ) -> Result<Self, ScpiError> {
+        let mut core: RpcClient = RpcClient::connect(core_address, connect_timeout)?;
+        core.set_timeout(Some(
+            Duration::from_millis(DEFAULT_IO_TIMEOUT_MS as u64) + RPC_TIMEOUT_MARGIN,
+        ))?;
+
+        let client_id: i32 = std::process::id() as i32;
+        let params: Vec<u8> = XdrWriter::new()
+            .put_i32(client_id)
+            .put_bool(false)
+            .put_u32(0)
+            .put_string(device_name)
+            .into_bytes();
+        let results: Vec<u8> = core.call(
+            DEVICE_CORE_PROGRAM,
+            DEVICE_CORE_VERSION,
+            CREATE_LINK,
+            &params,
+        )?;
+
+        let mut reader: XdrReader = XdrReader::new(&results);
+        check_device_error(reader.get_i32()?)?;
+        let link_id: i32 = reader.get_i32()?;
+        let abort_port: u16 = reader.get_u32()? as u16;
+        let max_receive_size: u32 = reader.get_u32()?.max(1);
+
+        Ok(Self {
+            core,
+            link_id,
+            max_receive_size,
+            abort_port,
+            read_io_timeout_ms: DEFAULT_IO_TIMEOUT_MS,
+            write_io_timeout_ms: DEFAULT_IO_TIMEOUT_MS,
+            lock_timeout_ms: 0,
+            term_char: None,
+            linked: true,
+            interrupt: None,
+        })
+    }
+
+    /// Writes `data` in chunks no larger than the device accepts, setting END on the last one.
+    pub fn device_write(&mut self, data: &[u8]) -> Result<usize, ScpiError> {
+        let chunks: Vec<&[u8]> = match data.is_empty() {
+            true => vec![data],
+            false => data.chunks(self.max_receive_size as usize).collect(),
+        };
+        let mut written: usize = 0;
+
+        for (index, chunk) in chunks.iter().enumerate() {
+            let flags: i32 = match index + 1 == chunks.len() {
+                true => FLAG_END,
+                false => 0,
+            };
+            let params: Vec<u8> = XdrWriter::new()
+                .put_i32(self.link_id)
+                .put_u32(self.write_io_timeout_ms)
+                .put_u32(self.lock_timeout_ms)
+                .put_i32(flags)
+                .put_opaque(chunk)
+                .into_bytes();
+            let results: Vec<u8> = self.call(DEVICE_WRITE, &params)?;
+
+            let mut reader: XdrReader = XdrReader::new(&results);
+            check_device_error(reader.get_i32()?)?;
+            written += reader.get_u32()? as usize;
+        }
+
+        Ok(written)
+    }
+
+    /// Reads up to `request_size` bytes, returning the data and the `REASON_*` bits.
+    pub fn device_read(&mut self, request_size: u32) -> Result<(Vec<u8>, i32), ScpiError> {
+        let (flags, term_char): (i32, u8) = match self.term_char {
+            Some(x) => (FLAG_TERMCHAR_SET, x),
+            None => (0, 0),
+        };
+        let params: Vec<u8> = XdrWriter::new()
+            .put_i32(self.link_id)
+            .put_u32(request_size)
+            .put_u32(self.read_io_timeout_ms)
+            .put_u32(self.lock_timeout_ms)
+            .put_i32(flags)
+            .put_u32(term_char as u32)
+            .into_bytes();
+        let results: Vec<u8> = self.call(DEVICE_READ, &params)?;
+
+        let mut reader: XdrReader = XdrReader::new(&results);
+        check_device_error(reader.get_i32()?)?;
+        let reason: i32 = reader.get_i32()?;
+        let data: Vec<u8> = reader.get_opaque()?;
+        Ok((data, reason))
+    }
+
+    pub fn device_read_stb(&mut self) -> Result<u8, ScpiError> {
+        let results: Vec<u8> = self.generic_call(DEVICE_READ_STB)?;
+
+        let mut reader: XdrReader = XdrReader::new(&results);
+        check_device_error(reader.get_i32()?)?;
+        Ok(reader.get_u32()? as u8)
+    }
+
+    pub fn device_trigger(&mut self) -> Result<(), ScpiError> {
+        let results: Vec<u8> = self.generic_call(DEVICE_TRIGGER)?;
+        check_device_error(XdrReader::new(&results).get_i32()?)
+    }
+
+    pub fn device_clear(&mut self) -> Result<(), ScpiError> {
+        let results: Vec<u8> = self.generic_call(DEVICE_CLEAR)?;
+        check_device_error(XdrReader::new(&results).get_i32()?)
+    }
+
+    pub fn device_remote(&mut self) -> Result<(), ScpiError> {
+        let results: Vec<u8> = self.generic_call(DEVICE_REMOTE)?;
+        check_device_error(XdrReader::new(&results).get_i32()?)
+    }
+
+    pub fn device_local(&mut self) -> Result<(), ScpiError> {
+        let results: Vec<u8> = self.generic_call(DEVICE_LOCAL)?;
+        check_device_error(XdrReader::new(&results).get_i32()?)
+    }
+
+    /// Turns service requests on the interrupt channel on or off, opening the channel first if
+    /// needed. The instrument must be able to connect back to the address of the core channel.
+    /// Servers without interrupt channels give `ScpiError::Unsupported`.
+    pub fn enable_srq(&mut self, enable: bool) -> Result<(), ScpiError> {
+        if enable && self.interrupt.is_none() {
+            self.create_interrupt_channel()?;
+        }
+
+        let params: Vec<u8> = XdrWriter::new()
+            .put_i32(self.link_id)
+            .put_bool(enable)
+            .put_opaque(&self.link_id.to_be_bytes())
+            .into_bytes();
+        let result: Result<(), ScpiError> = self
+            .call(DEVICE_ENABLE_SRQ, &params)
+            .and_then(|x| check_device_error(XdrReader::new(&x).get_i32()?));
+        // A channel that never carries service requests would make every wait time out.
+        if enable && result.is_err() && self.interrupt.take().is_some() {
+            let _ = self.call(DESTROY_INTR_CHAN, &[]);
+        }
+        result
+    }
+
+    /// Waits for a `device_intr_srq` call, enabling service requests on first use. Returns false if
+    /// none arrived within `timeout`.
+    pub fn wait_for_service_request(&mut self, timeout: Duration) -> Result<bool, ScpiError> {
+        if self.interrupt.is_none() {
+            self.enable_srq(true)?;
+        }
+
+        let deadline: Instant = Instant::now() + timeout;
+        let interrupt: &mut InterruptChannel = match self.interrupt.as_mut() {
+            Some(x) => x,
+            None => return Ok(false),
+        };
+
+        loop {
+            let connection: &mut TcpStream = match interrupt.connection {
+                Some(ref mut x) => x,
+                None => match accept_until(&interrupt.listener, deadline)? {
+                    Some(x) => interrupt.connection.insert(x),
+                    None => return Ok(false),
+                },
+            };
+
+            let remaining: Duration = deadline.saturating_duration_since(Instant::now());
+            connection.set_read_timeout(Some(remaining.max(MIN_WAIT)))?;
+            // device_intr_srq is a one-way call, so it is never answered.
+            match onc_rpc::read_call(connection) {
+                Ok(x) if is_service_request(&x, self.link_id) => return Ok(true),
+                Ok(_) => continue,
+                Err(ScpiError::Timeout) => return Ok(false),
+                // The instrument may open a new connection for the next request.
+                Err(_) => interrupt.connection = None,
+            }
+        }
+    }
+
+    /// Closes the link. Also attempted on drop if not called explicitly.
+    pub fn destroy_link(&mut self) -> Result<(), ScpiError> {
+        if !self.linked {
+            return Ok(());
+        }
+
+        self.linked = false;
+        let interrupt_result: Result<(), ScpiError> = match self.interrupt.take() {
+            Some(_) => self
+                .call(DESTROY_INTR_CHAN, &[])
+                .and_then(|x| check_device_error(XdrReader::new(&x).get_i32()?)),
+            None => Ok(()),
+        };
+
+        let params: Vec<u8> = XdrWriter::new().put_i32(self.link_id).into_bytes();
+        let results: Vec<u8> = self.call(DESTROY_LINK, &params)?;
+        check_device_error(XdrReader::new(&results).get_i32()?)?;
+        interrupt_result
+    }
+
+    /// Makes the device stop a read at `term_char` instead of only at END.
+    pub fn set_term_char(&mut self, term_char: Option<u8>) {
+        self.term_char = term_char;
+    }
+
+    pub fn set_lock_timeout(&mut self, lock_timeout: Duration) {
+        self.lock_timeout_ms = duration_to_ms(Some(lock_timeout));
+    }
+
+    fn create_interrupt_channel(&mut self) -> Result<(), ScpiError> {
+        let host: IpAddr = self.core.local_addr()?.ip();
+        let host_address: u32 = match host {
+            IpAddr::V4(x) => u32::from(x),
+            IpAddr::V6(_) => {
+                return Err(ScpiError::Unsupported(
+                    "VXI-11 interrupt channels require IPv4",
+                ))
+            }
+        };
+        let listener: TcpListener = TcpListener::bind((host, 0))?;
+        listener.set_nonblocking(true)?;
+
+        let params: Vec<u8> = XdrWriter::new()
+            .put_u32(host_address)
+            .put_u32(listener.local_addr()?.port() as u32)
+            .put_u32(DEVICE_INTR_PROGRAM)
+            .put_u32(DEVICE_INTR_VERSION)
+            .put_u32(DEVICE_TCP)
+            .into_bytes();
+        // Servers without interrupt channels refuse this in different ways, either as a device
+        // error or by not implementing the procedure at all.
+        self.call(CREATE_INTR_CHAN, &params)
+            .and_then(|x| check_device_error(XdrReader::new(&x).get_i32()?))
+            .map_err(|_| {
+                ScpiError::Unsupported("The VXI-11 server did not open an interrupt channel")
+            })?;
+
+        self.interrupt = Some(InterruptChannel {
+            listener,
+            connection: None,
+        });
+        Ok(())
+    }
+
+    fn generic_call(&mut self, procedure: u32) -> Result<Vec<u8>, ScpiError> {
+        let params: Vec<u8> = XdrWriter::new()
+            .put_i32(self.link_id)
+            .put_i32(0)
+            .put_u32(self.lock_timeout_ms)
+            .put_u32(self.read_io_timeout_ms)
+            .into_bytes();
+        self.call(procedure, &params)
+    }
+
+    fn call(&mut self, procedure: u32, params: &[u8]) -> Result<Vec<u8>, ScpiError> {
+        self.core
+            .call(DEVICE_CORE_PROGRAM, DEVICE_CORE_VERSION, procedure, params)
+    }
+
+    /* ****************************************************************************************** */
+    /*                                   Boilerplate Getters                                      */
+    /* ****************************************************************************************** */
+
+    pub fn get_link_id(&self) -> i32 {
+        self.link_id
+    }
+
+    pub fn get_max_receive_size(&self) -> u32 {
+        self.max_receive_size
+    }
+
+    pub fn get_abort_port(&self) -> u16 {
+        self.abort_port
+    }
+}
+
+impl Transport for Vxi11Client {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<usize, ScpiError> {
+        self.device_write(bytes)
+    }
+
+    fn read_bytes(&mut self, buffer: &mut [u8]) -> Result<usize, ScpiError> {
+        let request_size: u32 = u32::try_from(buffer.len()).unwrap_or(u32::MAX);
+        let (data, _): (Vec<u8>, i32) = self.device_read(request_size)?;
+        let received: usize = data.len().min(buffer.len());
+        buffer[..received].copy_from_slice(&data[..received]);
+        Ok(received)
+    }
+
+    fn set_timeout(
+        &mut self,
+        read_timeout: Option<Duration>,
+        write_timeout: Option<Duration>,
+    ) -> Result<(), ScpiError> {
+        self.read_io_timeout_ms = duration_to_ms(read_timeout);
+        self.write_io_timeout_ms = duration_to_ms(write_timeout);
+
+        let rpc_timeout: Option<Duration> = match (read_timeout, write_timeout) {
+            (Some(read), Some(write)) => Some(read.max(write) + RPC_TIMEOUT_MARGIN),
+            _ => None,
+        };
+        self.core.set_timeout(rpc_timeout)
+    }
+
+    fn flush(&mut self) -> Result<(), ScpiError> {
+        Ok(())
+    }
+
+    fn clear(&mut self) -> Result<(), ScpiError> {
+        self.device_clear()
+    }
+
+    fn wait_for_service_request(&mut self, timeout: Duration) -> Result<bool, ScpiError> {
+        Vxi11Client::wait_for_service_request(self, timeout)
+    }
+}
+
+impl Drop for Vxi11Client {
+    fn drop(&mut self) {
+        let _ = self.destroy_link();
+    }
+}
+
+fn duration_to_ms(duration: Option<Duration>) -> u32 {
+    match duration {
+        Some(x) => u32::try_from(x.as_millis()).unwrap_or(u32::MAX),
+        None => u32::MAX,
+    }
+}
+
+/// Whether `call` is a `device_intr_srq` carrying the handle `enable_srq` registered for `link_id`.
+fn is_service_request(call: &RpcCall, link_id: i32) -> bool {
+    let handle: Option<Vec<u8>> = XdrReader::new(&call.params).get_opaque().ok();
+    call.program == DEVICE_INTR_PROGRAM
+        && call.procedure == DEVICE_INTR_SRQ
+        && handle.as_deref() == Some(link_id.to_be_bytes().as_slice())
+}
+
+/// Accepts one connection on a non-blocking `listener`, giving up at `deadline`.
+fn accept_until(listener: &TcpListener, deadline: Instant) -> Result<Option<TcpStream>, ScpiError> {
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                return Ok(Some(stream));
+            }
+            Err(x) if x.kind() == ErrorKind::WouldBlock => match Instant::now() >= deadline {
+                true => return Ok(None),
+                false => std::thread::sleep(ACCEPT_POLL_INTERVAL),
+            },
+            Err(x) => return Err(x.into()),
+        }
+    }
+}
+
+fn check_device_error(code: i32) -> Result<(), ScpiError> {
+    let description: &str = match code {
+        0 => return Ok(()),
+        IO_TIMEOUT_ERROR => return Err(ScpiError::Timeout),
+        1 => "syntax error",
+        3 => "device not accessible",
+        4 => "invalid link identifier",
+        5 => "parameter error",
+        6 => "channel not established",
+        8 => {
+            return Err(ScpiError::Unsupported(
+                "The VXI-11 server does not support this operation",
+            ))
+        }
+        9 => "out of resources",
+        11 => "device locked by another link",
+        12 => "no lock held by this link",
+        17 => "I/O error",
+        21 => "invalid address",
+        23 => "abort",
+        29 => "channel already established",
+        _ => "unknown error",
+    };
+
+    Err(ScpiError::Connection(Error::other(format!(
+        "VXI-11 device error {}: {}",
+        code, description
+    ))))
+}